@@ -0,0 +1,76 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn decode_byte(byte: u8) -> Option<u32> {
+    match byte {
+        b'A'..=b'Z' => Some((byte - b'A') as u32),
+        b'a'..=b'z' => Some((byte - b'a' + 26) as u32),
+        b'0'..=b'9' => Some((byte - b'0' + 52) as u32),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    }
+}
+
+/// Decodes standard or URL-safe base64, with or without padding.
+pub(crate) fn decode(input: &str) -> Option<Vec<u8>> {
+    let input = input.trim().trim_end_matches('=').as_bytes();
+    if input.len() % 4 == 1 {
+        return None;
+    }
+    let mut output = Vec::with_capacity(input.len() * 3 / 4);
+    for group in input.chunks(4) {
+        let mut bits = 0;
+        for &byte in group {
+            bits = bits << 6 | decode_byte(byte)?;
+        }
+        bits <<= 6 * (4 - group.len());
+        let bytes = bits.to_be_bytes();
+        output.extend_from_slice(&bytes[1..group.len()]);
+    }
+    Some(output)
+}
+
+/// Encodes standard base64, with padding.
+pub(crate) fn encode<T: AsRef<[u8]>>(input: T) -> String {
+    let input = input.as_ref();
+    let mut output = String::with_capacity(input.len().div_ceil(3) * 4);
+    for group in input.chunks(3) {
+        let mut bytes = [0; 3];
+        bytes[..group.len()].copy_from_slice(group);
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= group.len() {
+                output.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode};
+
+    #[test]
+    fn decodes_with_and_without_padding() {
+        assert_eq!(decode("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode("aGVsbG8").unwrap(), b"hello");
+        assert_eq!(decode("aGVsbG8h").unwrap(), b"hello!");
+        assert_eq!(decode("").unwrap(), b"");
+        assert_eq!(decode("a"), None);
+        assert_eq!(decode("aG*s"), None);
+    }
+
+    #[test]
+    fn encodes_with_padding() {
+        assert_eq!(encode("hello"), "aGVsbG8=");
+        assert_eq!(encode("hello!"), "aGVsbG8h");
+        assert_eq!(encode("hi"), "aGk=");
+        assert_eq!(encode(""), "");
+    }
+}
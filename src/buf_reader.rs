@@ -1,6 +1,6 @@
 use alloc::boxed::Box;
 use core::cmp;
-use embedded_io_async::{BufRead, ErrorType, Read};
+use embedded_io_async::{BufRead, ErrorType, Read, Write};
 
 /// The `BufReader` struct adds buffering to any reader.
 ///
@@ -39,6 +39,18 @@ impl<R: Read> BufReader<R> {
         }
     }
 
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    ///
+    /// It is inadvisable to directly read from the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
     /// Returns a reference to the internally buffered data.
     ///
     /// Unlike `fill_buf`, this will not attempt to fill the buffer if it is empty.
@@ -86,3 +98,15 @@ impl<R: Read> BufRead for BufReader<R> {
         self.pos = cmp::min(self.pos + amt, self.cap);
     }
 }
+
+/// Writes go straight to the underlying stream, eg. for protocols
+/// which continue on the connection after an HTTP response.
+impl<R: Read + Write> Write for BufReader<R> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.inner.write(buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.inner.flush().await
+    }
+}
@@ -0,0 +1,99 @@
+use core::mem::MaybeUninit;
+use esp_idf_sys::*;
+
+/// A running SHA-256 hash, computed by mbedtls with the hardware
+/// accelerator where available.
+pub(crate) struct Sha256(mbedtls_sha256_context);
+
+impl Sha256 {
+    pub(crate) fn new() -> Sha256 {
+        let mut ctx = MaybeUninit::uninit();
+        unsafe {
+            mbedtls_sha256_init(ctx.as_mut_ptr());
+            mbedtls_sha256_starts(ctx.as_mut_ptr(), 0);
+            Sha256(ctx.assume_init())
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        unsafe { mbedtls_sha256_update(&mut self.0, data.as_ptr(), data.len()) };
+    }
+
+    pub(crate) fn finish(mut self) -> [u8; 32] {
+        let mut output = [0; 32];
+        unsafe { mbedtls_sha256_finish(&mut self.0, output.as_mut_ptr()) };
+        output
+    }
+}
+
+impl Drop for Sha256 {
+    fn drop(&mut self) {
+        unsafe { mbedtls_sha256_free(&mut self.0) };
+    }
+}
+
+/// A running MD5 hash, only for checking `Content-MD5` headers.
+pub(crate) struct Md5(mbedtls_md5_context);
+
+impl Md5 {
+    pub(crate) fn new() -> Md5 {
+        let mut ctx = MaybeUninit::uninit();
+        unsafe {
+            mbedtls_md5_init(ctx.as_mut_ptr());
+            mbedtls_md5_starts(ctx.as_mut_ptr());
+            Md5(ctx.assume_init())
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        unsafe { mbedtls_md5_update(&mut self.0, data.as_ptr(), data.len()) };
+    }
+
+    pub(crate) fn finish(mut self) -> [u8; 16] {
+        let mut output = [0; 16];
+        unsafe { mbedtls_md5_finish(&mut self.0, output.as_mut_ptr()) };
+        output
+    }
+}
+
+impl Drop for Md5 {
+    fn drop(&mut self) {
+        unsafe { mbedtls_md5_free(&mut self.0) };
+    }
+}
+
+/// A running SHA-1 hash, only for the WebSocket handshake.
+pub(crate) struct Sha1(mbedtls_sha1_context);
+
+impl Sha1 {
+    pub(crate) fn new() -> Sha1 {
+        let mut ctx = MaybeUninit::uninit();
+        unsafe {
+            mbedtls_sha1_init(ctx.as_mut_ptr());
+            mbedtls_sha1_starts(ctx.as_mut_ptr());
+            Sha1(ctx.assume_init())
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        unsafe { mbedtls_sha1_update(&mut self.0, data.as_ptr(), data.len()) };
+    }
+
+    pub(crate) fn finish(mut self) -> [u8; 20] {
+        let mut output = [0; 20];
+        unsafe { mbedtls_sha1_finish(&mut self.0, output.as_mut_ptr()) };
+        output
+    }
+}
+
+impl Drop for Sha1 {
+    fn drop(&mut self) {
+        unsafe { mbedtls_sha1_free(&mut self.0) };
+    }
+}
+
+/// Updates a CRC-32 (IEEE 802.3) checksum with `data`. Start with
+/// `crc` 0.
+pub(crate) fn crc32(crc: u32, data: &[u8]) -> u32 {
+    unsafe { esp_rom_crc32_le(crc, data.as_ptr(), data.len() as u32) }
+}
@@ -0,0 +1,620 @@
+use crate::http::headers::{self, cache_control, CacheControl};
+use crate::http::{Error, Method, Request, Response};
+use crate::tcp::{ConnectionInfo, HttpConnect};
+use crate::timer;
+use alloc::collections::btree_map::BTreeMap as HashMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// Status codes which are cacheable by default, see [RFC 9110 section
+/// 15.1](https://datatracker.ietf.org/doc/html/rfc9110#section-15.1).
+const CACHEABLE_STATUSES: [i32; 10] = [200, 203, 204, 300, 301, 404, 405, 410, 414, 501];
+
+/// Heuristic freshness is capped at one day.
+const MAX_HEURISTIC_LIFETIME: u64 = 24 * 60 * 60;
+
+const ENTRY_FORMAT_VERSION: u8 = 1;
+
+/// A response stored in a [`CacheStore`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CacheEntry {
+    /// The URL of the cached resource, without the fragment.
+    pub url: String,
+    /// The status code of the response, eg. 200.
+    pub status_code: i32,
+    /// The reason phrase of the response, eg. "OK".
+    pub reason_phrase: String,
+    /// The headers of the response. The header field names (the
+    /// keys) are all lowercase.
+    pub headers: HashMap<String, String>,
+    /// The values of the request headers named by the response's
+    /// `Vary` header, keyed by their lowercase names. Headers which
+    /// were missing from the request are missing here too.
+    pub vary: HashMap<String, String>,
+    /// When the response was received or last revalidated, in seconds
+    /// since the Unix epoch, or 0 if the system clock was not set.
+    pub stored_at: u64,
+    /// The body of the response.
+    pub body: Vec<u8>,
+}
+
+impl CacheEntry {
+    fn from_response(
+        url: String,
+        response: &Response,
+        request_headers: &HashMap<String, String>,
+        now: Option<u64>,
+    ) -> Option<CacheEntry> {
+        if !CACHEABLE_STATUSES.contains(&response.status_code) {
+            return None;
+        }
+        let cache_control = cache_control(&response.headers);
+        if cache_control.no_store {
+            return None;
+        }
+
+        let mut vary = HashMap::new();
+        if let Some(names) = response.headers.get("vary") {
+            for name in names.split(',').map(str::trim) {
+                if name == "*" {
+                    return None;
+                }
+                let name = name.to_ascii_lowercase();
+                if let Some(value) = find_header(request_headers, &name) {
+                    vary.insert(name, value.clone());
+                }
+            }
+        }
+
+        let has_validators =
+            response.headers.contains_key("etag") || response.headers.contains_key("last-modified");
+        let has_lifetime = freshness_lifetime(&response.headers, &cache_control, now).is_some();
+        if !has_validators && !(has_lifetime && now.is_some()) {
+            // Could never be used again without downloading it anyway.
+            return None;
+        }
+
+        Some(CacheEntry {
+            url,
+            status_code: response.status_code,
+            reason_phrase: response.reason_phrase.clone(),
+            headers: response.headers.clone(),
+            vary,
+            stored_at: now.unwrap_or(0),
+            body: response.as_bytes().to_vec(),
+        })
+    }
+
+    fn matches_vary(&self, request_headers: &HashMap<String, String>) -> bool {
+        let vary = match self.headers.get("vary") {
+            Some(vary) => vary,
+            None => return true,
+        };
+        vary.split(',').map(str::trim).all(|name| {
+            let name = name.to_ascii_lowercase();
+            self.vary.get(&name) == find_header(request_headers, &name)
+        })
+    }
+
+    fn age(&self, now: u64) -> u64 {
+        let initial_age = self
+            .headers
+            .get("age")
+            .and_then(|age| age.trim().parse::<u64>().ok())
+            .unwrap_or(0);
+        initial_age + now.saturating_sub(self.stored_at)
+    }
+
+    fn is_fresh(&self, now: Option<u64>, max_age: Option<u64>) -> bool {
+        let now = match now {
+            Some(now) if self.stored_at != 0 => now,
+            _ => return false,
+        };
+        let cache_control = cache_control(&self.headers);
+        if cache_control.no_cache {
+            return false;
+        }
+        match freshness_lifetime(&self.headers, &cache_control, Some(self.stored_at)) {
+            Some(lifetime) => self.age(now) < max_age.map_or(lifetime, |max| lifetime.min(max)),
+            None => false,
+        }
+    }
+
+    fn to_response(&self, now: Option<u64>) -> Response {
+        let mut headers = self.headers.clone();
+        if let Some(now) = now.filter(|_| self.stored_at != 0) {
+            headers.insert("age".to_string(), self.age(now).to_string());
+        }
+        Response {
+            status_code: self.status_code,
+            reason_phrase: self.reason_phrase.clone(),
+            headers,
+            trailers: HashMap::new(),
+            url: self.url.clone(),
+            redirects: Vec::new(),
+            connection: ConnectionInfo::default(),
+            informational: Vec::new(),
+            body: self.body.clone(),
+        }
+    }
+
+    /// Encodes the entry into a compact binary form, for storing it
+    /// in flash.
+    pub fn encode(&self) -> Vec<u8> {
+        fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+            out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            out.extend_from_slice(bytes);
+        }
+        fn put_map(out: &mut Vec<u8>, map: &HashMap<String, String>) {
+            out.extend_from_slice(&(map.len() as u32).to_le_bytes());
+            for (key, value) in map {
+                put_bytes(out, key.as_bytes());
+                put_bytes(out, value.as_bytes());
+            }
+        }
+
+        let mut out = Vec::with_capacity(64 + self.body.len());
+        out.push(ENTRY_FORMAT_VERSION);
+        out.extend_from_slice(&self.stored_at.to_le_bytes());
+        out.extend_from_slice(&self.status_code.to_le_bytes());
+        put_bytes(&mut out, self.url.as_bytes());
+        put_bytes(&mut out, self.reason_phrase.as_bytes());
+        put_map(&mut out, &self.headers);
+        put_map(&mut out, &self.vary);
+        put_bytes(&mut out, &self.body);
+        out
+    }
+
+    /// Decodes an entry encoded with [`encode`](#method.encode).
+    /// Returns `None` if the data is corrupted or was written by an
+    /// incompatible version.
+    pub fn decode(bytes: &[u8]) -> Option<CacheEntry> {
+        struct Decoder<'a>(&'a [u8]);
+
+        impl<'a> Decoder<'a> {
+            fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+                if self.0.len() < len {
+                    return None;
+                }
+                let (taken, rest) = self.0.split_at(len);
+                self.0 = rest;
+                Some(taken)
+            }
+            fn u32(&mut self) -> Option<u32> {
+                Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+            }
+            fn bytes(&mut self) -> Option<&'a [u8]> {
+                let len = self.u32()? as usize;
+                self.take(len)
+            }
+            fn string(&mut self) -> Option<String> {
+                String::from_utf8(self.bytes()?.to_vec()).ok()
+            }
+            fn map(&mut self) -> Option<HashMap<String, String>> {
+                let mut map = HashMap::new();
+                for _ in 0..self.u32()? {
+                    map.insert(self.string()?, self.string()?);
+                }
+                Some(map)
+            }
+        }
+
+        let mut decoder = Decoder(bytes);
+        if decoder.take(1)? != [ENTRY_FORMAT_VERSION] {
+            return None;
+        }
+        let stored_at = u64::from_le_bytes(decoder.take(8)?.try_into().ok()?);
+        let status_code = i32::from_le_bytes(decoder.take(4)?.try_into().ok()?);
+        let entry = CacheEntry {
+            url: decoder.string()?,
+            status_code,
+            reason_phrase: decoder.string()?,
+            headers: decoder.map()?,
+            vary: decoder.map()?,
+            stored_at,
+            body: decoder.bytes()?.to_vec(),
+        };
+        if decoder.0.is_empty() {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+}
+
+/// Storage for the responses kept by an [`HttpCache`].
+///
+/// Implementations are free to drop entries at any time, for example
+/// to stay within a size budget.
+pub trait CacheStore {
+    /// Returns the entry stored for the given URL, if any.
+    fn get(&mut self, url: &str) -> Option<CacheEntry>;
+    /// Stores an entry for the given URL, replacing any previous one.
+    fn put(&mut self, url: &str, entry: CacheEntry);
+    /// Removes the entry stored for the given URL, if any.
+    fn remove(&mut self, url: &str);
+}
+
+/// A [`CacheStore`] which keeps entries in RAM, evicting the oldest
+/// entry when full.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MemoryCacheStore {
+    entries: HashMap<String, CacheEntry>,
+    max_entries: usize,
+}
+
+impl MemoryCacheStore {
+    /// Creates a new store holding at most `max_entries` responses.
+    pub fn new(max_entries: usize) -> MemoryCacheStore {
+        MemoryCacheStore {
+            entries: HashMap::new(),
+            max_entries,
+        }
+    }
+}
+
+impl CacheStore for MemoryCacheStore {
+    fn get(&mut self, url: &str) -> Option<CacheEntry> {
+        self.entries.get(url).cloned()
+    }
+
+    fn put(&mut self, url: &str, entry: CacheEntry) {
+        if self.max_entries == 0 {
+            return;
+        }
+        if !self.entries.contains_key(url) && self.entries.len() >= self.max_entries {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.stored_at)
+                .map(|(url, _)| url.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(url.to_string(), entry);
+    }
+
+    fn remove(&mut self, url: &str) {
+        self.entries.remove(url);
+    }
+}
+
+/// A key-value storage for binary blobs, such as an NVS namespace or a
+/// directory on a filesystem.
+///
+/// Keys are at most 15 ASCII characters long, so they fit the NVS key
+/// length limit.
+pub trait BlobStorage {
+    /// The error returned by the storage.
+    type Error: core::fmt::Debug;
+
+    /// Reads the blob stored under `key`, or `None` if there is none.
+    fn read_blob(&mut self, key: &str) -> Result<Option<Vec<u8>>, Self::Error>;
+    /// Writes a blob under `key`, replacing any previous one.
+    fn write_blob(&mut self, key: &str, data: &[u8]) -> Result<(), Self::Error>;
+    /// Removes the blob stored under `key`, if any.
+    fn remove_blob(&mut self, key: &str) -> Result<(), Self::Error>;
+}
+
+/// A [`CacheStore`] which persists entries in a [`BlobStorage`], so
+/// they survive a reboot.
+///
+/// Each entry is stored as one blob, encoded with
+/// [`CacheEntry::encode`]. Storage errors are logged and otherwise
+/// treated as cache misses.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BlobCacheStore<B> {
+    storage: B,
+}
+
+impl<B: BlobStorage> BlobCacheStore<B> {
+    /// Creates a new store on top of the given storage.
+    pub fn new(storage: B) -> BlobCacheStore<B> {
+        BlobCacheStore { storage }
+    }
+
+    /// Returns the underlying storage.
+    pub fn into_inner(self) -> B {
+        self.storage
+    }
+
+    /// Returns the blob key for a URL: a 15 character hash, as URLs
+    /// are usually too long to be used as keys directly.
+    fn key(url: &str) -> String {
+        // 64-bit FNV-1a
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in url.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        format!("c{:014x}", hash >> 8)
+    }
+}
+
+impl<B: BlobStorage> CacheStore for BlobCacheStore<B> {
+    fn get(&mut self, url: &str) -> Option<CacheEntry> {
+        match self.storage.read_blob(&Self::key(url)) {
+            // The URL is stored in the entry, which guards against hash collisions.
+            Ok(blob) => blob
+                .and_then(|blob| CacheEntry::decode(&blob))
+                .filter(|entry| entry.url == url),
+            Err(err) => {
+                log::warn!("Could not read cache entry for {}: {:?}", url, err);
+                None
+            }
+        }
+    }
+
+    fn put(&mut self, url: &str, entry: CacheEntry) {
+        if let Err(err) = self.storage.write_blob(&Self::key(url), &entry.encode()) {
+            log::warn!("Could not write cache entry for {}: {:?}", url, err);
+        }
+    }
+
+    fn remove(&mut self, url: &str) {
+        if let Err(err) = self.storage.remove_blob(&Self::key(url)) {
+            log::warn!("Could not remove cache entry for {}: {:?}", url, err);
+        }
+    }
+}
+
+/// A private HTTP cache, following [RFC
+/// 9111](https://datatracker.ietf.org/doc/html/rfc9111).
+///
+/// `GET` responses are stored according to their `Cache-Control`,
+/// `Expires` and `Vary` headers. Fresh responses are served without
+/// contacting the server. Stale responses with an `ETag` or
+/// `Last-Modified` header are revalidated with `If-None-Match` or
+/// `If-Modified-Since`, so a `304 Not Modified` answer doesn't
+/// download the body again.
+///
+/// Freshness is computed with the system clock: until it is set (see
+/// [`timer::unix_time`](crate::timer::unix_time)), every cached
+/// response is revalidated before use.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn main() -> Result<(), esp_minreq::Error> {
+/// let mut cache = esp_minreq::HttpCache::new(esp_minreq::MemoryCacheStore::new(8));
+/// let request = esp_minreq::get("http://example.com/config.json");
+/// let response = cache.send::<esp_minreq::tcp::HttpStream>(request).await?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct HttpCache<S> {
+    store: S,
+    max_entry_size: Option<usize>,
+}
+
+impl<S: CacheStore> HttpCache<S> {
+    /// Creates a new cache keeping its entries in `store`.
+    pub fn new(store: S) -> HttpCache<S> {
+        HttpCache {
+            store,
+            max_entry_size: None,
+        }
+    }
+
+    /// Sets the maximum body size of responses that are cached, in
+    /// bytes. Larger responses are passed through without being
+    /// stored. `None`, the default, caches responses of any size.
+    pub fn with_max_entry_size<T: Into<Option<usize>>>(mut self, max_entry_size: T) -> Self {
+        self.max_entry_size = max_entry_size.into();
+        self
+    }
+
+    /// Returns a mutable reference to the underlying store.
+    pub fn store_mut(&mut self) -> &mut S {
+        &mut self.store
+    }
+
+    /// Returns the underlying store.
+    pub fn into_store(self) -> S {
+        self.store
+    }
+
+    /// Sends the request, answering it from the cache when possible.
+    ///
+    /// Successful requests with unsafe methods, such as `POST`,
+    /// invalidate the cached response for their URL.
+    ///
+    /// # Errors
+    ///
+    /// See [`Request::send`](struct.Request.html#method.send).
+    pub async fn send<C: HttpConnect>(&mut self, mut request: Request) -> Result<Response, Error>
+    where
+        Error: From<C::Error>,
+    {
+        request = request.keep_headers(&[
+            "cache-control",
+            "vary",
+            "etag",
+            "last-modified",
+            "date",
+            "age",
+            "expires",
+        ]);
+        let mut key = String::new();
+        let url = request.parse_url()?;
+        url.write_base_url_to(&mut key).unwrap();
+        key.push_str(&url.path_and_query);
+
+        if request.method != Method::Get {
+            let is_safe = matches!(
+                request.method,
+                Method::Head | Method::Options | Method::Trace
+            );
+            let response = request.send::<C>().await?;
+            if !is_safe && (200..400).contains(&response.status_code) {
+                self.store.remove(&key);
+            }
+            return Ok(response);
+        }
+
+        let request_cache_control = find_header(&request.headers, "cache-control")
+            .map(|value| CacheControl::parse(value))
+            .unwrap_or_default();
+        if request_cache_control.no_store {
+            return request.send::<C>().await;
+        }
+
+        let now = timer::unix_time();
+        let entry = self
+            .store
+            .get(&key)
+            .filter(|entry| entry.matches_vary(&request.headers));
+        if let Some(ref entry) = entry {
+            if !request_cache_control.no_cache && entry.is_fresh(now, request_cache_control.max_age)
+            {
+                log::debug!("Serving {} from the cache.", key);
+                return Ok(entry.to_response(now));
+            }
+            if let Some(etag) = entry.headers.get("etag") {
+                if find_header(&request.headers, "if-none-match").is_none() {
+                    request
+                        .headers
+                        .insert("If-None-Match".to_string(), etag.clone());
+                }
+            }
+            if let Some(last_modified) = entry.headers.get("last-modified") {
+                if find_header(&request.headers, "if-modified-since").is_none() {
+                    request
+                        .headers
+                        .insert("If-Modified-Since".to_string(), last_modified.clone());
+                }
+            }
+        }
+
+        let request_headers = request.headers.clone();
+        let response = request.send::<C>().await?;
+
+        if let (Some(mut entry), 304) = (entry, response.status_code) {
+            log::debug!("Revalidated {} in the cache.", key);
+            for (header, value) in response.headers {
+                if header != "content-length" && header != "transfer-encoding" {
+                    entry.headers.insert(header, value);
+                }
+            }
+            entry.stored_at = now.unwrap_or(0);
+            let response = entry.to_response(now);
+            self.store.put(&key, entry);
+            return Ok(response);
+        }
+
+        let too_large = matches!(self.max_entry_size, Some(max) if response.as_bytes().len() > max);
+        match CacheEntry::from_response(key.clone(), &response, &request_headers, now) {
+            Some(entry) if !too_large => self.store.put(&key, entry),
+            // Keep the old entry around if the server is having issues.
+            _ if response.status_code >= 500 => {}
+            _ => self.store.remove(&key),
+        }
+        Ok(response)
+    }
+}
+
+fn find_header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a String> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+/// Returns for how many seconds a response is fresh after it was
+/// generated, see [RFC 9111 section
+/// 4.2.1](https://datatracker.ietf.org/doc/html/rfc9111#section-4.2.1).
+fn freshness_lifetime(
+    headers: &HashMap<String, String>,
+    cache_control: &CacheControl,
+    received_at: Option<u64>,
+) -> Option<u64> {
+    if let Some(max_age) = cache_control.max_age {
+        return Some(max_age);
+    }
+    // Without a Date header, the time the response was received stands in.
+    let date = headers::http_date(headers, "date").or(received_at);
+    if headers.contains_key("expires") {
+        // Invalid dates, such as "0", mean the response is already expired.
+        let expires = headers::http_date(headers, "expires").unwrap_or(0);
+        return Some(expires.saturating_sub(date?));
+    }
+    let last_modified = headers::http_date(headers, "last-modified")?;
+    Some((date?.saturating_sub(last_modified) / 10).min(MAX_HEURISTIC_LIFETIME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{freshness_lifetime, CacheControl, CacheEntry, HttpCache, MemoryCacheStore};
+    use crate::http::test_support::{block_on, Stream};
+    use alloc::collections::btree_map::BTreeMap as HashMap;
+    use alloc::string::ToString;
+
+    #[test]
+    fn encode_roundtrip() {
+        let mut headers = HashMap::new();
+        headers.insert("etag".to_string(), "\"abc\"".to_string());
+        let mut vary = HashMap::new();
+        vary.insert("accept".to_string(), "application/json".to_string());
+        let entry = CacheEntry {
+            url: "http://example.com/".to_string(),
+            status_code: 200,
+            reason_phrase: "OK".to_string(),
+            headers,
+            vary,
+            stored_at: 1_700_000_000,
+            body: b"{}".to_vec(),
+        };
+        let encoded = entry.encode();
+        assert_eq!(CacheEntry::decode(&encoded), Some(entry));
+        assert_eq!(CacheEntry::decode(&encoded[..encoded.len() - 1]), None);
+    }
+
+    #[test]
+    fn lifetime_from_headers() {
+        let mut headers = HashMap::new();
+        headers.insert(
+            "date".to_string(),
+            "Sun, 06 Nov 1994 08:49:37 GMT".to_string(),
+        );
+        headers.insert(
+            "expires".to_string(),
+            "Sun, 06 Nov 1994 09:49:37 GMT".to_string(),
+        );
+        assert_eq!(
+            freshness_lifetime(&headers, &CacheControl::default(), None),
+            Some(3600)
+        );
+        let cache_control = CacheControl::parse("public, max-age=60");
+        assert_eq!(freshness_lifetime(&headers, &cache_control, None), Some(60));
+
+        headers.remove("date");
+        assert_eq!(
+            freshness_lifetime(&headers, &CacheControl::default(), None),
+            None
+        );
+        assert_eq!(
+            freshness_lifetime(&headers, &CacheControl::default(), Some(784_113_577)),
+            Some(1800)
+        );
+    }
+
+    #[test]
+    fn keeps_validators_through_header_filter() {
+        let mut cache = HttpCache::new(MemoryCacheStore::new(4));
+        let request = || crate::get("http://example.com/").with_header_filter(["x-kept"]);
+        Stream::script(Stream::new(
+            b"HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nX-Other: 1\r\nContent-Length: 2\r\n\r\nhi",
+            64,
+        ));
+        let response = block_on(cache.send::<Stream>(request())).unwrap();
+        assert_eq!(response.headers.get("etag").unwrap(), "\"v1\"");
+        assert!(!response.headers.contains_key("x-other"));
+
+        Stream::script(Stream::new(b"HTTP/1.1 304 Not Modified\r\n\r\n", 64));
+        let response = block_on(cache.send::<Stream>(request())).unwrap();
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.as_bytes(), b"hi");
+    }
+}
@@ -0,0 +1,152 @@
+use crate::http::Error;
+use crate::waker::AtomicWaker;
+use alloc::sync::Arc;
+use core::future::Future;
+use core::pin::pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Poll;
+
+/// A handle for aborting requests from elsewhere, eg. a button press
+/// handler or before entering deep sleep.
+///
+/// Clones of a token share its state, so a clone can be attached to
+/// a request with
+/// [`Request::with_cancellation`](struct.Request.html#method.with_cancellation)
+/// while another is kept for cancelling it. The token is checked
+/// every time the request is polled: while connecting (including the
+/// TLS handshake), sending, waiting for the response, and reading the
+/// body of a [`ResponseLazy`](struct.ResponseLazy.html). Once
+/// cancelled, these fail with
+/// [`Cancelled`](enum.Error.html#variant.Cancelled), and the
+/// connection is closed when the request future or the
+/// `ResponseLazy` is dropped.
+///
+/// Cancelling wakes the task waiting on the request, so it fails
+/// right away even in the middle of a delay, eg. a retry backoff. If
+/// several tasks share a token, only the last one polled is woken;
+/// the others notice on their next poll.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn main() -> Result<(), esp_minreq::Error> {
+/// let token = esp_minreq::CancellationToken::new();
+/// let request = esp_minreq::get("http://example.com").with_cancellation(token.clone());
+/// // In the button handler:
+/// token.cancel();
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct CancellationToken {
+    shared: Arc<Shared>,
+}
+
+#[derive(Default, Debug)]
+struct Shared {
+    cancelled: AtomicBool,
+    /// The task waiting on a request using the token.
+    waker: AtomicWaker,
+}
+
+impl CancellationToken {
+    /// Creates a new token, which is not cancelled.
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Cancels the requests using this token. This can't be undone.
+    pub fn cancel(&self) {
+        self.shared.cancelled.store(true, Ordering::Release);
+        self.shared.waker.wake();
+    }
+
+    /// Returns whether [`cancel`](#method.cancel) has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.cancelled.load(Ordering::Acquire)
+    }
+}
+
+/// Tokens are equal if they are clones of each other.
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+}
+
+impl Eq for CancellationToken {}
+
+/// Runs `future` until it completes, or returns
+/// [`Error::Cancelled`] as soon as `token` is cancelled, dropping the
+/// future.
+pub(crate) async fn cancellable<F: Future>(
+    token: Option<&CancellationToken>,
+    future: F,
+) -> Result<F::Output, Error> {
+    let Some(token) = token else {
+        return Ok(future.await);
+    };
+    let mut future = pin!(future);
+    core::future::poll_fn(|ctx| {
+        token.shared.waker.register(ctx.waker());
+        if token.is_cancelled() {
+            log::debug!("Request cancelled.");
+            return Poll::Ready(Err(Error::Cancelled));
+        }
+        future.as_mut().poll(ctx).map(Ok)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::{cancellable, CancellationToken};
+    use crate::http::test_support::poll_once;
+    use crate::http::Error;
+    use crate::timer;
+    use alloc::sync::Arc;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use core::task::{Context, Poll, Waker};
+    use std::task::Wake;
+
+    struct Flag(AtomicBool);
+
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn cancels_pending_future() {
+        let token = CancellationToken::new();
+        let mut future = pin!(cancellable(Some(&token), core::future::pending::<()>()));
+        assert!(poll_once(future.as_mut()).is_pending());
+
+        token.clone().cancel();
+        assert!(matches!(
+            poll_once(future.as_mut()),
+            Poll::Ready(Err(Error::Cancelled))
+        ));
+    }
+
+    #[test]
+    fn wakes_task_during_delay() {
+        let token = CancellationToken::new();
+        let woken = Arc::new(Flag(AtomicBool::new(false)));
+        let waker = Waker::from(woken.clone());
+        let mut ctx = Context::from_waker(&waker);
+        let mut future = pin!(cancellable(Some(&token), timer::delay_ms(60_000)));
+        assert!(future.as_mut().poll(&mut ctx).is_pending());
+
+        token.cancel();
+        assert!(woken.0.load(Ordering::SeqCst));
+        assert!(matches!(
+            future.as_mut().poll(&mut ctx),
+            Poll::Ready(Err(Error::Cancelled))
+        ));
+    }
+}
@@ -0,0 +1,151 @@
+use crate::http::{Error, Request, Response};
+use crate::tcp::HttpConnect;
+use crate::timer;
+
+/// The wall-clock time learned from the `Date` header of a response,
+/// see [`sync_clock`](fn.sync_clock.html).
+///
+/// The `Date` header only has a resolution of one second, and the
+/// server may have generated it at any point during the round trip,
+/// so the estimate is off by up to half a second plus half the round
+/// trip time. That is plenty for validating certificates, but SNTP
+/// should take over once the network is up.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ClockSync {
+    /// The estimated time when the response was received, in
+    /// milliseconds since the Unix epoch.
+    pub unix_time_ms: u64,
+    /// The [uptime](crate::timer::uptime_ms) when the response was
+    /// received, in milliseconds.
+    pub uptime_ms: u64,
+    /// How long the request took, in milliseconds, from writing it to
+    /// the connection to receiving the response head.
+    pub round_trip_ms: u64,
+}
+
+impl ClockSync {
+    /// Estimates the time from the `Date` header of a response which
+    /// was just received, after a request which took `round_trip_ms`
+    /// milliseconds, as measured with
+    /// [`timer::uptime_ms`](crate::timer::uptime_ms).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MissingDate`](enum.Error.html#variant.MissingDate) if
+    /// the response has no valid `Date` header.
+    pub fn from_response(response: &Response, round_trip_ms: u64) -> Result<ClockSync, Error> {
+        let date = response.date().ok_or(Error::MissingDate)?;
+        Ok(ClockSync::estimate(date, round_trip_ms, timer::uptime_ms()))
+    }
+
+    /// The server's clock read `date` (truncated to seconds) half way
+    /// through the round trip, on average.
+    fn estimate(date: u64, round_trip_ms: u64, uptime_ms: u64) -> ClockSync {
+        ClockSync {
+            unix_time_ms: date * 1000 + 500 + round_trip_ms / 2,
+            uptime_ms,
+            round_trip_ms,
+        }
+    }
+
+    /// Returns the estimated current time, in milliseconds since the
+    /// Unix epoch, by adding the time passed since the response was
+    /// received.
+    pub fn now_ms(&self) -> u64 {
+        self.unix_time_ms + timer::uptime_ms().saturating_sub(self.uptime_ms)
+    }
+
+    /// Returns the estimated current time, in seconds since the Unix
+    /// epoch.
+    pub fn now(&self) -> u64 {
+        self.now_ms() / 1000
+    }
+
+    /// Sets the system clock to the estimated current time, see
+    /// [`timer::set_unix_time_ms`](crate::timer::set_unix_time_ms).
+    /// Returns whether the clock was set.
+    pub fn set_system_clock(&self) -> bool {
+        timer::set_unix_time_ms(self.now_ms())
+    }
+}
+
+/// Learns the current time from the `Date` header of the response to
+/// `request`, eg. to set the clock right after boot, when TLS
+/// certificates can't be validated yet.
+///
+/// Use a `HEAD` request over plain HTTP, so there is neither a body
+/// nor a certificate to check. A `Cache-Control: no-cache` header is
+/// added, so caching proxies don't answer with an old `Date`, and
+/// redirects are not followed, as they would add a round trip after
+/// the `Date` was generated.
+///
+/// The round trip is measured from when the request is written to
+/// the established connection, so DNS lookups, connecting and the
+/// delays between retries don't count towards it.
+///
+/// # Errors
+///
+/// Returns [`MissingDate`](enum.Error.html#variant.MissingDate) if
+/// the response has no valid `Date` header,
+/// [`TooManyRedirections`](enum.Error.html#variant.TooManyRedirections)
+/// if it is a redirection, and the errors of
+/// [`Request::send`](struct.Request.html#method.send).
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn main() -> Result<(), esp_minreq::Error> {
+/// let clock = esp_minreq::sync_clock::<esp_minreq::tcp::HttpStream>(esp_minreq::head(
+///     "http://example.com",
+/// ))
+/// .await?;
+/// clock.set_system_clock();
+/// # Ok(()) }
+/// ```
+pub async fn sync_clock<C: HttpConnect>(request: Request) -> Result<ClockSync, Error>
+where
+    Error: From<C::Error>,
+{
+    let request = request
+        .with_header("Cache-Control", "no-cache")
+        .with_max_redirects(0)
+        .keep_headers(&["date"]);
+    let response = request.send_lazy::<C>().await?;
+    let received_at = timer::uptime_ms();
+    let round_trip_ms = received_at.saturating_sub(response.sent_at_ms);
+    let date = response.date().ok_or(Error::MissingDate)?;
+    let clock = ClockSync::estimate(date, round_trip_ms, received_at);
+    log::debug!(
+        "Clock synchronized to {} ms, round trip {} ms.",
+        clock.unix_time_ms,
+        round_trip_ms
+    );
+    Ok(clock)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sync_clock, ClockSync};
+    use crate::http::test_support::{block_on, Stream};
+    use crate::http::Error;
+
+    #[test]
+    fn compensates_for_round_trip() {
+        // Sun, 06 Nov 1994 08:49:37 GMT
+        let clock = ClockSync::estimate(784_111_777, 300, 5000);
+        assert_eq!(clock.unix_time_ms, 784_111_777_650);
+        assert_eq!(clock.uptime_ms, 5000);
+        assert_eq!(clock.round_trip_ms, 300);
+    }
+
+    #[test]
+    fn does_not_follow_redirects() {
+        Stream::script(Stream::new(
+            b"HTTP/1.1 302 Found\r\nLocation: /b\r\nDate: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n",
+            64,
+        ));
+        let clock = block_on(sync_clock::<Stream>(crate::head("http://example.com/a")));
+        assert!(matches!(clock, Err(Error::TooManyRedirections)));
+        assert_eq!(Stream::connected_urls().len(), 1);
+    }
+}
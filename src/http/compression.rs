@@ -0,0 +1,680 @@
+use crate::digest;
+use crate::http::deflate::Deflater;
+use crate::http::progress::{Direction, ProgressConfig, UPLOAD_CHUNK_LENGTH};
+use crate::http::Error;
+use alloc::boxed::Box;
+use alloc::collections::btree_map::BTreeMap as HashMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use embedded_io_async::{ErrorType, Write};
+use miniz_oxide::inflate::stream::{inflate, InflateState};
+use miniz_oxide::{DataFormat, MZError, MZFlush, MZStatus};
+
+/// The value of the `Accept-Encoding` header sent with requests.
+pub(crate) const ACCEPT_ENCODING: &str = "gzip, deflate";
+
+/// The default of
+/// [`Request::with_max_decompression_ratio`](struct.Request.html#method.with_max_decompression_ratio).
+pub(crate) const DEFAULT_MAX_RATIO: u32 = 100;
+
+// Bodies may always decode to this size, however well they compress,
+// as tiny and highly repetitive bodies have large ratios too.
+const MIN_RATIO_LIMITED_LENGTH: u64 = 64 * 1024;
+
+// The compressed bytes are collected in, and the decompressed bytes
+// served from, buffers of these sizes. The 32 KiB window required by
+// deflate is allocated separately by miniz_oxide.
+const INPUT_LENGTH: usize = 256;
+const OUTPUT_LENGTH: usize = 512;
+
+const GZIP_FHCRC: u8 = 0x02;
+const GZIP_FEXTRA: u8 = 0x04;
+const GZIP_FNAME: u8 = 0x08;
+const GZIP_FCOMMENT: u8 = 0x10;
+const GZIP_RESERVED: u8 = 0xe0;
+
+/// The part of the gzip header being parsed, see RFC 1952.
+#[derive(Clone, Copy)]
+enum GzipHeader {
+    /// The fixed ten bytes, of which this many were read.
+    Fixed(u8),
+    /// The length of the extra field, of which this many bytes were
+    /// read.
+    ExtraLength(u8, u16),
+    /// The extra field, with this many bytes left.
+    Extra(u16),
+    /// The zero-terminated file name.
+    Name,
+    /// The zero-terminated comment.
+    Comment,
+    /// The header CRC, of which this many bytes were read.
+    Crc(u8),
+}
+
+enum Stage {
+    GzipHeader(GzipHeader),
+    /// Waiting for the first two bytes of a `deflate` body, which
+    /// tell whether it has a zlib wrapper.
+    DeflateFormat,
+    Body,
+    /// The gzip trailer, of which this many bytes were read.
+    GzipTrailer(u8),
+    Done,
+}
+
+/// Decompresses a `gzip` or `deflate` encoded body piece by piece.
+///
+/// The compressed body is written into [`input`](#method.input), and
+/// the decompressed bytes are read from [`output`](#method.output)
+/// after calling [`decode`](#method.decode).
+pub(crate) struct Decoder {
+    gzip: bool,
+    stage: Stage,
+    flags: u8,
+    inflate: Option<Box<InflateState>>,
+    input: [u8; INPUT_LENGTH],
+    input_start: usize,
+    input_end: usize,
+    output: [u8; OUTPUT_LENGTH],
+    output_start: usize,
+    output_end: usize,
+    trailer: [u8; 8],
+    crc: u32,
+    encoded: u64,
+    decoded: u64,
+    max_ratio: u32,
+}
+
+impl Decoder {
+    /// Returns a decoder for a body with the given `Content-Encoding`
+    /// headers, or `None` if the body is not compressed, or uses an
+    /// encoding that is not supported. The headers are changed to
+    /// describe the decompressed body: the `Content-Encoding` is
+    /// removed, and so is the `Content-Length`, which is only known
+    /// once the whole body has been decompressed.
+    pub(crate) fn for_headers(
+        headers: &mut HashMap<String, String>,
+        max_ratio: u32,
+    ) -> Option<Box<Decoder>> {
+        let encoding = headers.get("content-encoding")?.trim().to_ascii_lowercase();
+        let (gzip, stage) = match encoding.as_str() {
+            "gzip" | "x-gzip" => (true, Stage::GzipHeader(GzipHeader::Fixed(0))),
+            "deflate" => (false, Stage::DeflateFormat),
+            _ => return None,
+        };
+        headers.remove("content-encoding");
+        headers.remove("content-length");
+        Some(Box::new(Decoder {
+            gzip,
+            stage,
+            flags: 0,
+            inflate: None,
+            input: [0; INPUT_LENGTH],
+            input_start: 0,
+            input_end: 0,
+            output: [0; OUTPUT_LENGTH],
+            output_start: 0,
+            output_end: 0,
+            trailer: [0; 8],
+            crc: 0,
+            encoded: 0,
+            decoded: 0,
+            max_ratio,
+        }))
+    }
+
+    /// Returns the free space for more of the compressed body, which
+    /// is empty if the unprocessed input fills the whole buffer.
+    pub(crate) fn input(&mut self) -> &mut [u8] {
+        if self.input_start > 0 {
+            self.input.copy_within(self.input_start..self.input_end, 0);
+            self.input_end -= self.input_start;
+            self.input_start = 0;
+        }
+        &mut self.input[self.input_end..]
+    }
+
+    /// Marks `length` bytes written into [`input`](#method.input) as
+    /// ready for decoding.
+    pub(crate) fn fill(&mut self, length: usize) {
+        self.input_end += length;
+        self.encoded += length as u64;
+    }
+
+    /// Returns the decompressed bytes which have not been consumed.
+    pub(crate) fn output(&self) -> &[u8] {
+        &self.output[self.output_start..self.output_end]
+    }
+
+    /// Consumes `length` bytes of the [`output`](#method.output).
+    pub(crate) fn consume(&mut self, length: usize) {
+        self.output_start += length;
+    }
+
+    /// Returns whether the end of the compressed body was decoded.
+    pub(crate) fn is_done(&self) -> bool {
+        matches!(self.stage, Stage::Done)
+    }
+
+    /// Decodes the buffered input, until some output is available or
+    /// more input is needed. Returns whether any progress was made.
+    pub(crate) fn decode(&mut self) -> Result<bool, Error> {
+        let mut progress = false;
+        while self.output().is_empty() {
+            let made_progress = match self.stage {
+                Stage::GzipHeader(header) => self.decode_gzip_header(header)?,
+                Stage::DeflateFormat => self.detect_deflate_format(),
+                Stage::Body => self.decode_body()?,
+                Stage::GzipTrailer(read) => self.decode_gzip_trailer(read)?,
+                Stage::Done => false,
+            };
+            if !made_progress {
+                break;
+            }
+            progress = true;
+        }
+        Ok(progress)
+    }
+
+    /// Checks that the compressed body is complete once there is no
+    /// more input. An empty body is accepted, as servers send those
+    /// with a `Content-Encoding` eg. for `HEAD` requests.
+    pub(crate) fn finish(&mut self) -> Result<(), Error> {
+        match self.stage {
+            Stage::Done => Ok(()),
+            _ if self.encoded == 0 => {
+                self.stage = Stage::Done;
+                Ok(())
+            }
+            _ => Err(Error::DecompressionFailed),
+        }
+    }
+
+    fn next_input_byte(&mut self) -> Option<u8> {
+        if self.input_start == self.input_end {
+            return None;
+        }
+        self.input_start += 1;
+        Some(self.input[self.input_start - 1])
+    }
+
+    fn decode_gzip_header(&mut self, header: GzipHeader) -> Result<bool, Error> {
+        let Some(byte) = self.next_input_byte() else {
+            return Ok(false);
+        };
+        let header = match header {
+            GzipHeader::Fixed(read) => {
+                match (read, byte) {
+                    (0, 0x1f) | (1, 0x8b) | (2, 8) => {}
+                    (3, flags) if flags & GZIP_RESERVED == 0 => self.flags = flags,
+                    (0..=3, _) => return Err(Error::DecompressionFailed),
+                    _ => {}
+                }
+                if read < 9 {
+                    Some(GzipHeader::Fixed(read + 1))
+                } else {
+                    self.next_gzip_header_field()
+                }
+            }
+            GzipHeader::ExtraLength(0, _) => Some(GzipHeader::ExtraLength(1, byte as u16)),
+            GzipHeader::ExtraLength(_, low) => match (byte as u16) << 8 | low {
+                0 => self.next_gzip_header_field(),
+                length => Some(GzipHeader::Extra(length)),
+            },
+            GzipHeader::Extra(1) => self.next_gzip_header_field(),
+            GzipHeader::Extra(left) => Some(GzipHeader::Extra(left - 1)),
+            GzipHeader::Name | GzipHeader::Comment if byte == 0 => self.next_gzip_header_field(),
+            field @ (GzipHeader::Name | GzipHeader::Comment) => Some(field),
+            GzipHeader::Crc(0) => Some(GzipHeader::Crc(1)),
+            GzipHeader::Crc(_) => self.next_gzip_header_field(),
+        };
+        self.stage = match header {
+            Some(header) => Stage::GzipHeader(header),
+            None => {
+                self.inflate = Some(InflateState::new_boxed(DataFormat::Raw));
+                Stage::Body
+            }
+        };
+        Ok(true)
+    }
+
+    /// Returns the next optional field of the gzip header, or `None`
+    /// if the header ends.
+    fn next_gzip_header_field(&mut self) -> Option<GzipHeader> {
+        let fields = [
+            (GZIP_FEXTRA, GzipHeader::ExtraLength(0, 0)),
+            (GZIP_FNAME, GzipHeader::Name),
+            (GZIP_FCOMMENT, GzipHeader::Comment),
+            (GZIP_FHCRC, GzipHeader::Crc(0)),
+        ];
+        let (flag, field) = fields
+            .into_iter()
+            .find(|&(flag, _)| self.flags & flag != 0)?;
+        self.flags &= !flag;
+        Some(field)
+    }
+
+    fn detect_deflate_format(&mut self) -> bool {
+        let input = &self.input[self.input_start..self.input_end];
+        if input.len() < 2 {
+            return false;
+        }
+        // Despite the name, `deflate` means a zlib wrapped stream, but
+        // some servers send a raw deflate stream instead.
+        let zlib =
+            input[0] & 0x0f == 8 && (u16::from(input[0]) << 8 | u16::from(input[1])) % 31 == 0;
+        let format = if zlib {
+            DataFormat::Zlib
+        } else {
+            DataFormat::Raw
+        };
+        self.inflate = Some(InflateState::new_boxed(format));
+        self.stage = Stage::Body;
+        true
+    }
+
+    fn decode_body(&mut self) -> Result<bool, Error> {
+        let Some(ref mut state) = self.inflate else {
+            return Err(Error::Other("decoding a body without an inflate state"));
+        };
+        let result = inflate(
+            state,
+            &self.input[self.input_start..self.input_end],
+            &mut self.output,
+            MZFlush::None,
+        );
+        self.input_start += result.bytes_consumed;
+        self.output_start = 0;
+        self.output_end = result.bytes_written;
+        if self.gzip {
+            self.crc = digest::crc32(self.crc, &self.output[..self.output_end]);
+        }
+        self.decoded += result.bytes_written as u64;
+        let limit = (self.encoded * self.max_ratio as u64).max(MIN_RATIO_LIMITED_LENGTH);
+        if self.decoded > limit {
+            return Err(Error::CompressionRatioExceeded);
+        }
+
+        match result.status {
+            Ok(MZStatus::StreamEnd) => {
+                // The window is not needed anymore.
+                self.inflate = None;
+                self.stage = if self.gzip {
+                    Stage::GzipTrailer(0)
+                } else {
+                    Stage::Done
+                };
+                Ok(true)
+            }
+            Ok(MZStatus::Ok) | Err(MZError::Buf) => {
+                Ok(result.bytes_consumed > 0 || result.bytes_written > 0)
+            }
+            _ => Err(Error::DecompressionFailed),
+        }
+    }
+
+    /// Reads the CRC-32 and the length of the decompressed data that
+    /// end a gzip body, and checks them.
+    fn decode_gzip_trailer(&mut self, read: u8) -> Result<bool, Error> {
+        let Some(byte) = self.next_input_byte() else {
+            return Ok(false);
+        };
+        self.trailer[read as usize] = byte;
+        if read < 7 {
+            self.stage = Stage::GzipTrailer(read + 1);
+            return Ok(true);
+        }
+        let crc = u32::from_le_bytes([
+            self.trailer[0],
+            self.trailer[1],
+            self.trailer[2],
+            self.trailer[3],
+        ]);
+        let length = u32::from_le_bytes([
+            self.trailer[4],
+            self.trailer[5],
+            self.trailer[6],
+            self.trailer[7],
+        ]);
+        if crc != self.crc || length != self.decoded as u32 {
+            return Err(Error::DecompressionFailed);
+        }
+        self.stage = Stage::Done;
+        Ok(true)
+    }
+}
+
+/// A gzip header without a file name or modification time, from an
+/// unknown operating system.
+const GZIP_HEADER: [u8; 10] = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+
+// Compressed data is written into the sink in pieces of at least this
+// size, unless flushed.
+const MIN_GZIP_WRITE_LENGTH: usize = 512;
+
+/// Settings for compressing request bodies with gzip, see
+/// [`Request::with_gzip_body`](struct.Request.html#method.with_gzip_body)
+/// and [`GzipEncoder`].
+///
+/// The compressor needs roughly `6 * 2^window_bits` bytes of RAM, or
+/// 6 KiB with the default settings.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GzipOptions {
+    level: u8,
+    window_bits: u8,
+    chunked: bool,
+}
+
+impl GzipOptions {
+    /// Creates the default settings: level 6, a 1 KiB window, and
+    /// the body compressed before sending.
+    pub fn new() -> GzipOptions {
+        GzipOptions {
+            level: 6,
+            window_bits: 10,
+            chunked: false,
+        }
+    }
+
+    /// Sets how hard to look for repeated data, from 0 (not at all,
+    /// fastest) to 9 (smallest output, slowest).
+    pub fn with_level(mut self, level: u8) -> GzipOptions {
+        self.level = level.min(9);
+        self
+    }
+
+    /// Sets the size of the window in which repeated data is looked
+    /// for to `2^window_bits` bytes, from 9 (512 bytes) to 15 (32
+    /// KiB). Larger windows compress better, and need more RAM.
+    pub fn with_window_bits(mut self, window_bits: u8) -> GzipOptions {
+        self.window_bits = window_bits.clamp(9, 15);
+        self
+    }
+
+    /// Sets whether the body is compressed while it is sent, using
+    /// `Transfer-Encoding: chunked`, instead of before, with a
+    /// `Content-Length`. This saves keeping the compressed body in
+    /// memory, but not all servers accept chunked requests.
+    pub fn with_chunked(mut self, chunked: bool) -> GzipOptions {
+        self.chunked = chunked;
+        self
+    }
+
+    pub(crate) fn is_chunked(&self) -> bool {
+        self.chunked
+    }
+}
+
+impl Default for GzipOptions {
+    fn default() -> GzipOptions {
+        GzipOptions::new()
+    }
+}
+
+/// Compresses data written into it with gzip, and writes the result
+/// into another [`Write`](embedded_io_async::Write), eg. for
+/// compressing a body which is produced piece by piece.
+///
+/// The compressed data is written into the sink in pieces of at least
+/// 512 bytes. [`flush`](embedded_io_async::Write::flush) writes out
+/// everything written so far, so the receiver can decompress it
+/// without waiting for more, at the cost of a few bytes.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn main() -> Result<(), core::convert::Infallible> {
+/// use embedded_io_async::Write;
+///
+/// let mut buffer = [0; 1024];
+/// let mut encoder = esp_minreq::GzipEncoder::new(&mut buffer[..], esp_minreq::GzipOptions::new());
+/// encoder.write_all(b"temperature=21.5\n").await.unwrap();
+/// encoder.write_all(b"temperature=21.6\n").await.unwrap();
+/// encoder.finish().await.unwrap();
+/// # Ok(()) }
+/// ```
+pub struct GzipEncoder<W: Write> {
+    sink: W,
+    deflater: Deflater,
+    crc: u32,
+    length: u32,
+}
+
+impl<W: Write> GzipEncoder<W> {
+    /// Creates an encoder writing into `sink`.
+    pub fn new(sink: W, options: GzipOptions) -> GzipEncoder<W> {
+        let mut deflater = Deflater::new(options.level, options.window_bits);
+        deflater.output.extend_from_slice(&GZIP_HEADER);
+        GzipEncoder {
+            sink,
+            deflater,
+            crc: 0,
+            length: 0,
+        }
+    }
+
+    /// Returns a reference to the sink.
+    pub fn get_ref(&self) -> &W {
+        &self.sink
+    }
+
+    /// Compresses the rest of the data, writes the end of the gzip
+    /// stream into the sink and flushes it, and returns the sink.
+    pub async fn finish(mut self) -> Result<W, W::Error> {
+        self.deflater.finish();
+        self.deflater
+            .output
+            .extend_from_slice(&self.crc.to_le_bytes());
+        self.deflater
+            .output
+            .extend_from_slice(&self.length.to_le_bytes());
+        self.write_output().await?;
+        self.sink.flush().await?;
+        Ok(self.sink)
+    }
+
+    async fn write_output(&mut self) -> Result<(), W::Error> {
+        self.sink.write_all(&self.deflater.output).await?;
+        self.deflater.output.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> ErrorType for GzipEncoder<W> {
+    type Error = W::Error;
+}
+
+impl<W: Write> Write for GzipEncoder<W> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.deflater.write(buf);
+        self.crc = digest::crc32(self.crc, buf);
+        self.length = self.length.wrapping_add(buf.len() as u32);
+        if self.deflater.output.len() >= MIN_GZIP_WRITE_LENGTH {
+            self.write_output().await?;
+        }
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.deflater.flush();
+        self.write_output().await?;
+        self.sink.flush().await
+    }
+}
+
+/// Compresses `data` with gzip.
+pub(crate) fn gzip(data: &[u8], options: GzipOptions) -> Vec<u8> {
+    let mut deflater = Deflater::new(options.level, options.window_bits);
+    deflater.output.extend_from_slice(&GZIP_HEADER);
+    deflater.write(data);
+    deflater.finish();
+    let mut gzip = deflater.output;
+    gzip.extend_from_slice(&digest::crc32(0, data).to_le_bytes());
+    gzip.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    gzip
+}
+
+/// Writes the request body compressed with gzip, with chunked
+/// framing, reporting the progress of the uncompressed body if
+/// requested.
+pub(crate) async fn write_gzip_chunked<W: Write>(
+    stream: &mut W,
+    body: &[u8],
+    options: GzipOptions,
+    progress: Option<&ProgressConfig>,
+) -> Result<(), W::Error> {
+    let mut tracker =
+        progress.map(|progress| progress.tracker(Direction::Upload, Some(body.len() as u64)));
+    let mut encoder = GzipEncoder::new(ChunkedWriter(stream), options);
+    for piece in body.chunks(UPLOAD_CHUNK_LENGTH) {
+        encoder.write_all(piece).await?;
+        if let Some(ref mut tracker) = tracker {
+            tracker.advance(piece.len());
+        }
+    }
+    let ChunkedWriter(stream) = encoder.finish().await?;
+    stream.write_all(b"0\r\n\r\n").await?;
+    if let Some(ref mut tracker) = tracker {
+        tracker.finish();
+    }
+    Ok(())
+}
+
+/// Writes everything written into it as a chunk of a chunked body.
+struct ChunkedWriter<'a, W: Write>(&'a mut W);
+
+impl<W: Write> ErrorType for ChunkedWriter<'_, W> {
+    type Error = W::Error;
+}
+
+impl<W: Write> Write for ChunkedWriter<'_, W> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if !buf.is_empty() {
+            self.0
+                .write_all(format!("{:x}\r\n", buf.len()).as_bytes())
+                .await?;
+            self.0.write_all(buf).await?;
+            self.0.write_all(b"\r\n").await?;
+        }
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.0.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{gzip, Decoder, GzipOptions};
+    use crate::digest;
+    use crate::http::Error;
+    use alloc::boxed::Box;
+    use alloc::collections::btree_map::BTreeMap as HashMap;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+    use miniz_oxide::deflate::{compress_to_vec, compress_to_vec_zlib};
+
+    fn decoder(encoding: &str, max_ratio: u32) -> Box<Decoder> {
+        let mut headers = HashMap::new();
+        headers.insert("content-encoding".to_string(), encoding.to_string());
+        headers.insert("content-length".to_string(), "10".to_string());
+        let decoder = Decoder::for_headers(&mut headers, max_ratio).unwrap();
+        assert!(headers.is_empty());
+        decoder
+    }
+
+    fn miniz_gzip(data: &[u8]) -> Vec<u8> {
+        // With FNAME and FEXTRA set.
+        let mut gzip = Vec::from(*b"\x1f\x8b\x08\x0c\0\0\0\0\0\x03\x02\0ab");
+        gzip.extend_from_slice(b"body.txt\0");
+        gzip.extend(compress_to_vec(data, 6));
+        gzip.extend(digest::crc32(0, data).to_le_bytes());
+        gzip.extend((data.len() as u32).to_le_bytes());
+        gzip
+    }
+
+    /// Decodes `encoded`, feeding it in pieces of `piece` bytes.
+    fn decode(decoder: &mut Decoder, encoded: &[u8], piece: usize) -> Result<Vec<u8>, Error> {
+        let mut decoded = Vec::new();
+        let mut pieces = encoded.chunks(piece);
+        loop {
+            decoded.extend_from_slice(decoder.output());
+            decoder.consume(decoder.output().len());
+            if decoder.is_done() {
+                return Ok(decoded);
+            }
+            if decoder.decode()? {
+                continue;
+            }
+            match pieces.next() {
+                Some(piece) => {
+                    decoder.input()[..piece.len()].copy_from_slice(piece);
+                    decoder.fill(piece.len());
+                }
+                None => decoder.finish()?,
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_gzip_and_deflate() {
+        let body = b"hello, hello, hello, world! ".repeat(100);
+        let gzip = miniz_gzip(&body);
+        for piece in [1, 7, 256] {
+            assert_eq!(
+                decode(&mut decoder("gzip", 100), &gzip, piece).unwrap(),
+                body
+            );
+        }
+        let zlib = compress_to_vec_zlib(&body, 6);
+        assert_eq!(
+            decode(&mut decoder("deflate", 100), &zlib, 5).unwrap(),
+            body
+        );
+        let raw = compress_to_vec(&body, 6);
+        assert_eq!(decode(&mut decoder("Deflate", 100), &raw, 5).unwrap(), body);
+        assert_eq!(decode(&mut decoder("gzip", 100), b"", 5).unwrap(), b"");
+
+        let mut headers = HashMap::new();
+        headers.insert("content-encoding".to_string(), "br".to_string());
+        assert!(Decoder::for_headers(&mut headers, 100).is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_and_bombs() {
+        let body = b"hello, world".repeat(20);
+        let mut gzip = miniz_gzip(&body);
+        let truncated = &gzip[..gzip.len() - 3];
+        assert!(matches!(
+            decode(&mut decoder("gzip", 100), truncated, 7),
+            Err(Error::DecompressionFailed)
+        ));
+        let crc = gzip.len() - 8;
+        gzip[crc] ^= 1;
+        assert!(matches!(
+            decode(&mut decoder("gzip", 100), &gzip, 7),
+            Err(Error::DecompressionFailed)
+        ));
+
+        let bomb = compress_to_vec(&[0; 1024 * 1024], 6);
+        assert!(matches!(
+            decode(&mut decoder("deflate", 100), &bomb, 64),
+            Err(Error::CompressionRatioExceeded)
+        ));
+        assert!(decode(&mut decoder("deflate", 2000), &bomb, 64).is_ok());
+    }
+
+    #[test]
+    fn compresses_gzip() {
+        let body = b"temperature=21.5;humidity=40\n".repeat(40);
+        let options = GzipOptions::new().with_level(9).with_window_bits(9);
+        let compressed = gzip(&body, options);
+        assert!(compressed.len() < body.len() / 4);
+        assert_eq!(
+            decode(&mut decoder("gzip", 100), &compressed, 64).unwrap(),
+            body
+        );
+    }
+}
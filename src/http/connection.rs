@@ -1,10 +1,13 @@
 use crate::buf_reader::BufReader;
 use crate::http::request::ParsedRequest;
-use crate::http::{Error, Method, ResponseLazy};
+use crate::http::response_ref::SliceWriter;
+use crate::http::{Error, Method, Redirect, ResponseLazy, ResponseRef};
 use alloc::string::String;
+use alloc::vec::Vec;
 use esp_idf_hal::io::EspIOError;
 
-use crate::tcp::HttpConnect;
+use crate::tcp::{ConnectionInfo, HttpConnect};
+use crate::timer;
 
 /// A connection to the server for sending
 /// [`Request`](struct.Request.html)s.
@@ -22,18 +25,47 @@ impl Connection {
     where
         Error: From<C::Error>,
     {
+        let mut redirects = Vec::new();
         let (mut conn, mut response) = self.send_::<C>().await?;
-        let mut next_hop =
-            get_redirect(conn, response.status_code, response.headers.get("location"));
+        let mut next_hop = get_redirect(
+            conn,
+            response.status_code,
+            response.headers.get("location"),
+            &mut redirects,
+        );
         while let NextHop::Redirect(res) = next_hop {
             conn = res?;
             (conn, response) = conn.send_().await?;
-            next_hop = get_redirect(conn, response.status_code, response.headers.get("location"));
+            next_hop = get_redirect(
+                conn,
+                response.status_code,
+                response.headers.get("location"),
+                &mut redirects,
+            );
         }
         if let NextHop::Destination(connection) = next_hop {
             let dst_url = connection.request.url;
             dst_url.write_base_url_to(&mut response.url).unwrap();
             dst_url.write_resource_to(&mut response.url).unwrap();
+            response.redirects = redirects;
+            if let Some(ref progress) = connection.request.config.progress {
+                response.track_progress(progress);
+            }
+            response.cancellation = connection.request.config.cancellation.clone();
+            if connection.request.config.method == Method::Connect
+                && (200..300).contains(&response.status_code)
+            {
+                response.upgraded = true;
+            }
+            if let Some(max_body_size) = connection.request.config.max_body_size {
+                let is_head = connection.request.config.method == Method::Head;
+                response.limit_body_size(max_body_size, !is_head)?;
+            }
+            #[cfg(feature = "compression")]
+            if connection.request.config.decompression {
+                let is_head = connection.request.config.method == Method::Head;
+                response.decompress(connection.request.config.max_decompression_ratio, !is_head);
+            }
             return Ok(response);
         }
         unreachable!()
@@ -47,24 +79,88 @@ impl Connection {
         Error: From<C::Error>,
     {
         self.request.url.host = ensure_ascii_host(self.request.url.host)?;
-        let bytes = self.request.as_bytes();
+
+        log::trace!("Establishing TCP connection to {}.", self.request.url.host);
+        let mut tcp: C = self.connect().await?;
+        let connection_info = tcp.connection_info().unwrap_or_else(|| ConnectionInfo {
+            tls: self.request.url.https,
+            ..ConnectionInfo::default()
+        });
+
+        let sent_at_ms = timer::uptime_ms();
+        let mut response = match self.request.expect_continue_timeout() {
+            Some(timeout_ms) => {
+                // Send the head, and the body only once the server agrees
+                log::trace!("Writing HTTP request head.");
+                tcp.write_all(self.request.get_http_head().as_bytes())
+                    .await?;
+
+                log::trace!("Waiting for 100 Continue.");
+                ResponseLazy::from_stream_expecting_continue(
+                    tcp,
+                    &self.request,
+                    timeout_ms,
+                    self.request.config.max_headers_size,
+                    self.request.config.max_status_line_len,
+                )
+                .await?
+            }
+            None => {
+                // Send request
+                log::trace!("Writing HTTP request.");
+                if self.request.writes_body_separately() {
+                    tcp.write_all(self.request.get_http_head().as_bytes())
+                        .await?;
+                    self.request.write_body(&mut tcp).await?;
+                } else {
+                    tcp.write_all(&self.request.as_bytes()).await?;
+                }
+
+                // Receive response
+                log::trace!("Reading HTTP response.");
+                ResponseLazy::from_stream(
+                    tcp,
+                    self.request.config.max_headers_size,
+                    self.request.config.max_status_line_len,
+                    self.request.config.header_filter.as_deref(),
+                )
+                .await?
+            }
+        };
+        response.connection = connection_info;
+        response.sent_at_ms = sent_at_ms;
+        Ok((self, response))
+    }
+
+    /// Sends the [`Request`](struct.Request.html) without following
+    /// redirects, writing its head into `buf`, and reads the response
+    /// into `buf` and `headers`, see
+    /// [`Request::send_into`](struct.Request.html#method.send_into).
+    pub(crate) async fn send_into<'b, C: HttpConnect>(
+        mut self,
+        buf: &'b mut [u8],
+        headers: &'b mut [(&'b str, &'b str)],
+    ) -> Result<ResponseRef<'b, C>, Error>
+    where
+        Error: From<C::Error>,
+    {
+        self.request.url.host = ensure_ascii_host(self.request.url.host)?;
 
         log::trace!("Establishing TCP connection to {}.", self.request.url.host);
         let mut tcp: C = self.connect().await?;
 
-        // Send request
         log::trace!("Writing HTTP request.");
-        tcp.write_all(&bytes).await?;
+        let mut head = SliceWriter::new(buf);
+        self.request
+            .write_http_head(&mut head)
+            .map_err(|_| Error::BufferTooSmall)?;
+        tcp.write_all(head.written()).await?;
+        self.request.write_body(&mut tcp).await?;
 
-        // Receive response
         log::trace!("Reading HTTP response.");
-        let response = ResponseLazy::from_stream(
-            tcp,
-            self.request.config.max_headers_size,
-            self.request.config.max_status_line_len,
-        )
-        .await?;
-        Ok((self, response))
+        let is_head = self.request.config.method == Method::Head;
+        let header_filter = self.request.config.header_filter.as_deref();
+        ResponseRef::from_stream(tcp, buf, headers, header_filter, is_head).await
     }
 
     async fn connect<C: HttpConnect>(&self) -> Result<C, Error> {
@@ -107,7 +203,12 @@ enum NextHop {
     Destination(Connection),
 }
 
-fn get_redirect(mut connection: Connection, status_code: i32, url: Option<&String>) -> NextHop {
+fn get_redirect(
+    mut connection: Connection,
+    status_code: i32,
+    url: Option<&String>,
+    redirects: &mut Vec<Redirect>,
+) -> NextHop {
     match status_code {
         301 | 302 | 303 | 307 => {
             let url = match url {
@@ -116,6 +217,15 @@ fn get_redirect(mut connection: Connection, status_code: i32, url: Option<&Strin
             };
             log::debug!("Redirecting ({}) to: {}", status_code, url);
 
+            let mut from = String::new();
+            connection.request.url.write_base_url_to(&mut from).unwrap();
+            connection.request.url.write_resource_to(&mut from).unwrap();
+            redirects.push(Redirect {
+                url: from,
+                status_code,
+                location: url.clone(),
+            });
+
             match connection.request.redirect_to(url.as_str()) {
                 Ok(()) => {
                     if status_code == 303 {
@@ -164,3 +274,72 @@ fn ensure_ascii_host(host: String) -> Result<String, Error> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::http::test_support::{block_on, Stream};
+    use crate::tcp::ConnectionInfo;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn records_followed_redirects() {
+        Stream::script(Stream::new(
+            b"HTTP/1.1 301 Moved Permanently\r\nLocation: /b\r\nContent-Length: 0\r\n\r\n",
+            64,
+        ));
+        Stream::script(Stream::new(
+            b"HTTP/1.1 302 Found\r\nLocation: http://other.com/c\r\nContent-Length: 0\r\n\r\n",
+            64,
+        ));
+        Stream::script(Stream::new(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+            64,
+        ));
+        let response = block_on(crate::get("http://example.com/a").send::<Stream>()).unwrap();
+        assert_eq!(response.as_str().unwrap(), "ok");
+        assert_eq!(response.url, "http://other.com/c");
+        let hops: Vec<(&str, i32, &str)> = response
+            .redirects
+            .iter()
+            .map(|r| (r.url.as_str(), r.status_code, r.location.as_str()))
+            .collect();
+        assert_eq!(
+            hops,
+            [
+                ("http://example.com/a", 301, "/b"),
+                ("http://example.com/b", 302, "http://other.com/c"),
+            ]
+        );
+        assert_eq!(Stream::connected_urls().len(), 3);
+    }
+
+    #[test]
+    fn reports_connection_info() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+        let info = ConnectionInfo {
+            remote_addr: Some("192.0.2.1:443".parse().unwrap()),
+            tls: false,
+            alpn: Some(String::from("http/1.1")),
+        };
+        Stream::script(Stream::new(response, 64).with_info(info.clone()));
+        let connection = block_on(crate::get("https://example.com").send::<Stream>())
+            .unwrap()
+            .connection;
+        assert_eq!(connection, info);
+
+        // Without information from the connector, the URL tells
+        // whether TLS is used.
+        Stream::script(Stream::new(response, 64));
+        let connection = block_on(crate::get("https://example.com").send::<Stream>())
+            .unwrap()
+            .connection;
+        assert_eq!(
+            connection,
+            ConnectionInfo {
+                tls: true,
+                ..ConnectionInfo::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! Parsing of HTTP-dates, as defined in [RFC 7231 section
+//! 7.1.1.1](https://datatracker.ietf.org/doc/html/rfc7231#section-7.1.1.1).
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Parses an HTTP-date into seconds since the Unix epoch.
+///
+/// All three formats are accepted:
+///
+/// ```text
+/// Sun, 06 Nov 1994 08:49:37 GMT  ; IMF-fixdate
+/// Sunday, 06-Nov-94 08:49:37 GMT ; obsolete RFC 850 format
+/// Sun Nov  6 08:49:37 1994       ; ANSI C's asctime() format
+/// ```
+pub(crate) fn parse_http_date(value: &str) -> Option<u64> {
+    let value = value.trim();
+    if let Some((_weekday, rest)) = value.split_once(',') {
+        let mut parts = rest.split_whitespace();
+        let date = parts.next()?;
+        let (day, month, year) = if date.contains('-') {
+            // RFC 850: the date is "06-Nov-94"
+            let mut date = date.split('-');
+            let day = date.next()?;
+            let month = date.next()?;
+            let year = parse_rfc850_year(date.next()?)?;
+            (day, month, year)
+        } else {
+            let month = parts.next()?;
+            let year = parts.next()?.parse().ok()?;
+            (date, month, year)
+        };
+        let time = parts.next()?;
+        if parts.next()? != "GMT" || parts.next().is_some() {
+            return None;
+        }
+        timestamp(year, parse_month(month)?, day.parse().ok()?, time)
+    } else {
+        // asctime: the weekday has no comma, and the day may be
+        // padded with a space instead of a zero.
+        let mut parts = value.split_whitespace();
+        let _weekday = parts.next()?;
+        let month = parse_month(parts.next()?)?;
+        let day = parts.next()?.parse().ok()?;
+        let time = parts.next()?;
+        let year = parts.next()?.parse().ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        timestamp(year, month, day, time)
+    }
+}
+
+fn parse_month(month: &str) -> Option<u32> {
+    MONTHS
+        .iter()
+        .position(|m| m.eq_ignore_ascii_case(month))
+        .map(|i| i as u32 + 1)
+}
+
+fn parse_rfc850_year(year: &str) -> Option<u32> {
+    let year: u32 = year.parse().ok()?;
+    if year >= 100 {
+        return Some(year);
+    }
+    // RFC 7231 says two-digit years that appear to be more than 50
+    // years in the future are in the past. Without a clock, split the
+    // century at 1970.
+    Some(if year >= 70 { 1900 + year } else { 2000 + year })
+}
+
+fn timestamp(year: u32, month: u32, day: u32, time: &str) -> Option<u64> {
+    let mut time = time.split(':');
+    let hour: u64 = time.next()?.parse().ok()?;
+    let minute: u64 = time.next()?.parse().ok()?;
+    let second: u64 = time.next()?.parse().ok()?;
+    if time.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    if year < 1970 || !(1..=12).contains(&month) || day == 0 || day > 31 {
+        return None;
+    }
+
+    // Days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    let y = (if month <= 2 { year - 1 } else { year }) as u64;
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let m = month as u64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as u64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    Some(days * 86_400 + hour * 3_600 + minute * 60 + second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_http_date;
+
+    #[test]
+    fn parse_all_formats() {
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(784_111_777)
+        );
+        assert_eq!(
+            parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"),
+            Some(784_111_777)
+        );
+        assert_eq!(
+            parse_http_date("Sun Nov  6 08:49:37 1994"),
+            Some(784_111_777)
+        );
+    }
+
+    #[test]
+    fn parse_invalid_dates() {
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 CET"), None);
+        assert_eq!(parse_http_date("Sun, 06 Foo 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 25:49:37 GMT"), None);
+        assert_eq!(parse_http_date("120"), None);
+    }
+}
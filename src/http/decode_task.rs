@@ -0,0 +1,244 @@
+use crate::http::{Error, ResponseLazy};
+use crate::waker::AtomicWaker;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+use core::ffi::c_void;
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use core::task::Poll;
+use embedded_io_async::{BufRead, Read};
+use esp_idf_sys::{QueueHandle_t, TickType_t};
+
+/// The size of the buffer the body is handed to the decoder through.
+const PIPE_LENGTH: usize = 512;
+
+/// More of the body may come.
+const OPEN: u8 = 0;
+/// The whole body went through the pipe.
+const ENDED: u8 = 1;
+/// Reading the body failed, or the caller stopped waiting.
+const CLOSED: u8 = 2;
+
+/// Decodes the rest of the body of `response` with `decode`, which
+/// reads it synchronously, without blocking the executor.
+///
+/// `decode` runs in a FreeRTOS task of its own, with `stack_size`
+/// bytes of stack, at the priority of the calling task. The body is
+/// read here, with awaited reads, and handed to it through a buffer
+/// of [`PIPE_LENGTH`] bytes, so only that much of the body is in
+/// memory at once. `decode` blocks its own task while it waits.
+pub(crate) async fn decode_in_task<R, T, F>(
+    response: &mut ResponseLazy<R>,
+    stack_size: u32,
+    decode: F,
+) -> Result<T, Error>
+where
+    R: Read + BufRead,
+    R::Error: Into<Error>,
+    T: Send + 'static,
+    F: FnOnce(&mut PipeReader<T>) -> Result<T, Error> + Send + 'static,
+{
+    let pipe = Pipe::spawn(stack_size, decode)?;
+    loop {
+        // The decoder empties the buffer before asking for more.
+        pipe.wait(|shared| shared.is_done() || shared.filled.load(Ordering::Acquire) == 0)
+            .await;
+        if pipe.shared.is_done() {
+            break;
+        }
+        // Safety: `filled` is 0, so the decoder doesn't access `buf`.
+        let buf = unsafe { &mut *pipe.shared.buf.get() };
+        match response.read_some(buf).await? {
+            0 => {
+                pipe.shared.state.store(ENDED, Ordering::Release);
+                pipe.shared.signal();
+                pipe.wait(Shared::is_done).await;
+                break;
+            }
+            length => {
+                pipe.shared.filled.store(length, Ordering::Release);
+                pipe.shared.signal();
+            }
+        }
+    }
+    // Safety: the decoder stored the result before setting `done`.
+    let result = unsafe { (*pipe.shared.result.get()).take() };
+    result.unwrap_or(Err(Error::Other("the decoder task stopped")))
+}
+
+/// The state shared by the caller and the decoder task.
+struct Shared<T> {
+    buf: UnsafeCell<[u8; PIPE_LENGTH]>,
+    /// The number of bytes in `buf` for the decoder. While it is 0,
+    /// the caller owns `buf`.
+    filled: AtomicUsize,
+    /// [`OPEN`], [`ENDED`] or [`CLOSED`].
+    state: AtomicU8,
+    /// A binary semaphore, given when `filled` or `state` change.
+    ready: QueueHandle_t,
+    /// Wakes the caller when `buf` is emptied or `result` is stored.
+    waker: AtomicWaker,
+    done: AtomicBool,
+    result: UnsafeCell<Option<Result<T, Error>>>,
+}
+
+// `buf` is only accessed by the side `filled` gives it to, and
+// `result` only by the decoder until `done` is set.
+unsafe impl<T: Send> Send for Shared<T> {}
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    fn is_done(&self) -> bool {
+        self.done.load(Ordering::Acquire)
+    }
+
+    /// Wakes the decoder if it waits for the caller.
+    fn signal(&self) {
+        unsafe {
+            esp_idf_sys::xQueueGenericSend(
+                self.ready,
+                core::ptr::null(),
+                0,
+                esp_idf_sys::queueSEND_TO_BACK as _,
+            )
+        };
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        unsafe { esp_idf_sys::vQueueDelete(self.ready) };
+    }
+}
+
+/// The caller's end of the pipe. Dropping it before the body ended
+/// makes the decoder fail, so its task exits.
+struct Pipe<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Send + 'static> Pipe<T> {
+    fn spawn<F>(stack_size: u32, decode: F) -> Result<Pipe<T>, Error>
+    where
+        F: FnOnce(&mut PipeReader<T>) -> Result<T, Error> + Send + 'static,
+    {
+        let ready = unsafe {
+            esp_idf_sys::xQueueGenericCreate(
+                1,
+                0,
+                esp_idf_sys::queueQUEUE_TYPE_BINARY_SEMAPHORE as _,
+            )
+        };
+        if ready.is_null() {
+            return Err(Error::Other("couldn't create the decoder's semaphore"));
+        }
+        let shared = Arc::new(Shared {
+            buf: UnsafeCell::new([0; PIPE_LENGTH]),
+            filled: AtomicUsize::new(0),
+            state: AtomicU8::new(OPEN),
+            ready,
+            waker: AtomicWaker::new(),
+            done: AtomicBool::new(false),
+            result: UnsafeCell::new(None),
+        });
+
+        let arg = Box::into_raw(Box::new((shared.clone(), decode))) as *mut c_void;
+        let created = unsafe {
+            esp_idf_sys::xTaskCreatePinnedToCore(
+                Some(run_decoder::<T, F>),
+                c"esp-minreq decode".as_ptr(),
+                stack_size,
+                arg,
+                esp_idf_sys::uxTaskPriorityGet(core::ptr::null_mut()),
+                core::ptr::null_mut(),
+                esp_idf_sys::tskNO_AFFINITY as _,
+            )
+        };
+        if created != esp_idf_sys::pdPASS as esp_idf_sys::BaseType_t {
+            unsafe { drop(Box::from_raw(arg as *mut (Arc<Shared<T>>, F))) };
+            return Err(Error::Other("couldn't start the decoder task"));
+        }
+        Ok(Pipe { shared })
+    }
+
+    /// Waits until `condition` holds, which the decoder makes true.
+    async fn wait(&self, condition: impl Fn(&Shared<T>) -> bool) {
+        poll_fn(|ctx| {
+            self.shared.waker.register(ctx.waker());
+            if condition(&self.shared) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+impl<T> Drop for Pipe<T> {
+    fn drop(&mut self) {
+        let closed =
+            self.shared
+                .state
+                .compare_exchange(OPEN, CLOSED, Ordering::AcqRel, Ordering::Acquire);
+        if closed.is_ok() {
+            self.shared.signal();
+        }
+    }
+}
+
+unsafe extern "C" fn run_decoder<T, F>(arg: *mut c_void)
+where
+    F: FnOnce(&mut PipeReader<T>) -> Result<T, Error>,
+{
+    let (shared, decode) = *Box::from_raw(arg as *mut (Arc<Shared<T>>, F));
+    let mut reader = PipeReader {
+        shared: shared.clone(),
+        pos: 0,
+    };
+    let result = decode(&mut reader);
+    drop(reader);
+    *shared.result.get() = Some(result);
+    shared.done.store(true, Ordering::Release);
+    shared.waker.wake();
+    drop(shared);
+    esp_idf_sys::vTaskDelete(core::ptr::null_mut());
+}
+
+/// The decoder's end of the pipe, a reader which blocks the decoder
+/// task until the caller has read more of the body.
+pub(crate) struct PipeReader<T> {
+    shared: Arc<Shared<T>>,
+    /// The number of bytes of `buf` already read.
+    pos: usize,
+}
+
+impl<T> std::io::Read for PipeReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let shared = &*self.shared;
+        loop {
+            let filled = shared.filled.load(Ordering::Acquire);
+            if filled > 0 {
+                // Safety: `filled` gives `buf` to this side.
+                let data = unsafe { &(&*shared.buf.get())[self.pos..filled] };
+                let length = data.len().min(buf.len());
+                buf[..length].copy_from_slice(&data[..length]);
+                self.pos += length;
+                if self.pos == filled {
+                    self.pos = 0;
+                    shared.filled.store(0, Ordering::Release);
+                    shared.waker.wake();
+                }
+                return Ok(length);
+            }
+            match shared.state.load(Ordering::Acquire) {
+                ENDED => return Ok(0),
+                CLOSED => return Err(std::io::ErrorKind::ConnectionAborted.into()),
+                _ => unsafe {
+                    esp_idf_sys::xQueueSemaphoreTake(shared.ready, TickType_t::MAX);
+                },
+            }
+        }
+    }
+}
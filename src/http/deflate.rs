@@ -0,0 +1,299 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_HASH_BITS: u8 = 12;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const END_OF_BLOCK: u16 = 256;
+
+/// The longest hash chain searched for a match, by level.
+const MAX_CHAIN: [u16; 10] = [0, 2, 4, 8, 16, 32, 64, 128, 512, 2048];
+
+/// A small deflate (RFC 1951) compressor, for request bodies. The
+/// compressed stream is written into [`output`](#structfield.output).
+///
+/// Matches are found with hash chains over a window of configurable
+/// size, and encoded with the fixed Huffman codes, which need no
+/// tables or buffering of whole blocks. This compresses text and JSON
+/// reasonably well with a few KiB of RAM, where zlib needs hundreds.
+pub(crate) struct Deflater {
+    /// The compressed bytes which have not been taken yet.
+    pub(crate) output: Vec<u8>,
+    /// The last `window` bytes, followed by the uncompressed data.
+    data: Vec<u8>,
+    /// The position in `data` up to which the data was compressed.
+    position: usize,
+    window: usize,
+    /// The last position with each hash, or 0 if none.
+    head: Vec<u16>,
+    /// The previous position with the same hash as each position in
+    /// the window, or 0 if none.
+    prev: Vec<u16>,
+    hash_shift: u32,
+    max_chain: u16,
+    bits: u64,
+    bit_count: u32,
+    in_block: bool,
+}
+
+impl Deflater {
+    /// Creates a compressor with a window of `2^window_bits` bytes
+    /// (9 to 15). Higher levels (0 to 9) search longer for matches,
+    /// and 0 disables them.
+    pub(crate) fn new(level: u8, window_bits: u8) -> Deflater {
+        let window = 1 << window_bits.clamp(9, 15);
+        let hash_bits = window_bits.clamp(9, MAX_HASH_BITS);
+        Deflater {
+            output: Vec::new(),
+            data: Vec::with_capacity(2 * window),
+            position: 0,
+            window,
+            head: vec![0; 1 << hash_bits],
+            prev: vec![0; window],
+            hash_shift: 32 - hash_bits as u32,
+            max_chain: MAX_CHAIN[level.min(9) as usize],
+            bits: 0,
+            bit_count: 0,
+            in_block: false,
+        }
+    }
+
+    /// Compresses `input`. Up to the longest match length of it is
+    /// kept back, until more data arrives or the stream is flushed.
+    pub(crate) fn write(&mut self, mut input: &[u8]) {
+        while !input.is_empty() {
+            if self.data.len() == 2 * self.window {
+                self.slide();
+            }
+            let length = input.len().min(2 * self.window - self.data.len());
+            self.data.extend_from_slice(&input[..length]);
+            input = &input[length..];
+            self.compress(false);
+        }
+    }
+
+    /// Compresses all the data written so far, and ends the block
+    /// with an empty stored block, so the receiver can decompress
+    /// everything without waiting for more.
+    pub(crate) fn flush(&mut self) {
+        self.compress(true);
+        if self.in_block {
+            self.put_symbol(END_OF_BLOCK);
+            self.in_block = false;
+        }
+        // BFINAL = 0, BTYPE = 00, then LEN = 0 and NLEN = !0.
+        self.put_bits(0, 3);
+        self.align();
+        self.output.extend_from_slice(&[0, 0, 0xff, 0xff]);
+    }
+
+    /// Compresses all the data written so far, and ends the stream.
+    pub(crate) fn finish(&mut self) {
+        self.compress(true);
+        if self.in_block {
+            self.put_symbol(END_OF_BLOCK);
+            self.in_block = false;
+        }
+        // An empty final block with fixed codes.
+        self.put_bits(0b011, 3);
+        self.put_symbol(END_OF_BLOCK);
+        self.align();
+    }
+
+    /// Drops the oldest half of `data`, keeping a window of history.
+    fn slide(&mut self) {
+        let window = self.window;
+        self.data.copy_within(window.., 0);
+        self.data.truncate(self.data.len() - window);
+        self.position -= window;
+        for position in self.head.iter_mut().chain(self.prev.iter_mut()) {
+            *position = position.saturating_sub(window as u16);
+        }
+    }
+
+    fn compress(&mut self, all: bool) {
+        while self.position < self.data.len() {
+            let lookahead = self.data.len() - self.position;
+            if lookahead < MAX_MATCH && !all {
+                break;
+            }
+            if !self.in_block {
+                // BFINAL = 0, BTYPE = 01: fixed Huffman codes.
+                self.put_bits(0b010, 3);
+                self.in_block = true;
+            }
+
+            let (length, distance) = self.find_match();
+            if length >= MIN_MATCH {
+                self.put_match(length, distance);
+                for position in self.position + 1..self.position + length {
+                    self.insert(position);
+                }
+                self.position += length;
+            } else {
+                self.put_symbol(self.data[self.position] as u16);
+                self.position += 1;
+            }
+        }
+    }
+
+    fn hash(&self, position: usize) -> usize {
+        let bytes = &self.data[position..position + MIN_MATCH];
+        let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]);
+        (value.wrapping_mul(0x9e37_79b1) >> self.hash_shift) as usize
+    }
+
+    /// Adds `position` to the hash chains, returning the previous
+    /// position with the same hash.
+    fn insert(&mut self, position: usize) -> usize {
+        // Position 0 stands for none, so it is never matched.
+        if position == 0 || position + MIN_MATCH > self.data.len() {
+            return 0;
+        }
+        let hash = self.hash(position);
+        let previous = self.head[hash];
+        self.prev[position & (self.window - 1)] = previous;
+        self.head[hash] = position as u16;
+        previous as usize
+    }
+
+    /// Returns the length and distance of the longest match for the
+    /// data at `position`.
+    fn find_match(&mut self) -> (usize, usize) {
+        let position = self.position;
+        let mut candidate = self.insert(position);
+        let max_length = (self.data.len() - position).min(MAX_MATCH);
+        let mut best = (0, 0);
+        let mut chain = self.max_chain;
+        while candidate != 0 && candidate < position && position - candidate < self.window {
+            if chain == 0 {
+                break;
+            }
+            chain -= 1;
+            let length = self.data[candidate..candidate + max_length]
+                .iter()
+                .zip(&self.data[position..position + max_length])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length > best.0 {
+                best = (length, position - candidate);
+                if length == max_length {
+                    break;
+                }
+            }
+            let next = self.prev[candidate & (self.window - 1)] as usize;
+            if next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+        best
+    }
+
+    fn put_match(&mut self, length: usize, distance: usize) {
+        let code = LENGTH_BASE
+            .iter()
+            .rposition(|&base| base as usize <= length)
+            .unwrap_or(0);
+        self.put_symbol(257 + code as u16);
+        self.put_bits(
+            (length - LENGTH_BASE[code] as usize) as u32,
+            LENGTH_EXTRA[code] as u32,
+        );
+        let code = DISTANCE_BASE
+            .iter()
+            .rposition(|&base| base as usize <= distance)
+            .unwrap_or(0);
+        self.put_bits(reverse_bits(code as u32, 5), 5);
+        self.put_bits(
+            (distance - DISTANCE_BASE[code] as usize) as u32,
+            DISTANCE_EXTRA[code] as u32,
+        );
+    }
+
+    /// Writes a literal/length symbol with its fixed Huffman code.
+    fn put_symbol(&mut self, symbol: u16) {
+        let (code, length) = match symbol {
+            0..=143 => (0x30 + symbol, 8),
+            144..=255 => (0x190 + symbol - 144, 9),
+            256..=279 => (symbol - 256, 7),
+            _ => (0xc0 + symbol - 280, 8),
+        };
+        self.put_bits(reverse_bits(code as u32, length), length);
+    }
+
+    fn put_bits(&mut self, value: u32, count: u32) {
+        self.bits |= (value as u64) << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.output.push(self.bits as u8);
+            self.bits >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    fn align(&mut self) {
+        if self.bit_count > 0 {
+            self.output.push(self.bits as u8);
+        }
+        self.bits = 0;
+        self.bit_count = 0;
+    }
+}
+
+/// Huffman codes are packed starting with their most significant bit.
+fn reverse_bits(value: u32, count: u32) -> u32 {
+    value.reverse_bits() >> (32 - count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Deflater;
+    use alloc::vec::Vec;
+    use miniz_oxide::inflate::decompress_to_vec;
+
+    #[test]
+    fn round_trips_through_inflate() {
+        let mut text = Vec::new();
+        for i in 0..2000u32 {
+            text.extend_from_slice(b"{\"sensor\":\"temperature\",\"value\":");
+            text.extend_from_slice(&(i * 7919 % 1000).to_le_bytes());
+            text.extend_from_slice(b"}\n");
+        }
+        for (level, window_bits) in [(0, 9), (1, 9), (6, 12), (9, 15)] {
+            let mut deflater = Deflater::new(level, window_bits);
+            for piece in text.chunks(100) {
+                deflater.write(piece);
+            }
+            deflater.flush();
+            deflater.write(b"and then some more");
+            deflater.finish();
+            let mut expected = text.clone();
+            expected.extend_from_slice(b"and then some more");
+            assert_eq!(decompress_to_vec(&deflater.output).unwrap(), expected);
+            if level > 0 {
+                assert!(deflater.output.len() < text.len() / 3);
+            }
+        }
+
+        let mut deflater = Deflater::new(6, 12);
+        deflater.finish();
+        assert_eq!(decompress_to_vec(&deflater.output).unwrap(), b"");
+    }
+}
@@ -0,0 +1,477 @@
+use crate::base64;
+use crate::digest::{self, Md5, Sha256};
+use crate::http::cancel::cancellable;
+use crate::http::{ContentRange, Error, Request, RetryPolicy};
+use crate::tcp::HttpConnect;
+use crate::timer;
+use alloc::collections::btree_map::BTreeMap as HashMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use embedded_io_async::{Error as _, ErrorType, Read, Write};
+
+const COPY_BUFFER_LENGTH: usize = 512;
+
+/// Downloads a resource into an
+/// [`embedded_io_async::Write`](embedded_io_async::Write) sink,
+/// resuming with range requests when the connection breaks.
+///
+/// After an IO error, or if the connection is closed before the whole
+/// body was received, the download continues from the last byte
+/// written to the sink with `Range: bytes=<offset>-`. The resource's
+/// `ETag` (or `Last-Modified` date if it has no strong `ETag`) is sent
+/// in `If-Range`, so the server sends the whole resource again if it
+/// changed in the meantime. When that happens, or the server does not
+/// support ranges and sends a resource which can't be told to be the
+/// same, as its validator differs or neither response had one,
+/// [`ResourceChanged`](enum.Error.html#variant.ResourceChanged) is
+/// returned, as the sink already contains the start of the old one.
+/// The download then starts over from the first byte on the next
+/// [`run`](#method.run), which should be given an emptied sink. If a
+/// server without range support sends the same resource again, the
+/// bytes the sink already has are skipped.
+///
+/// The state is kept between calls to [`run`](#method.run), and can
+/// be saved with [`offset`](#method.offset) and
+/// [`validator`](#method.validator) to continue after a reboot with
+/// [`resume`](#method.resume).
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn main() -> Result<(), esp_minreq::Error> {
+/// let mut buffer = [0; 4096];
+/// let mut sink = &mut buffer[..];
+/// let mut download = esp_minreq::ResumableDownload::new(esp_minreq::get("http://example.com"));
+/// download.run::<esp_minreq::tcp::HttpStream, _>(&mut sink).await?;
+/// println!("Downloaded {} bytes", download.offset());
+/// # Ok(()) }
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ResumableDownload {
+    request: Request,
+    retry_policy: RetryPolicy,
+    offset: u64,
+    validator: Option<String>,
+    total_length: Option<u64>,
+}
+
+impl ResumableDownload {
+    /// Creates a download of the resource requested by `request`,
+    /// starting at the first byte.
+    pub fn new(request: Request) -> ResumableDownload {
+        ResumableDownload::resume(request, 0, None)
+    }
+
+    /// Creates a download that continues at byte `offset`, eg. with
+    /// the state saved before a reboot. `validator` is the
+    /// [`validator`](#method.validator) of the earlier download; if it
+    /// is `None`, the server can't tell whether the resource changed.
+    pub fn resume(request: Request, offset: u64, validator: Option<String>) -> ResumableDownload {
+        ResumableDownload {
+            request,
+            retry_policy: RetryPolicy::default(),
+            offset,
+            validator,
+            total_length: None,
+        }
+    }
+
+    /// Sets how many times and how quickly a broken download is
+    /// resumed. Only the retry count and backoff of the policy are
+    /// used. Defaults to [`RetryPolicy::default`].
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> ResumableDownload {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Returns the number of bytes written to the sink so far,
+    /// including those written before [`resume`](#method.resume).
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the `ETag` or `Last-Modified` value identifying the
+    /// version of the resource being downloaded, if the server sent
+    /// one.
+    pub fn validator(&self) -> Option<&str> {
+        self.validator.as_deref()
+    }
+
+    /// Returns the length of the whole resource, if known.
+    pub fn total_length(&self) -> Option<u64> {
+        self.total_length
+    }
+
+    /// Downloads the rest of the resource into `sink`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the last attempt if the download could
+    /// not be completed after the retries of the
+    /// [`RetryPolicy`](struct.RetryPolicy.html), and any error other
+    /// than [`IoError`](enum.Error.html#variant.IoError) and
+    /// [`IncompleteBody`](enum.Error.html#variant.IncompleteBody)
+    /// right away. Responses other than `200 OK` and `206 Partial
+    /// Content` are returned as
+    /// [`UnexpectedStatus`](enum.Error.html#variant.UnexpectedStatus).
+    pub async fn run<C: HttpConnect, W: Write>(&mut self, sink: &mut W) -> Result<(), Error>
+    where
+        Error: From<C::Error>,
+    {
+        let mut attempt = 0;
+        loop {
+            let offset = self.offset;
+            match self.attempt::<C, W>(sink).await {
+                Err(Error::IoError(_) | Error::IncompleteBody)
+                    if attempt < self.retry_policy.max_retries =>
+                {
+                    // Only consecutive attempts without progress count.
+                    if self.offset > offset {
+                        attempt = 0;
+                    }
+                    let delay = self.retry_policy.backoff_ms(attempt);
+                    log::debug!("Resuming download at byte {} in {} ms.", self.offset, delay);
+                    let token = self.request.cancellation.as_ref();
+                    cancellable(token, timer::delay_ms(delay)).await?;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn attempt<C: HttpConnect, W: Write>(&mut self, sink: &mut W) -> Result<(), Error>
+    where
+        Error: From<C::Error>,
+    {
+        let mut request =
+            self.request
+                .clone()
+                .keep_headers(&["content-range", "etag", "last-modified"]);
+        // The offset counts the bytes as sent, so they must not be
+        // decompressed.
+        #[cfg(feature = "compression")]
+        {
+            request = request.with_decompression(false);
+        }
+        if self.offset > 0 {
+            request = request.with_range(self.offset..);
+            if let Some(ref validator) = self.validator {
+                request = request.with_header("If-Range", validator.clone());
+            }
+        }
+
+        let mut response = request.send_lazy::<C>().await?;
+        let mut skip = 0;
+        match response.status_code {
+            206 => match response.content_range() {
+                Some(ContentRange::Bytes {
+                    first,
+                    complete_length,
+                    ..
+                }) if first == self.offset => {
+                    if self.validator.is_none() {
+                        self.validator = validator(&response.headers);
+                    }
+                    self.total_length = complete_length.or(self.total_length);
+                }
+                _ => return Err(Error::UnexpectedContentRange),
+            },
+            200 => {
+                let validator = validator(&response.headers);
+                if self.offset > 0 {
+                    if validator.is_none() || validator != self.validator {
+                        self.offset = 0;
+                        self.validator = None;
+                        self.total_length = None;
+                        return Err(Error::ResourceChanged);
+                    }
+                    skip = self.offset;
+                }
+                self.validator = validator;
+                self.total_length = match response.headers.get("content-length") {
+                    Some(length) => Some(
+                        length
+                            .trim()
+                            .parse()
+                            .or(Err(Error::MalformedContentLength))?,
+                    ),
+                    None => None,
+                };
+            }
+            // The previous attempt got everything, but the connection
+            // broke before we could tell.
+            416 if self.offset > 0
+                && response.content_range().and_then(|r| r.complete_length())
+                    == Some(self.offset) =>
+            {
+                return Ok(());
+            }
+            status => return Err(Error::UnexpectedStatus(status)),
+        }
+
+        let mut buf = [0; COPY_BUFFER_LENGTH];
+        loop {
+            let read = response.read(&mut buf).await?;
+            if read == 0 {
+                break;
+            }
+            let skipped = skip.min(read as u64) as usize;
+            skip -= skipped as u64;
+            let data = &buf[skipped..read];
+            sink.write_all(data)
+                .await
+                .map_err(|err| Error::SinkError(err.kind()))?;
+            self.offset += data.len() as u64;
+        }
+        sink.flush()
+            .await
+            .map_err(|err| Error::SinkError(err.kind()))?;
+
+        match self.total_length {
+            Some(total_length) if self.offset < total_length => Err(Error::IncompleteBody),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Returns the value to send in `If-Range`: a strong `ETag`, or the
+/// `Last-Modified` date.
+fn validator(headers: &HashMap<String, String>) -> Option<String> {
+    match headers.get("etag") {
+        Some(etag) if !etag.starts_with("W/") => Some(etag.clone()),
+        _ => headers.get("last-modified").cloned(),
+    }
+}
+
+/// The checksums a [`download`](fn.download.html) is expected to
+/// have. Checksums that are not set are not checked.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct ExpectedDigest {
+    sha256: Option<[u8; 32]>,
+    crc32: Option<u32>,
+}
+
+impl ExpectedDigest {
+    /// Creates an `ExpectedDigest` that checks nothing but the
+    /// digests sent by the server.
+    pub fn new() -> ExpectedDigest {
+        ExpectedDigest::default()
+    }
+
+    /// Expects the body to have the given SHA-256 hash.
+    pub fn with_sha256(mut self, sha256: [u8; 32]) -> ExpectedDigest {
+        self.sha256 = Some(sha256);
+        self
+    }
+
+    /// Expects the body to have the given CRC-32 (IEEE 802.3)
+    /// checksum.
+    pub fn with_crc32(mut self, crc32: u32) -> ExpectedDigest {
+        self.crc32 = Some(crc32);
+        self
+    }
+}
+
+/// The length and checksums of a completed
+/// [`download`](fn.download.html).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DownloadDigest {
+    /// The number of bytes written into the sink.
+    pub length: u64,
+    /// The SHA-256 hash of the body.
+    pub sha256: [u8; 32],
+    /// The CRC-32 (IEEE 802.3) checksum of the body.
+    pub crc32: u32,
+}
+
+/// Sends `request` and streams the response body into `sink`, while
+/// computing its SHA-256 hash and CRC-32 checksum.
+///
+/// The checksums are compared against `expected`, and against the
+/// `Digest` (`SHA-256` or `MD5`), `Content-Digest` and `Content-MD5`
+/// headers, if the server sent any. The body is not buffered in
+/// memory, so it is already in the sink when a mismatch is detected:
+/// the caller has to discard it, eg. by not marking an OTA partition
+/// as bootable.
+///
+/// # Errors
+///
+/// Returns [`DigestMismatch`](enum.Error.html#variant.DigestMismatch)
+/// if a checksum does not match,
+/// [`UnexpectedStatus`](enum.Error.html#variant.UnexpectedStatus) if
+/// the response is not successful (2xx), and the errors of
+/// [`ResponseLazy::copy_to`](struct.ResponseLazy.html#method.copy_to),
+/// including
+/// [`IncompleteBody`](enum.Error.html#variant.IncompleteBody) if the
+/// body is shorter than its `Content-Length`.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn main() -> Result<(), esp_minreq::Error> {
+/// # let sha256 = [0; 32];
+/// let mut buffer = [0; 4096];
+/// let mut sink = &mut buffer[..];
+/// let expected = esp_minreq::ExpectedDigest::new().with_sha256(sha256);
+/// let digest = esp_minreq::download::<esp_minreq::tcp::HttpStream, _>(
+///     esp_minreq::get("http://example.com/firmware.bin"),
+///     &mut sink,
+///     &expected,
+/// )
+/// .await?;
+/// println!("Downloaded {} bytes", digest.length);
+/// # Ok(()) }
+/// ```
+pub async fn download<C: HttpConnect, W: Write>(
+    request: Request,
+    sink: &mut W,
+    expected: &ExpectedDigest,
+) -> Result<DownloadDigest, Error>
+where
+    Error: From<C::Error>,
+{
+    let request = request.keep_headers(&["digest", "content-digest", "content-md5"]);
+    // The digest headers describe the body as sent.
+    #[cfg(feature = "compression")]
+    let request = request.with_decompression(false);
+    let mut response = request.send_lazy::<C>().await?;
+    if !(200..300).contains(&response.status_code) {
+        return Err(Error::UnexpectedStatus(response.status_code));
+    }
+
+    let advertised = AdvertisedDigest::parse(&response.headers);
+    let mut writer = DigestWriter {
+        sink,
+        sha256: Sha256::new(),
+        md5: advertised.md5.as_ref().map(|_| Md5::new()),
+        crc32: 0,
+    };
+    let length = response.copy_to(&mut writer).await?;
+
+    let DigestWriter {
+        sha256, md5, crc32, ..
+    } = writer;
+    let digest = DownloadDigest {
+        length,
+        sha256: sha256.finish(),
+        crc32,
+    };
+    let md5 = md5.map(Md5::finish);
+    let mismatch = expected
+        .sha256
+        .is_some_and(|sha256| sha256 != digest.sha256)
+        || expected.crc32.is_some_and(|crc32| crc32 != digest.crc32)
+        || advertised
+            .sha256
+            .is_some_and(|sha256| sha256 != digest.sha256)
+        || advertised
+            .md5
+            .is_some_and(|expected| Some(expected) != md5.map(Vec::from));
+    if mismatch {
+        return Err(Error::DigestMismatch);
+    }
+    Ok(digest)
+}
+
+/// The digests sent by the server in the response headers.
+struct AdvertisedDigest {
+    sha256: Option<Vec<u8>>,
+    md5: Option<Vec<u8>>,
+}
+
+impl AdvertisedDigest {
+    fn parse(headers: &HashMap<String, String>) -> AdvertisedDigest {
+        let mut digest = AdvertisedDigest {
+            sha256: None,
+            md5: headers
+                .get("content-md5")
+                .and_then(|value| base64::decode(value)),
+        };
+        // RFC 3230 `Digest: sha-256=<base64>` and RFC 9530
+        // `Content-Digest: sha-256=:<base64>:`.
+        let values = ["digest", "content-digest"]
+            .into_iter()
+            .filter_map(|name| headers.get(name));
+        for value in values.flat_map(|value| value.split(',')) {
+            let Some((algorithm, value)) = value.split_once('=') else {
+                continue;
+            };
+            let value = base64::decode(value.trim().trim_matches(':'));
+            match algorithm.trim().to_ascii_lowercase().as_str() {
+                "sha-256" => digest.sha256 = value.or(digest.sha256),
+                "md5" => digest.md5 = value.or(digest.md5),
+                _ => {}
+            }
+        }
+        digest
+    }
+}
+
+/// Computes the checksums of everything written into the sink.
+struct DigestWriter<'a, W> {
+    sink: &'a mut W,
+    sha256: Sha256,
+    md5: Option<Md5>,
+    crc32: u32,
+}
+
+impl<W: Write> ErrorType for DigestWriter<'_, W> {
+    type Error = W::Error;
+}
+
+impl<W: Write> Write for DigestWriter<'_, W> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let written = self.sink.write(buf).await?;
+        let buf = &buf[..written];
+        self.sha256.update(buf);
+        if let Some(ref mut md5) = self.md5 {
+            md5.update(buf);
+        }
+        self.crc32 = digest::crc32(self.crc32, buf);
+        Ok(written)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.sink.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AdvertisedDigest, ResumableDownload};
+    use crate::http::test_support::{block_on, Stream};
+    use crate::http::Error;
+    use alloc::collections::btree_map::BTreeMap as HashMap;
+    use alloc::string::ToString;
+
+    #[test]
+    fn parse_advertised_digest() {
+        let mut headers = HashMap::new();
+        headers.insert(
+            "digest".to_string(),
+            "SHA-256=aGVsbG8=, unixsum=30".to_string(),
+        );
+        headers.insert("content-md5".to_string(), "aGk=".to_string());
+        let digest = AdvertisedDigest::parse(&headers);
+        assert_eq!(digest.sha256.unwrap(), b"hello");
+        assert_eq!(digest.md5.unwrap(), b"hi");
+
+        headers.clear();
+        headers.insert("content-digest".to_string(), "sha-256=:aGk=:".to_string());
+        assert_eq!(AdvertisedDigest::parse(&headers).sha256.unwrap(), b"hi");
+    }
+
+    #[test]
+    fn restarts_without_validators() {
+        Stream::script(Stream::new(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello",
+            64,
+        ));
+        let mut download = ResumableDownload::resume(crate::get("http://example.com"), 3, None);
+        let mut buf = [0; 8];
+        let result = block_on(download.run::<Stream, _>(&mut &mut buf[..]));
+        assert!(matches!(result, Err(Error::ResourceChanged)));
+        assert_eq!(download.offset(), 0);
+    }
+}
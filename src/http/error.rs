@@ -9,9 +9,31 @@ pub enum Error {
     #[cfg(feature = "json")]
     /// Ran into a Serde error.
     SerdeJsonError(serde_json::Error),
+    #[cfg(feature = "json-core")]
+    /// Ran into a serde-json-core error.
+    SerdeJsonCoreError(serde_json_core::de::Error),
+    #[cfg(feature = "cbor")]
+    /// Ran into an error while encoding CBOR.
+    SerdeCborEncodeError(ciborium::ser::Error<core::convert::Infallible>),
+    #[cfg(feature = "cbor")]
+    /// Ran into an error while decoding CBOR.
+    SerdeCborDecodeError(ciborium::de::Error<ciborium_io::EndOfFile>),
+    #[cfg(feature = "msgpack")]
+    /// Ran into an error while encoding MessagePack.
+    SerdeMsgpackEncodeError(rmp_serde::encode::Error),
+    #[cfg(feature = "msgpack")]
+    /// Ran into an error while decoding MessagePack.
+    SerdeMsgpackDecodeError(rmp_serde::decode::Error),
+    #[cfg(feature = "postcard")]
+    /// Ran into a postcard error.
+    SerdePostcardError(postcard::Error),
     /// The response body contains invalid UTF-8, so the `as_str()`
     /// conversion failed.
     InvalidUtf8InBody(alloc::str::Utf8Error),
+    /// The `charset` of the response's `Content-Type` is not
+    /// supported by [`Charset`](enum.Charset.html), so the body can't
+    /// be decoded into text.
+    UnsupportedCharset(alloc::string::String),
 
     /// Ran into an IO problem while loading the response.
     IoError(EspIOError),
@@ -72,6 +94,73 @@ pub enum Error {
     /// The URL ended up redirecting to an URL that does not start
     /// with http:// or https://.
     InvalidProtocolInRedirect,
+    /// The server answered with a status code that the operation
+    /// could not handle, eg. `404` while downloading.
+    UnexpectedStatus(i32),
+    /// The `Content-Range` header of a `206 Partial Content` response
+    /// is missing, malformed, or does not match the requested range.
+    UnexpectedContentRange,
+    /// The resource changed on the server while it was being
+    /// downloaded, so the download can't be resumed.
+    ResourceChanged,
+    /// The connection was closed before the whole body was received.
+    IncompleteBody,
+    /// Writing the response body into the provided sink failed.
+    SinkError(ErrorKind),
+    /// The checksum of the downloaded body does not match the
+    /// expected one, or the one sent by the server.
+    DigestMismatch,
+    /// The response body is larger than
+    /// [Request::with_max_body_size](crate::request::Request::with_max_body_size),
+    /// or the buffer provided for it.
+    BodyOverflow,
+    /// The compressed response body is malformed or truncated, or
+    /// its checksum does not match.
+    DecompressionFailed,
+    /// The response body decompresses to more than
+    /// [Request::with_max_decompression_ratio](crate::request::Request::with_max_decompression_ratio)
+    /// times its compressed size.
+    CompressionRatioExceeded,
+    /// A line of the body is longer than the max line length of
+    /// [`ResponseLazy::lines`](struct.ResponseLazy.html#method.lines).
+    LineTooLong,
+    /// [`ResponseLazy::next_chunk`](struct.ResponseLazy.html#method.next_chunk)
+    /// was called on a body which is not `Transfer-Encoding: chunked`.
+    NotChunked,
+    /// A chunk is larger than the max chunk size of
+    /// [`ResponseLazy::next_chunk`](struct.ResponseLazy.html#method.next_chunk).
+    ChunkTooLarge,
+    /// The response has no `Date` header, or it is not a valid
+    /// HTTP-date, so the clock can't be synchronized with it.
+    MissingDate,
+    /// The response to an
+    /// [`EventSource`](struct.EventSource.html) is not a
+    /// `text/event-stream`.
+    NotAnEventStream,
+    /// An event of an [`EventSource`](struct.EventSource.html) is
+    /// larger than its max event size.
+    EventTooLarge,
+    /// The server's answer to a WebSocket handshake is missing the
+    /// upgrade headers, has a wrong `Sec-WebSocket-Accept`, or picked
+    /// a subprotocol or extension which was not offered.
+    WebSocketHandshakeFailed,
+    /// The server violated the WebSocket protocol, eg. by sending a
+    /// masked frame or an unknown opcode.
+    WebSocketProtocolError,
+    /// The WebSocket connection is closed, or the server closed it
+    /// without a close frame.
+    WebSocketClosed,
+    /// A WebSocket frame or message is larger than the limits of its
+    /// [`WebSocketOptions`](struct.WebSocketOptions.html), or a
+    /// control message to send is longer than 125 bytes.
+    MessageTooLarge,
+    /// The buffers given to
+    /// [`Request::send_into`](struct.Request.html#method.send_into)
+    /// can't hold the request head, the response head or its headers.
+    BufferTooSmall,
+    /// The request was cancelled with its
+    /// [`CancellationToken`](struct.CancellationToken.html).
+    Cancelled,
     /// This is a special error case, one that should never be
     /// returned! Think of this as a cleaner alternative to calling
     /// `unreachable!()` inside the library. If you come across this,
@@ -86,8 +175,21 @@ impl Display for Error {
         match self {
             #[cfg(feature = "json")]
             SerdeJsonError(err) => write!(f, "{}", err),
+            #[cfg(feature = "json-core")]
+            SerdeJsonCoreError(err) => write!(f, "{}", err),
+            #[cfg(feature = "cbor")]
+            SerdeCborEncodeError(err) => write!(f, "{}", err),
+            #[cfg(feature = "cbor")]
+            SerdeCborDecodeError(err) => write!(f, "{}", err),
+            #[cfg(feature = "msgpack")]
+            SerdeMsgpackEncodeError(err) => write!(f, "{}", err),
+            #[cfg(feature = "msgpack")]
+            SerdeMsgpackDecodeError(err) => write!(f, "{}", err),
+            #[cfg(feature = "postcard")]
+            SerdePostcardError(err) => write!(f, "{}", err),
             IoError(err) => write!(f, "{}", err),
             InvalidUtf8InBody(err) => write!(f, "{}", err),
+            UnsupportedCharset(charset) => write!(f, "unsupported charset {}", charset),
 
             #[cfg(feature = "rustls")]
             RustlsCreateConnection(err) => write!(f, "error creating rustls connection: {}", err),
@@ -111,6 +213,27 @@ impl Display for Error {
             // TODO: Uncomment these two for 3.0
             InvalidProtocol => write!(f, "the url does not start with http:// or https://"),
             InvalidProtocolInRedirect => write!(f, "got redirected to an absolute url which does not start with http:// or https://"),
+            UnexpectedStatus(status) => write!(f, "unexpected response status code {}", status),
+            UnexpectedContentRange => write!(f, "the content-range header does not match the requested range"),
+            ResourceChanged => write!(f, "the resource changed while it was being downloaded"),
+            IncompleteBody => write!(f, "the connection was closed before the whole body was received"),
+            SinkError(kind) => write!(f, "writing the body into the sink failed: {:?}", kind),
+            DigestMismatch => write!(f, "the checksum of the body does not match the expected one"),
+            BodyOverflow => write!(f, "the response body is too large"),
+            DecompressionFailed => write!(f, "the compressed response body is malformed"),
+            CompressionRatioExceeded => write!(f, "the response body decompresses to more than the max decompression ratio allows"),
+            LineTooLong => write!(f, "the line is longer than the max line length"),
+            NotChunked => write!(f, "the response body is not chunked"),
+            ChunkTooLarge => write!(f, "the chunk is larger than the max chunk size"),
+            BufferTooSmall => write!(f, "the buffer is too small for the request or response head"),
+            MissingDate => write!(f, "the response has no valid Date header"),
+            NotAnEventStream => write!(f, "the response is not an event stream"),
+            EventTooLarge => write!(f, "the event is larger than the max event size"),
+            WebSocketHandshakeFailed => write!(f, "the WebSocket handshake failed"),
+            WebSocketProtocolError => write!(f, "the server violated the WebSocket protocol"),
+            WebSocketClosed => write!(f, "the WebSocket connection is closed"),
+            MessageTooLarge => write!(f, "the WebSocket message is too large"),
+            Cancelled => write!(f, "the request was cancelled"),
             Other(msg) => write!(f, "error in minreq: please open an issue in the minreq repo, include the following: '{}'", msg),
         }
     }
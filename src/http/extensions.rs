@@ -0,0 +1,143 @@
+use alloc::boxed::Box;
+use alloc::collections::btree_map::BTreeMap as HashMap;
+use core::any::{Any, TypeId};
+use core::fmt;
+
+trait AnyClone: Any {
+    fn clone_box(&self) -> Box<dyn AnyClone>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Clone + 'static> AnyClone for T {
+    fn clone_box(&self) -> Box<dyn AnyClone> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+/// A map of values keyed by their type, for passing context along
+/// with a [`Request`](struct.Request.html), for example between
+/// [`Middleware`](trait.Middleware.html)s.
+///
+/// # Example
+///
+/// ```
+/// #[derive(Clone)]
+/// struct RequestId(u32);
+///
+/// let request = esp_minreq::get("http://example.com").with_extension(RequestId(7));
+/// assert_eq!(request.extensions().get::<RequestId>().unwrap().0, 7);
+/// ```
+#[derive(Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Box<dyn AnyClone>>,
+}
+
+impl Extensions {
+    /// Creates an empty `Extensions`.
+    pub fn new() -> Extensions {
+        Extensions::default()
+    }
+
+    /// Inserts a value, returning the previous value of the same
+    /// type, if any.
+    pub fn insert<T: Clone + 'static>(&mut self, value: T) -> Option<T> {
+        self.map
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.into_any().downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    /// Returns a reference to the value of type `T`, if any.
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .and_then(|value| (**value).as_any().downcast_ref())
+    }
+
+    /// Returns a mutable reference to the value of type `T`, if any.
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.map
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| (**value).as_any_mut().downcast_mut())
+    }
+
+    /// Removes and returns the value of type `T`, if any.
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.map
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.into_any().downcast().ok())
+            .map(|value| *value)
+    }
+
+    /// Returns the number of values in the map.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns whether the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+impl Clone for Extensions {
+    fn clone(&self) -> Self {
+        Extensions {
+            map: self
+                .map
+                .iter()
+                .map(|(id, value)| (*id, (**value).clone_box()))
+                .collect(),
+        }
+    }
+}
+
+/// Extensions are compared by the types of the values they contain,
+/// as the values themselves may not be comparable.
+impl PartialEq for Extensions {
+    fn eq(&self, other: &Self) -> bool {
+        self.map.keys().eq(other.map.keys())
+    }
+}
+
+impl Eq for Extensions {}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.map.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Extensions;
+
+    #[test]
+    fn insert_get_remove() {
+        let mut extensions = Extensions::new();
+        assert_eq!(extensions.insert(5u32), None);
+        assert_eq!(extensions.insert(6u32), Some(5));
+        *extensions.get_mut::<u32>().unwrap() += 1;
+
+        let copy = extensions.clone();
+        assert_eq!(extensions.remove::<u32>(), Some(7));
+        assert_eq!(extensions.get::<u32>(), None);
+        assert_eq!(copy.get::<u32>(), Some(&7));
+    }
+}
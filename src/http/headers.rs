@@ -0,0 +1,397 @@
+use crate::http::date::parse_http_date;
+use crate::http::text::{self, Charset};
+use alloc::borrow::Cow;
+use alloc::collections::btree_map::BTreeMap as HashMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// The directives of a `Cache-Control` header, see [RFC 9111 section
+/// 5.2](https://datatracker.ietf.org/doc/html/rfc9111#section-5.2).
+/// Unknown directives are ignored.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct CacheControl {
+    /// `no-store`: the response must not be stored in any cache.
+    pub no_store: bool,
+    /// `no-cache`: the response must be revalidated before each use.
+    pub no_cache: bool,
+    /// `must-revalidate`: the response must not be used once stale.
+    pub must_revalidate: bool,
+    /// `no-transform`: intermediaries must not change the body.
+    pub no_transform: bool,
+    /// `public`: the response may be stored by shared caches.
+    pub public: bool,
+    /// `private`: the response is meant for a single user.
+    pub private: bool,
+    /// `immutable`: the response won't change while it is fresh.
+    pub immutable: bool,
+    /// `max-age`: for how many seconds the response is fresh.
+    pub max_age: Option<u64>,
+    /// `s-maxage`: for how many seconds the response is fresh in
+    /// shared caches.
+    pub s_maxage: Option<u64>,
+}
+
+impl CacheControl {
+    /// Parses the value of a `Cache-Control` header, eg.
+    /// `max-age=3600, must-revalidate`.
+    pub fn parse(value: &str) -> CacheControl {
+        let mut cache_control = CacheControl::default();
+        for directive in value.split(',') {
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name.trim(), Some(argument.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            let seconds = || argument.and_then(|s| s.parse().ok());
+            if name.eq_ignore_ascii_case("no-store") {
+                cache_control.no_store = true;
+            } else if name.eq_ignore_ascii_case("no-cache") {
+                cache_control.no_cache = true;
+            } else if name.eq_ignore_ascii_case("must-revalidate") {
+                cache_control.must_revalidate = true;
+            } else if name.eq_ignore_ascii_case("no-transform") {
+                cache_control.no_transform = true;
+            } else if name.eq_ignore_ascii_case("public") {
+                cache_control.public = true;
+            } else if name.eq_ignore_ascii_case("private") {
+                cache_control.private = true;
+            } else if name.eq_ignore_ascii_case("immutable") {
+                cache_control.immutable = true;
+            } else if name.eq_ignore_ascii_case("max-age") {
+                cache_control.max_age = seconds();
+            } else if name.eq_ignore_ascii_case("s-maxage") {
+                cache_control.s_maxage = seconds();
+            }
+        }
+        cache_control
+    }
+}
+
+/// A parsed `Content-Type` header, eg. `text/html; charset=utf-8`,
+/// see [RFC 9110 section
+/// 8.3.1](https://datatracker.ietf.org/doc/html/rfc9110#section-8.3.1).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MediaType<'a> {
+    essence: &'a str,
+    type_length: usize,
+    parameters: &'a str,
+}
+
+impl<'a> MediaType<'a> {
+    /// Parses the value of a `Content-Type` header. Returns `None` if
+    /// it is not of the form `type/subtype`.
+    pub fn parse(value: &'a str) -> Option<MediaType<'a>> {
+        let (essence, parameters) = value.split_once(';').unwrap_or((value, ""));
+        let essence = essence.trim();
+        let (type_, subtype) = essence.split_once('/')?;
+        if type_.is_empty() || subtype.is_empty() || essence.contains(char::is_whitespace) {
+            return None;
+        }
+        Some(MediaType {
+            essence,
+            type_length: type_.len(),
+            parameters,
+        })
+    }
+
+    /// Returns the type and subtype, eg. `text/html`, as sent by the
+    /// server. They are case-insensitive, see
+    /// [`is`](#method.is).
+    pub fn essence(&self) -> &'a str {
+        self.essence
+    }
+
+    /// Returns the type, eg. `text`.
+    pub fn type_(&self) -> &'a str {
+        &self.essence[..self.type_length]
+    }
+
+    /// Returns the subtype, eg. `html`.
+    pub fn subtype(&self) -> &'a str {
+        &self.essence[self.type_length + 1..]
+    }
+
+    /// Returns whether the essence is `essence`, ignoring case, eg.
+    /// `media_type.is("application/json")`.
+    pub fn is(&self, essence: &str) -> bool {
+        self.essence.eq_ignore_ascii_case(essence)
+    }
+
+    /// Returns the value of the parameter with the given
+    /// (case-insensitive) name, without quotes, eg. `utf-8` for
+    /// `charset`.
+    pub fn parameter(&self, name: &str) -> Option<&'a str> {
+        self.parameters()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the parameters as `(name, value)` pairs, with the
+    /// quotes removed from the values.
+    pub fn parameters(&self) -> impl Iterator<Item = (&'a str, &'a str)> {
+        parameters(self.parameters)
+    }
+
+    /// Returns the charset named by the `charset` parameter, or
+    /// `None` if there is none or it is not supported.
+    pub fn charset(&self) -> Option<Charset> {
+        Charset::from_label(self.parameter("charset")?)
+    }
+}
+
+/// A parsed `Content-Disposition` header, eg. `attachment;
+/// filename="firmware.bin"`, see [RFC
+/// 6266](https://datatracker.ietf.org/doc/html/rfc6266).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ContentDisposition<'a> {
+    disposition_type: &'a str,
+    parameters: &'a str,
+}
+
+impl<'a> ContentDisposition<'a> {
+    /// Parses the value of a `Content-Disposition` header.
+    pub fn parse(value: &'a str) -> ContentDisposition<'a> {
+        let (disposition_type, parameters) = value.split_once(';').unwrap_or((value, ""));
+        ContentDisposition {
+            disposition_type: disposition_type.trim(),
+            parameters,
+        }
+    }
+
+    /// Returns the disposition type, eg. `attachment` or `inline`.
+    pub fn disposition_type(&self) -> &'a str {
+        self.disposition_type
+    }
+
+    /// Returns whether the body should be saved rather than
+    /// displayed.
+    pub fn is_attachment(&self) -> bool {
+        self.disposition_type.eq_ignore_ascii_case("attachment")
+    }
+
+    /// Returns the suggested file name. The extended `filename*`
+    /// parameter, which may contain any characters, is preferred
+    /// over `filename`.
+    ///
+    /// Only the last path component is returned, so a name like
+    /// `../../boot.bin` can't be used to write outside of a
+    /// directory. Empty names, `.` and `..` are ignored.
+    pub fn filename(&self) -> Option<Cow<'a, str>> {
+        let mut filename = None;
+        for (name, value) in parameters(self.parameters) {
+            if name.eq_ignore_ascii_case("filename*") {
+                if let Some(value) = decode_ext_value(value) {
+                    filename = Some(value);
+                    break;
+                }
+            } else if name.eq_ignore_ascii_case("filename") && filename.is_none() {
+                filename = Some(unescape(value));
+            }
+        }
+        match filename? {
+            Cow::Borrowed(name) => base_name(name).map(Cow::Borrowed),
+            Cow::Owned(name) => base_name(&name).map(|name| Cow::Owned(String::from(name))),
+        }
+    }
+}
+
+/// Splits `;`-separated `name=value` parameters, keeping `;` in
+/// quoted values.
+fn parameters(rest: &str) -> impl Iterator<Item = (&str, &str)> {
+    fields(rest).filter_map(|(name, value)| Some((name, value?)))
+}
+
+/// Parses the extensions of a chunk size line, eg.
+/// `;sig="a;b";last`, into lowercase names and unquoted values.
+/// Extensions without a value get an empty one.
+pub(crate) fn chunk_extensions(rest: &str) -> Vec<(String, String)> {
+    fields(rest)
+        .map(|(name, value)| {
+            let value = value.map_or(Cow::Borrowed(""), unescape);
+            (name.to_ascii_lowercase(), value.into_owned())
+        })
+        .collect()
+}
+
+/// Splits `;`-separated `name=value` or `name` fields, keeping `;`
+/// in quoted values.
+fn fields(mut rest: &str) -> impl Iterator<Item = (&str, Option<&str>)> {
+    core::iter::from_fn(move || {
+        rest = rest.trim_start_matches(|c: char| c == ';' || c.is_whitespace());
+        if rest.is_empty() {
+            return None;
+        }
+        let mut quoted = false;
+        let mut escaped = false;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' if quoted => escaped = true,
+                    '"' => quoted = !quoted,
+                    ';' if !quoted => return true,
+                    _ => {}
+                }
+                false
+            })
+            .map_or(rest.len(), |(i, _)| i);
+        let field = &rest[..end];
+        rest = &rest[end..];
+        match field.split_once('=') {
+            Some((name, value)) => {
+                let value = value.trim();
+                let value = match value.strip_prefix('"') {
+                    Some(value) => value.strip_suffix('"').unwrap_or(value),
+                    None => value,
+                };
+                Some((name.trim(), Some(value)))
+            }
+            None => Some((field.trim(), None)),
+        }
+    })
+}
+
+/// Removes the backslashes of a quoted-string.
+fn unescape(value: &str) -> Cow<'_, str> {
+    if !value.contains('\\') {
+        return Cow::Borrowed(value);
+    }
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    Cow::Owned(unescaped)
+}
+
+/// Decodes an RFC 8187 extended value, eg. `UTF-8''%e2%82%ac.txt`.
+fn decode_ext_value(value: &str) -> Option<Cow<'_, str>> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = Charset::from_label(parts.next()?)?;
+    let _language = parts.next()?;
+    let encoded = parts.next()?;
+
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = core::str::from_utf8(after.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &after[2..];
+        } else {
+            bytes.push(byte);
+            rest = after;
+        }
+    }
+    let text = text::decode(&bytes, charset, false).ok()?;
+    Some(Cow::Owned(text.into_owned()))
+}
+
+fn base_name(path: &str) -> Option<&str> {
+    let name = path.rsplit(['/', '\\']).next()?.trim();
+    match name {
+        "" | "." | ".." => None,
+        name => Some(name),
+    }
+}
+
+/// The headers kept by every header filter, because reading the
+/// response depends on them, see
+/// [`Request::with_header_filter`](crate::Request::with_header_filter).
+const FRAMING_HEADERS: [&str; 6] = [
+    "content-length",
+    "transfer-encoding",
+    "content-encoding",
+    "location",
+    "connection",
+    "upgrade",
+];
+
+/// Returns whether the header `name`, in lowercase, passes `filter`.
+pub(crate) fn is_kept<S: AsRef<str>>(filter: Option<&[S]>, name: &str) -> bool {
+    match filter {
+        Some(names) => {
+            FRAMING_HEADERS.contains(&name)
+                || names
+                    .iter()
+                    .any(|kept| kept.as_ref().eq_ignore_ascii_case(name))
+        }
+        None => true,
+    }
+}
+
+pub(crate) fn content_type(headers: &HashMap<String, String>) -> Option<MediaType<'_>> {
+    MediaType::parse(headers.get("content-type")?)
+}
+
+pub(crate) fn content_length(headers: &HashMap<String, String>) -> Option<u64> {
+    headers.get("content-length")?.trim().parse().ok()
+}
+
+pub(crate) fn http_date(headers: &HashMap<String, String>, name: &str) -> Option<u64> {
+    parse_http_date(headers.get(name)?)
+}
+
+pub(crate) fn cache_control(headers: &HashMap<String, String>) -> CacheControl {
+    headers
+        .get("cache-control")
+        .map(|value| CacheControl::parse(value))
+        .unwrap_or_default()
+}
+
+pub(crate) fn content_disposition(
+    headers: &HashMap<String, String>,
+) -> Option<ContentDisposition<'_>> {
+    Some(ContentDisposition::parse(
+        headers.get("content-disposition")?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CacheControl, ContentDisposition, MediaType};
+    use crate::http::Charset;
+
+    #[test]
+    fn parses_media_types() {
+        let media_type = MediaType::parse("Text/HTML; charset=\"ISO-8859-1\"; q=\"a;b\"").unwrap();
+        assert!(media_type.is("text/html"));
+        assert_eq!(media_type.type_(), "Text");
+        assert_eq!(media_type.subtype(), "HTML");
+        assert_eq!(media_type.parameter("CHARSET"), Some("ISO-8859-1"));
+        assert_eq!(media_type.parameter("q"), Some("a;b"));
+        assert_eq!(media_type.charset(), Some(Charset::Iso8859_1));
+        assert_eq!(MediaType::parse("text"), None);
+        assert_eq!(MediaType::parse("text/ html"), None);
+    }
+
+    #[test]
+    fn parses_content_disposition_filenames() {
+        let disposition = ContentDisposition::parse(
+            "attachment; filename=\"rates.txt\"; filename*=UTF-8''%e2%82%ac%20rates.txt",
+        );
+        assert!(disposition.is_attachment());
+        assert_eq!(disposition.filename().unwrap(), "€ rates.txt");
+
+        let disposition = ContentDisposition::parse("inline; filename=\"../a \\\"b\\\".bin\"");
+        assert!(!disposition.is_attachment());
+        assert_eq!(disposition.filename().unwrap(), "a \"b\".bin");
+        assert_eq!(ContentDisposition::parse("attachment").filename(), None);
+        assert_eq!(
+            ContentDisposition::parse("attachment; filename=\"/..\"").filename(),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_cache_control() {
+        let cache_control = CacheControl::parse("public, max-age=60, S-MAXAGE=\"600\", immutable");
+        assert!(cache_control.public && cache_control.immutable);
+        assert!(!cache_control.no_store);
+        assert_eq!(cache_control.max_age, Some(60));
+        assert_eq!(cache_control.s_maxage, Some(600));
+    }
+}
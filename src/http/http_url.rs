@@ -11,7 +11,6 @@ pub(crate) enum Port {
 }
 
 impl Port {
-    #[allow(dead_code)]
     pub(crate) fn port(self) -> u32 {
         match self {
             Port::ImplicitHttp => 80,
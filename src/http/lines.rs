@@ -0,0 +1,149 @@
+use crate::http::{Error, ResponseLazy};
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "json")]
+use core::marker::PhantomData;
+use core::mem;
+use embedded_io_async::{BufRead, Read};
+
+// The body is read in pieces of this size.
+const LINE_READ_LENGTH: usize = 256;
+
+/// Reads the body of a [`ResponseLazy`](struct.ResponseLazy.html)
+/// line by line, as the lines arrive, see
+/// [`ResponseLazy::lines`](struct.ResponseLazy.html#method.lines).
+pub struct Lines<'a, R: Read> {
+    response: &'a mut ResponseLazy<R>,
+    max_line_length: usize,
+    line: Vec<u8>,
+    /// Whether the rest of a line which was too long is being
+    /// skipped.
+    skipping: bool,
+    buf: [u8; LINE_READ_LENGTH],
+    start: usize,
+    end: usize,
+}
+
+impl<'a, R: Read + BufRead> Lines<'a, R>
+where
+    R::Error: Into<Error>,
+{
+    pub(crate) fn new(response: &'a mut ResponseLazy<R>, max_line_length: usize) -> Lines<'a, R> {
+        Lines {
+            response,
+            max_line_length,
+            line: Vec::new(),
+            skipping: false,
+            buf: [0; LINE_READ_LENGTH],
+            start: 0,
+            end: 0,
+        }
+    }
+
+    /// Waits for the next line, without its `\n` or `\r\n`. Returns
+    /// `None` at the end of the body. The last line does not need to
+    /// end with a newline.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LineTooLong`](enum.Error.html#variant.LineTooLong) if
+    /// a line is longer than the max line length, after which it is
+    /// skipped and reading continues with the next one,
+    /// [`InvalidUtf8InBody`](enum.Error.html#variant.InvalidUtf8InBody)
+    /// if a line is not UTF-8, and the errors of reading the body.
+    pub async fn next(&mut self) -> Result<Option<String>, Error> {
+        loop {
+            if self.start < self.end {
+                let data = &self.buf[self.start..self.end];
+                let (piece, ends_line) = match data.iter().position(|&byte| byte == b'\n') {
+                    Some(newline) => (&data[..newline], true),
+                    None => (data, false),
+                };
+                self.start += piece.len() + ends_line as usize;
+                if self.skipping {
+                    self.skipping = !ends_line;
+                    continue;
+                }
+                // One more byte for a `\r` before the `\n`.
+                if self.line.len() + piece.len() > self.max_line_length + 1 {
+                    self.line.clear();
+                    self.skipping = !ends_line;
+                    return Err(Error::LineTooLong);
+                }
+                self.line.extend_from_slice(piece);
+                if ends_line {
+                    return self.take_line().map(Some);
+                }
+                continue;
+            }
+
+            match self.response.read_some(&mut self.buf).await? {
+                0 if self.line.is_empty() || self.skipping => return Ok(None),
+                0 => return self.take_line().map(Some),
+                length => {
+                    self.start = 0;
+                    self.end = length;
+                }
+            }
+        }
+    }
+
+    fn take_line(&mut self) -> Result<String, Error> {
+        let mut line = mem::take(&mut self.line);
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        if line.len() > self.max_line_length {
+            return Err(Error::LineTooLong);
+        }
+        String::from_utf8(line).map_err(|err| Error::InvalidUtf8InBody(err.utf8_error()))
+    }
+}
+
+/// Reads newline-delimited JSON records from the body of a
+/// [`ResponseLazy`](struct.ResponseLazy.html) as they arrive, see
+/// [`ResponseLazy::ndjson`](struct.ResponseLazy.html#method.ndjson).
+#[cfg(feature = "json")]
+pub struct Ndjson<'a, R: Read, T> {
+    lines: Lines<'a, R>,
+    record: PhantomData<fn() -> T>,
+}
+
+#[cfg(feature = "json")]
+impl<'a, R: Read + BufRead, T> Ndjson<'a, R, T>
+where
+    R::Error: Into<Error>,
+    T: serde::de::DeserializeOwned,
+{
+    pub(crate) fn new(lines: Lines<'a, R>) -> Ndjson<'a, R, T> {
+        Ndjson {
+            lines,
+            record: PhantomData,
+        }
+    }
+
+    /// Waits for the next record. Blank lines are skipped. Returns
+    /// `None` at the end of the body.
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`SerdeJsonError`](enum.Error.html#variant.SerdeJsonError) if
+    /// a record can't be deserialized, and the errors of
+    /// [`Lines::next`](struct.Lines.html#method.next). Reading can
+    /// continue with the next record after these.
+    pub async fn next(&mut self) -> Result<Option<T>, Error> {
+        loop {
+            let Some(line) = self.lines.next().await? else {
+                return Ok(None);
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            return match serde_json::from_str(&line) {
+                Ok(record) => Ok(Some(record)),
+                Err(err) => Err(Error::SerdeJsonError(err)),
+            };
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use crate::buf_reader::BufReader;
+use crate::http::{Error, Extensions, Request, Response, ResponseLazy};
+use crate::tcp::HttpConnect;
+use alloc::collections::btree_map::BTreeMap as HashMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// A hook around sending requests with a [`Client`].
+///
+/// Middleware can modify requests before they are sent, answer them
+/// without contacting the server, and observe or replace the
+/// responses. Values can be passed from
+/// [`on_request`](#method.on_request) to
+/// [`on_response`](#method.on_response) or
+/// [`on_error`](#method.on_error) through the request's
+/// [`Extensions`].
+///
+/// # Example
+///
+/// ```
+/// use esp_minreq::{Error, Middleware, Request};
+///
+/// struct BearerAuth(String);
+///
+/// impl Middleware for BearerAuth {
+///     fn on_request(&self, request: &mut Request) -> Result<Option<esp_minreq::Response>, Error> {
+///         let token = format!("Bearer {}", self.0);
+///         request.headers_mut().insert("Authorization".into(), token);
+///         Ok(None)
+///     }
+/// }
+///
+/// let client = esp_minreq::Client::new().with(BearerAuth("secret".into()));
+/// ```
+pub trait Middleware {
+    /// Called before the request is sent. Middleware is called in the
+    /// order it was added to the [`Client`].
+    ///
+    /// Returning `Ok(Some(response))` answers the request without
+    /// sending it: the remaining middleware is skipped, and only the
+    /// middleware before this one sees the response. Returning an
+    /// error aborts the request, and the middleware before this one
+    /// sees it in [`on_error`](#method.on_error).
+    fn on_request(&self, request: &mut Request) -> Result<Option<Response>, Error> {
+        let _ = request;
+        Ok(None)
+    }
+
+    /// Called with the response, in the reverse order of
+    /// [`on_request`](#method.on_request). `extensions` are the
+    /// extensions of the request that produced the response.
+    fn on_response(
+        &self,
+        response: Response,
+        extensions: &mut Extensions,
+    ) -> Result<Response, Error> {
+        let _ = extensions;
+        Ok(response)
+    }
+
+    /// Called instead of [`on_response`](#method.on_response) by
+    /// [`Client::send_lazy`], with the head of the response, before
+    /// its body is read.
+    fn on_response_head(
+        &self,
+        status_code: i32,
+        headers: &mut HashMap<String, String>,
+        extensions: &mut Extensions,
+    ) -> Result<(), Error> {
+        let _ = (status_code, headers, extensions);
+        Ok(())
+    }
+
+    /// Called instead of [`on_response`](#method.on_response) when
+    /// the request fails, or when middleware later in the chain
+    /// returns an error. The error is returned from the
+    /// [`Client`] once all middleware has seen it.
+    fn on_error(&self, error: &Error, extensions: &mut Extensions) {
+        let _ = (error, extensions);
+    }
+}
+
+/// Sends requests through an ordered chain of [`Middleware`].
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn main() -> Result<(), esp_minreq::Error> {
+/// # struct Logger;
+/// # impl esp_minreq::Middleware for Logger {}
+/// let client = esp_minreq::Client::new().with(Logger);
+/// let response = client
+///     .send::<esp_minreq::tcp::HttpStream>(esp_minreq::get("http://example.com"))
+///     .await?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Default)]
+pub struct Client {
+    middleware: Vec<Arc<dyn Middleware>>,
+}
+
+impl Client {
+    /// Creates a new `Client` without any middleware.
+    pub fn new() -> Client {
+        Client::default()
+    }
+
+    /// Adds a middleware to the end of the chain.
+    pub fn with<M: Middleware + 'static>(mut self, middleware: M) -> Client {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Sends the request through the middleware chain.
+    ///
+    /// # Errors
+    ///
+    /// See [`Request::send`](struct.Request.html#method.send). The
+    /// middleware can return any other error as well.
+    pub async fn send<C: HttpConnect>(&self, mut request: Request) -> Result<Response, Error>
+    where
+        Error: From<C::Error>,
+    {
+        let (called, answer) = self.on_request(&mut request);
+        let mut extensions = core::mem::take(&mut request.extensions);
+        let mut result = match answer {
+            Ok(Some(response)) => Ok(response),
+            Ok(None) => request.send::<C>().await,
+            Err(error) => Err(error),
+        };
+        for middleware in self.middleware[..called].iter().rev() {
+            result = result.and_then(|response| middleware.on_response(response, &mut extensions));
+            if let Err(error) = &result {
+                middleware.on_error(error, &mut extensions);
+            }
+        }
+        result
+    }
+
+    /// Sends the request through the middleware chain, loaded lazily.
+    /// The middleware sees the head of the response in
+    /// [`on_response_head`](trait.Middleware.html#method.on_response_head).
+    ///
+    /// # Errors
+    ///
+    /// See [`Request::send_lazy`](struct.Request.html#method.send_lazy).
+    /// The middleware can return any other error as well. As the
+    /// response would have no connection to read from, a middleware
+    /// answering the request in
+    /// [`on_request`](trait.Middleware.html#method.on_request) makes
+    /// this return [`Other`](enum.Error.html#variant.Other).
+    pub async fn send_lazy<C: HttpConnect>(
+        &self,
+        mut request: Request,
+    ) -> Result<ResponseLazy<BufReader<C>>, Error>
+    where
+        Error: From<C::Error>,
+    {
+        let (called, answer) = self.on_request(&mut request);
+        let mut extensions = core::mem::take(&mut request.extensions);
+        let mut result = match answer {
+            Ok(Some(_)) => Err(Error::Other("middleware answered a lazy request")),
+            Ok(None) => request.send_lazy::<C>().await,
+            Err(error) => Err(error),
+        };
+        for middleware in self.middleware[..called].iter().rev() {
+            if let Ok(response) = &mut result {
+                let head = middleware.on_response_head(
+                    response.status_code,
+                    &mut response.headers,
+                    &mut extensions,
+                );
+                if let Err(error) = head {
+                    result = Err(error);
+                }
+            }
+            if let Err(error) = &result {
+                middleware.on_error(error, &mut extensions);
+            }
+        }
+        result
+    }
+
+    /// Calls [`Middleware::on_request`] along the chain, until one
+    /// answers the request or fails. Returns how many middleware are
+    /// to see the outcome, and the answer.
+    fn on_request(&self, request: &mut Request) -> (usize, Result<Option<Response>, Error>) {
+        for (index, middleware) in self.middleware.iter().enumerate() {
+            match middleware.on_request(request) {
+                Ok(None) => {}
+                answer => return (index, answer),
+            }
+        }
+        (self.middleware.len(), Ok(None))
+    }
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("middleware", &self.middleware.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Client, Middleware};
+    use crate::http::test_support::{block_on, Stream};
+    use crate::http::{Error, Extensions, Method, Request};
+    use alloc::collections::btree_map::BTreeMap as HashMap;
+    use alloc::rc::Rc;
+    use alloc::string::String;
+    use core::cell::Cell;
+
+    /// Rewrites requests, and counts what it sees.
+    #[derive(Default)]
+    struct Rewrite {
+        heads: Cell<usize>,
+        errors: Cell<usize>,
+    }
+
+    impl Middleware for Rc<Rewrite> {
+        fn on_request(
+            &self,
+            request: &mut Request,
+        ) -> Result<Option<crate::http::Response>, Error> {
+            request.set_method(Method::Post);
+            request.set_url("http://example.com/rewritten");
+            request.set_body("hi");
+            Ok(None)
+        }
+
+        fn on_response_head(
+            &self,
+            status_code: i32,
+            headers: &mut HashMap<String, String>,
+            _: &mut Extensions,
+        ) -> Result<(), Error> {
+            assert_eq!(status_code, 200);
+            headers.insert("x-seen".into(), "1".into());
+            self.heads.set(self.heads.get() + 1);
+            Ok(())
+        }
+
+        fn on_error(&self, _: &Error, _: &mut Extensions) {
+            self.errors.set(self.errors.get() + 1);
+        }
+    }
+
+    #[test]
+    fn rewrites_requests_and_sees_outcomes() {
+        let rewrite = Rc::new(Rewrite::default());
+        let client = Client::new().with(rewrite.clone());
+
+        Stream::script(Stream::new(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+            64,
+        ));
+        let response = block_on(client.send_lazy::<Stream>(crate::get("http://example.com")));
+        let response = response.unwrap();
+        assert_eq!(response.url, "http://example.com/rewritten");
+        assert_eq!(
+            response.headers.get("x-seen").map(String::as_str),
+            Some("1")
+        );
+        assert_eq!(Stream::connected_urls(), ["http://example.com/rewritten"]);
+        assert_eq!(rewrite.heads.get(), 1);
+
+        // Nothing is scripted, so connecting fails.
+        let response = block_on(client.send::<Stream>(crate::get("http://example.com")));
+        assert!(response.is_err());
+        assert_eq!(rewrite.errors.get(), 1);
+    }
+}
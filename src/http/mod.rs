@@ -38,6 +38,62 @@
 //! [`json()`](struct.Response.html#method.json) for constructing the
 //! struct from JSON and extracting the JSON body out, respectively.
 //!
+//! [`ResponseLazy::json()`](struct.ResponseLazy.html#method.json)
+//! deserializes a lazy response as it is read, without collecting
+//! the body, and
+//! [`ResponseLazy::ndjson()`](struct.ResponseLazy.html#method.ndjson)
+//! reads bodies made of many small JSON values. This feature needs
+//! `std`, which is available on ESP-IDF targets.
+//!
+//! ## `json-core`
+//!
+//! This feature adds
+//! [`ResponseLazy::json_in()`](struct.ResponseLazy.html#method.json_in),
+//! which deserializes JSON with the
+//! [`serde-json-core`](https://crates.io/crates/serde-json-core)
+//! crate into borrowed types, using a buffer provided by the caller
+//! instead of allocating. The body is read into the buffer before
+//! it is deserialized, so the buffer bounds the memory used.
+//!
+//! ## `cbor`, `msgpack` and `postcard`
+//!
+//! These features add the same for
+//! [CBOR](https://crates.io/crates/ciborium),
+//! [MessagePack](https://crates.io/crates/rmp-serde) and
+//! [postcard](https://crates.io/crates/postcard) bodies:
+//! [`with_cbor()`](struct.Request.html#method.with_cbor) and
+//! [`cbor()`](struct.Response.html#method.cbor),
+//! [`with_msgpack()`](struct.Request.html#method.with_msgpack) and
+//! [`msgpack()`](struct.Response.html#method.msgpack), and
+//! [`with_postcard()`](struct.Request.html#method.with_postcard) and
+//! [`postcard()`](struct.Response.html#method.postcard). Note that
+//! `rmp-serde` needs `std`, which is available on ESP-IDF targets.
+//!
+//! CBOR and MessagePack bodies can also be decoded from a
+//! [`ResponseLazy`](struct.ResponseLazy.html), with
+//! [`ResponseLazy::cbor()`](struct.ResponseLazy.html#method.cbor) and
+//! [`ResponseLazy::msgpack()`](struct.ResponseLazy.html#method.msgpack),
+//! which collect the body first.
+//!
+//! ## `compression`
+//!
+//! This feature makes requests advertise `Accept-Encoding: gzip,
+//! deflate`, and transparently decompresses response bodies sent with
+//! such a `Content-Encoding`, using the
+//! [`miniz_oxide`](https://crates.io/crates/miniz_oxide) crate. The
+//! body is decompressed while it is read, so a
+//! [`ResponseLazy`](struct.ResponseLazy.html) only needs the 32 KiB
+//! window of deflate on top of a few small buffers. See
+//! [`with_decompression()`](struct.Request.html#method.with_decompression)
+//! and
+//! [`with_max_decompression_ratio()`](struct.Request.html#method.with_max_decompression_ratio).
+//!
+//! Request bodies can be compressed too, with
+//! [`with_gzip_body()`](struct.Request.html#method.with_gzip_body),
+//! or with a [`GzipEncoder`](struct.GzipEncoder.html) for bodies
+//! produced piece by piece. The compressor is a small one of this
+//! crate, whose window size can be picked to fit the available RAM.
+//!
 //! ## `punycode`
 //!
 //! This feature enables requests to non-ascii domains: the
@@ -152,16 +208,63 @@ extern crate serde;
 #[cfg(feature = "json")]
 extern crate serde_json;
 
+mod cache;
+mod cancel;
+mod clock;
+#[cfg(feature = "compression")]
+mod compression;
 mod connection;
+mod date;
+#[cfg(feature = "json")]
+mod decode_task;
+#[cfg(feature = "compression")]
+mod deflate;
+mod download;
 mod error;
+mod extensions;
+mod headers;
 mod http_url;
+mod lines;
+mod middleware;
+mod progress;
 #[cfg(feature = "proxy")]
 mod proxy;
+mod range;
 mod request;
+mod request_ref;
 mod response;
+mod response_ref;
+mod retry;
+mod sse;
+mod status;
+#[cfg(test)]
+mod test_support;
+mod text;
+mod websocket;
 
+pub use cache::*;
+pub use cancel::CancellationToken;
+pub use clock::*;
+#[cfg(feature = "compression")]
+pub use compression::{GzipEncoder, GzipOptions};
+pub use download::*;
 pub use error::*;
+pub use extensions::*;
+pub use headers::{CacheControl, ContentDisposition, MediaType};
+pub use lines::Lines;
+#[cfg(feature = "json")]
+pub use lines::Ndjson;
+pub use middleware::*;
+pub use progress::*;
 #[cfg(feature = "proxy")]
 pub use proxy::*;
+pub use range::*;
 pub use request::*;
+pub use request_ref::RequestRef;
 pub use response::*;
+pub use response_ref::ResponseRef;
+pub use retry::*;
+pub use sse::{Event, EventSource};
+pub use status::StatusCode;
+pub use text::{Charset, TextReader};
+pub use websocket::{CloseFrame, Message, WebSocket, WebSocketOptions};
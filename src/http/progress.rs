@@ -0,0 +1,169 @@
+use alloc::sync::Arc;
+use core::fmt;
+use embedded_io_async::Write;
+
+// The request body is written in pieces of this size, so progress can
+// be reported while uploading.
+pub(crate) const UPLOAD_CHUNK_LENGTH: usize = 1024;
+
+/// Whether a [`Progress`] report is about the request or the response.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    /// The request body is being sent.
+    Upload,
+    /// The response body is being received.
+    Download,
+}
+
+/// A progress report, see
+/// [`Request::with_progress`](struct.Request.html#method.with_progress).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Progress {
+    /// Which body the report is about.
+    pub direction: Direction,
+    /// The number of body bytes sent or received so far.
+    pub transferred: u64,
+    /// The size of the whole body, if known: the request body's
+    /// length, or the response's `Content-Length`.
+    pub total: Option<u64>,
+}
+
+/// Receives [`Progress`] reports of a request.
+///
+/// Implemented for all `Fn(Progress)` closures.
+pub trait ProgressObserver {
+    /// Called when more of a body has been transferred.
+    fn on_progress(&self, progress: Progress);
+}
+
+impl<F: Fn(Progress)> ProgressObserver for F {
+    fn on_progress(&self, progress: Progress) {
+        self(progress)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct ProgressConfig {
+    observer: Arc<dyn ProgressObserver>,
+    granularity: u64,
+}
+
+impl ProgressConfig {
+    pub(crate) fn new<O: ProgressObserver + 'static>(observer: O, granularity: u64) -> Self {
+        ProgressConfig {
+            observer: Arc::new(observer),
+            granularity,
+        }
+    }
+
+    pub(crate) fn tracker(&self, direction: Direction, total: Option<u64>) -> ProgressTracker {
+        ProgressTracker {
+            config: self.clone(),
+            progress: Progress {
+                direction,
+                transferred: 0,
+                total,
+            },
+            reported: None,
+        }
+    }
+}
+
+/// Observers are compared by identity, as closures are not comparable.
+impl PartialEq for ProgressConfig {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.observer, &other.observer) && self.granularity == other.granularity
+    }
+}
+
+impl Eq for ProgressConfig {}
+
+impl fmt::Debug for ProgressConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProgressConfig")
+            .field("granularity", &self.granularity)
+            .finish()
+    }
+}
+
+/// Counts the bytes of one body and reports them to the observer.
+pub(crate) struct ProgressTracker {
+    config: ProgressConfig,
+    progress: Progress,
+    reported: Option<u64>,
+}
+
+impl ProgressTracker {
+    pub(crate) fn advance(&mut self, bytes: usize) {
+        self.progress.transferred += bytes as u64;
+        let unreported = self.progress.transferred - self.reported.unwrap_or(0);
+        if unreported >= self.config.granularity.max(1)
+            || Some(self.progress.transferred) == self.progress.total
+        {
+            self.report();
+        }
+    }
+
+    /// Reports the final count when the body ends, unless it already
+    /// was.
+    pub(crate) fn finish(&mut self) {
+        if self.reported != Some(self.progress.transferred) {
+            self.report();
+        }
+    }
+
+    fn report(&mut self) {
+        self.reported = Some(self.progress.transferred);
+        self.config.observer.on_progress(self.progress);
+    }
+}
+
+/// Writes the request body, reporting the progress if requested.
+pub(crate) async fn write_body<W: Write>(
+    stream: &mut W,
+    body: &[u8],
+    progress: Option<&ProgressConfig>,
+) -> Result<(), W::Error> {
+    let Some(progress) = progress else {
+        return stream.write_all(body).await;
+    };
+    let mut tracker = progress.tracker(Direction::Upload, Some(body.len() as u64));
+    for chunk in body.chunks(UPLOAD_CHUNK_LENGTH) {
+        stream.write_all(chunk).await?;
+        tracker.advance(chunk.len());
+    }
+    tracker.finish();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Direction, Progress, ProgressConfig};
+    use alloc::rc::Rc;
+    use alloc::vec::Vec;
+    use core::cell::RefCell;
+
+    #[test]
+    fn reports_at_granularity_and_end() {
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let observer = {
+            let reports = reports.clone();
+            move |progress: Progress| reports.borrow_mut().push(progress.transferred)
+        };
+        let config = ProgressConfig::new(observer, 10);
+
+        let mut tracker = config.tracker(Direction::Download, Some(25));
+        for _ in 0..25 {
+            tracker.advance(1);
+        }
+        tracker.finish();
+        assert_eq!(*reports.borrow(), [10, 20, 25]);
+
+        reports.borrow_mut().clear();
+        let mut tracker = config.tracker(Direction::Download, None);
+        tracker.advance(4);
+        tracker.advance(4);
+        tracker.finish();
+        assert_eq!(*reports.borrow(), [8]);
+    }
+}
@@ -0,0 +1,112 @@
+use alloc::collections::btree_map::BTreeMap as HashMap;
+use alloc::string::String;
+
+/// A parsed `Content-Range` header, see [RFC 9110 section
+/// 14.4](https://datatracker.ietf.org/doc/html/rfc9110#section-14.4).
+/// Only byte ranges are supported.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ContentRange {
+    /// The response contains the bytes from `first` to `last`
+    /// (inclusive) of the resource.
+    Bytes {
+        /// The position of the first byte in the response.
+        first: u64,
+        /// The position of the last byte in the response.
+        last: u64,
+        /// The length of the whole resource, if the server knows it.
+        complete_length: Option<u64>,
+    },
+    /// The requested range could not be satisfied, sent with `416
+    /// Range Not Satisfiable`.
+    Unsatisfied {
+        /// The length of the whole resource.
+        complete_length: u64,
+    },
+}
+
+impl ContentRange {
+    /// Parses the value of a `Content-Range` header, eg.
+    /// `bytes 0-499/1234`.
+    pub fn parse(value: &str) -> Option<ContentRange> {
+        let (unit, range) = value.trim().split_once(' ')?;
+        if !unit.eq_ignore_ascii_case("bytes") {
+            return None;
+        }
+        let (range, complete_length) = range.trim().split_once('/')?;
+        if range == "*" {
+            return Some(ContentRange::Unsatisfied {
+                complete_length: complete_length.parse().ok()?,
+            });
+        }
+        let complete_length = match complete_length {
+            "*" => None,
+            length => Some(length.parse().ok()?),
+        };
+        let (first, last) = range.split_once('-')?;
+        let (first, last) = (first.parse().ok()?, last.parse().ok()?);
+        if last < first || matches!(complete_length, Some(length) if last >= length) {
+            return None;
+        }
+        Some(ContentRange::Bytes {
+            first,
+            last,
+            complete_length,
+        })
+    }
+
+    /// Returns the length of the whole resource, if known.
+    pub fn complete_length(&self) -> Option<u64> {
+        match *self {
+            ContentRange::Bytes {
+                complete_length, ..
+            } => complete_length,
+            ContentRange::Unsatisfied { complete_length } => Some(complete_length),
+        }
+    }
+}
+
+pub(crate) fn content_range(headers: &HashMap<String, String>) -> Option<ContentRange> {
+    ContentRange::parse(headers.get("content-range")?)
+}
+
+pub(crate) fn accepts_ranges(headers: &HashMap<String, String>) -> bool {
+    headers.get("accept-ranges").is_some_and(|value| {
+        value
+            .split(',')
+            .any(|unit| unit.trim().eq_ignore_ascii_case("bytes"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ContentRange;
+
+    #[test]
+    fn parse_content_range() {
+        assert_eq!(
+            ContentRange::parse("bytes 0-499/1234"),
+            Some(ContentRange::Bytes {
+                first: 0,
+                last: 499,
+                complete_length: Some(1234)
+            })
+        );
+        assert_eq!(
+            ContentRange::parse("bytes 500-999/*"),
+            Some(ContentRange::Bytes {
+                first: 500,
+                last: 999,
+                complete_length: None
+            })
+        );
+        assert_eq!(
+            ContentRange::parse("bytes */1234"),
+            Some(ContentRange::Unsatisfied {
+                complete_length: 1234
+            })
+        );
+        assert_eq!(ContentRange::parse("bytes 5-4/10"), None);
+        assert_eq!(ContentRange::parse("bytes 0-10/10"), None);
+        assert_eq!(ContentRange::parse("items 0-1/2"), None);
+    }
+}
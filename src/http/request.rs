@@ -1,7 +1,16 @@
 use crate::buf_reader::BufReader;
+use crate::http::cancel::cancellable;
+#[cfg(feature = "compression")]
+use crate::http::compression;
 use crate::http::connection::Connection;
 use crate::http::http_url::{HttpUrl, Port};
-use crate::http::{Error, Response, ResponseLazy};
+use crate::http::progress::{self, ProgressConfig};
+#[cfg(feature = "compression")]
+use crate::http::GzipOptions;
+use crate::http::{
+    CancellationToken, Error, Extensions, ProgressObserver, Response, ResponseLazy, ResponseRef,
+    RetryPolicy,
+};
 #[cfg(feature = "proxy")]
 use crate::proxy::Proxy;
 use crate::tcp::HttpConnect;
@@ -11,6 +20,7 @@ use alloc::fmt::Write;
 use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::ops::{Bound, RangeBounds};
 
 /// A URL type for requests.
 pub type URL = String;
@@ -41,6 +51,24 @@ pub enum Method {
     Custom(String),
 }
 
+impl Method {
+    /// Returns whether the method is idempotent, ie. whether sending
+    /// the same request multiple times has the same effect as sending
+    /// it once. See [RFC 7231 section
+    /// 4.2.2](https://datatracker.ietf.org/doc/html/rfc7231#section-4.2.2).
+    pub fn is_idempotent(&self) -> bool {
+        matches!(
+            self,
+            Method::Get
+                | Method::Head
+                | Method::Put
+                | Method::Delete
+                | Method::Options
+                | Method::Trace
+        )
+    }
+}
+
 impl fmt::Display for Method {
     /// Formats the Method to the form in the HTTP request,
     /// ie. Method::Get -> "GET", Method::Post -> "POST", etc.
@@ -78,13 +106,26 @@ impl fmt::Display for Method {
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Request {
     pub(crate) method: Method,
-    url: URL,
+    pub(crate) url: URL,
     params: String,
-    headers: HashMap<String, String>,
-    body: Option<Vec<u8>>,
+    pub(crate) headers: HashMap<String, String>,
+    pub(crate) body: Option<Vec<u8>>,
     pub(crate) max_headers_size: Option<usize>,
     pub(crate) max_status_line_len: Option<usize>,
+    pub(crate) header_filter: Option<Vec<String>>,
+    pub(crate) max_body_size: Option<usize>,
     max_redirects: usize,
+    expect_continue_timeout: Option<u32>,
+    retry_policy: Option<RetryPolicy>,
+    pub(crate) progress: Option<ProgressConfig>,
+    pub(crate) cancellation: Option<CancellationToken>,
+    #[cfg(feature = "compression")]
+    pub(crate) gzip_body: Option<GzipOptions>,
+    #[cfg(feature = "compression")]
+    pub(crate) decompression: bool,
+    #[cfg(feature = "compression")]
+    pub(crate) max_decompression_ratio: u32,
+    pub(crate) extensions: Extensions,
     #[cfg(feature = "proxy")]
     pub(crate) proxy: Option<Proxy>,
 }
@@ -110,7 +151,20 @@ impl Request {
             body: None,
             max_headers_size: None,
             max_status_line_len: None,
+            header_filter: None,
+            max_body_size: None,
             max_redirects: 100,
+            expect_continue_timeout: None,
+            retry_policy: None,
+            progress: None,
+            cancellation: None,
+            #[cfg(feature = "compression")]
+            gzip_body: None,
+            #[cfg(feature = "compression")]
+            decompression: true,
+            #[cfg(feature = "compression")]
+            max_decompression_ratio: compression::DEFAULT_MAX_RATIO,
+            extensions: Extensions::new(),
             #[cfg(feature = "proxy")]
             proxy: None,
         }
@@ -128,9 +182,53 @@ impl Request {
         let body = body.into();
         let body_length = body.len();
         self.body = Some(body);
+        #[cfg(feature = "compression")]
+        {
+            self.gzip_body = None;
+        }
         self.with_header("Content-Length", format!("{}", body_length))
     }
 
+    /// Sets the request body, compressed with gzip, and adds the
+    /// `Content-Encoding: gzip` header.
+    ///
+    /// By default, the body is compressed right away, and sent with a
+    /// `Content-Length`. With
+    /// [`GzipOptions::with_chunked`](struct.GzipOptions.html#method.with_chunked),
+    /// it is compressed while it is sent instead, with
+    /// `Transfer-Encoding: chunked`. [`body`](#method.body) returns
+    /// the uncompressed body then.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn main() -> Result<(), esp_minreq::Error> {
+    /// # let readings = "";
+    /// let options = esp_minreq::GzipOptions::new()
+    ///     .with_level(9)
+    ///     .with_window_bits(9);
+    /// let response = esp_minreq::post("http://example.com/telemetry")
+    ///     .with_gzip_body(readings, options)
+    ///     .send::<esp_minreq::tcp::HttpStream>()
+    ///     .await?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "compression")]
+    pub fn with_gzip_body<T: Into<Vec<u8>>>(self, body: T, options: GzipOptions) -> Request {
+        let body = body.into();
+        if !options.is_chunked() {
+            return self
+                .with_body(compression::gzip(&body, options))
+                .with_header("Content-Encoding", "gzip");
+        }
+        let mut request = self.with_body(body);
+        request.headers.remove("Content-Length");
+        request.gzip_body = Some(options);
+        request
+            .with_header("Content-Encoding", "gzip")
+            .with_header("Transfer-Encoding", "chunked")
+    }
+
     /// Adds given key and value as query parameter to request url
     /// (resource).
     ///
@@ -177,6 +275,72 @@ impl Request {
         }
     }
 
+    /// Converts given argument to CBOR and sets it as body, with the
+    /// `Content-Type: application/cbor` header.
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`SerdeCborEncodeError`](enum.Error.html#variant.SerdeCborEncodeError)
+    /// if Serde runs into a problem when converting `body` into CBOR.
+    #[cfg(feature = "cbor")]
+    pub fn with_cbor<T: serde::ser::Serialize>(mut self, body: &T) -> Result<Request, Error> {
+        use crate::alloc::string::ToString;
+        self.headers
+            .insert("Content-Type".to_string(), "application/cbor".to_string());
+        let mut cbor = Vec::new();
+        match ciborium::into_writer(body, &mut cbor) {
+            Ok(()) => Ok(self.with_body(cbor)),
+            Err(err) => Err(Error::SerdeCborEncodeError(err)),
+        }
+    }
+
+    /// Converts given argument to MessagePack and sets it as body,
+    /// with the `Content-Type: application/msgpack` header. Structs
+    /// are encoded as maps with their field names, like the JSON
+    /// representation.
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`SerdeMsgpackEncodeError`](enum.Error.html#variant.SerdeMsgpackEncodeError)
+    /// if Serde runs into a problem when converting `body` into
+    /// MessagePack.
+    #[cfg(feature = "msgpack")]
+    pub fn with_msgpack<T: serde::ser::Serialize>(mut self, body: &T) -> Result<Request, Error> {
+        use crate::alloc::string::ToString;
+        self.headers.insert(
+            "Content-Type".to_string(),
+            "application/msgpack".to_string(),
+        );
+        match rmp_serde::to_vec_named(body) {
+            Ok(msgpack) => Ok(self.with_body(msgpack)),
+            Err(err) => Err(Error::SerdeMsgpackEncodeError(err)),
+        }
+    }
+
+    /// Converts given argument to postcard and sets it as body, with
+    /// the `Content-Type: application/x-postcard` header.
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`SerdePostcardError`](enum.Error.html#variant.SerdePostcardError)
+    /// if Serde runs into a problem when converting `body` into
+    /// postcard.
+    #[cfg(feature = "postcard")]
+    pub fn with_postcard<T: serde::ser::Serialize>(mut self, body: &T) -> Result<Request, Error> {
+        use crate::alloc::string::ToString;
+        self.headers.insert(
+            "Content-Type".to_string(),
+            "application/x-postcard".to_string(),
+        );
+        match postcard::to_allocvec(body) {
+            Ok(postcard) => Ok(self.with_body(postcard)),
+            Err(err) => Err(Error::SerdePostcardError(err)),
+        }
+    }
+
     /// Sets the max redirects we follow until giving up. 100 by
     /// default.
     ///
@@ -209,6 +373,53 @@ impl Request {
         self
     }
 
+    /// Keeps only the response headers called one of `names`,
+    /// compared case-insensitively, along with the ones needed to read
+    /// the response: `Content-Length`, `Transfer-Encoding`,
+    /// `Content-Encoding`, `Location`, `Connection` and `Upgrade`. The
+    /// other headers are still read and count towards the
+    /// [max headers size](#method.with_max_headers_size), but are
+    /// dropped right away, which saves memory when servers send lots
+    /// of headers which are never looked at. Trailers are not
+    /// filtered.
+    ///
+    /// The headers used by [`HttpCache`](struct.HttpCache.html),
+    /// [`RetryPolicy`](struct.RetryPolicy.html), downloads, web
+    /// sockets, event sources and clock syncs are kept when those send
+    /// the request. Headers read by the methods of the response, eg.
+    /// `Content-Type` for
+    /// [`ResponseLazy::text_reader`](struct.ResponseLazy.html#method.text_reader),
+    /// have to be listed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let request = esp_minreq::get("http://example.com")
+    ///     .with_header_filter(["Content-Type", "ETag"]);
+    /// ```
+    pub fn with_header_filter<I, T>(mut self, names: I) -> Request
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let names = names.into_iter().map(|name| {
+            let mut name = name.into();
+            name.make_ascii_lowercase();
+            name
+        });
+        self.header_filter = Some(names.collect());
+        self
+    }
+
+    /// Adds `names`, in lowercase, to the header filter if there is
+    /// one, for features which read those headers themselves.
+    pub(crate) fn keep_headers(mut self, names: &[&str]) -> Request {
+        if let Some(ref mut filter) = self.header_filter {
+            filter.extend(names.iter().map(|&name| String::from(name)));
+        }
+        self
+    }
+
     /// Sets the maximum length of the status line this request will
     /// accept.
     ///
@@ -231,6 +442,219 @@ impl Request {
         self
     }
 
+    /// Sets the maximum size of the response body this request will
+    /// accept.
+    ///
+    /// If the response's `Content-Length` is larger, the request
+    /// fails right away with an [Error::BodyOverflow] error, before
+    /// the body is read. Bodies without a `Content-Length` fail with
+    /// the same error as soon as they grow past the limit, both when
+    /// they are loaded by [`send`](#method.send) and when they are
+    /// read from a [`ResponseLazy`].
+    ///
+    /// The size is counted in bytes, excluding the chunk framing of
+    /// chunked bodies. `None` disables the cap, which is the default.
+    pub fn with_max_body_size<S: Into<Option<usize>>>(mut self, max_body_size: S) -> Request {
+        self.max_body_size = max_body_size.into();
+        self
+    }
+
+    /// Sets whether to ask for a compressed response body, and to
+    /// decompress it while it is read. Enabled by default.
+    ///
+    /// When enabled, `Accept-Encoding: gzip, deflate` is sent unless
+    /// the request already has an `Accept-Encoding` header, and
+    /// `gzip` and `deflate` bodies are decompressed. The
+    /// `Content-Encoding` and `Content-Length` headers of the response
+    /// are then removed, as they describe the compressed body.
+    #[cfg(feature = "compression")]
+    pub fn with_decompression(mut self, decompression: bool) -> Request {
+        self.decompression = decompression;
+        self
+    }
+
+    /// Sets how many times larger than its compressed size a response
+    /// body may get when decompressed, to guard against decompression
+    /// bombs. Bodies exceeding it fail with an
+    /// [Error::CompressionRatioExceeded] error. Up to 64 KiB are
+    /// allowed regardless of the ratio.
+    ///
+    /// The default is 100.
+    #[cfg(feature = "compression")]
+    pub fn with_max_decompression_ratio(mut self, max_ratio: u32) -> Request {
+        self.max_decompression_ratio = max_ratio;
+        self
+    }
+
+    /// Requests only the given byte range of the resource, by adding
+    /// a `Range` header, eg. `with_range(500..1000)` or
+    /// `with_range(500..)`.
+    ///
+    /// Servers that support ranges answer with `206 Partial Content`
+    /// and a [`Content-Range`](struct.Response.html#method.content_range)
+    /// header, others send the whole resource with `200 OK`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is empty or reversed, eg. `5..5`, `..0` or
+    /// `5..=3`, as those can't be expressed in a `Range` header.
+    pub fn with_range<T: RangeBounds<u64>>(self, range: T) -> Request {
+        let first = match range.start_bound() {
+            Bound::Included(&first) => Some(first),
+            Bound::Excluded(&first) => first.checked_add(1),
+            Bound::Unbounded => Some(0),
+        };
+        let last = match range.end_bound() {
+            Bound::Included(&last) => Some(Some(last)),
+            Bound::Excluded(&end) => end.checked_sub(1).map(Some),
+            Bound::Unbounded => Some(None),
+        };
+        let value = match (first, last) {
+            (Some(first), Some(Some(last))) if first <= last => {
+                format!("bytes={}-{}", first, last)
+            }
+            (Some(first), Some(None)) => format!("bytes={}-", first),
+            _ => panic!("empty or reversed byte range"),
+        };
+        self.with_header("Range", value)
+    }
+
+    /// Sends the body only after the server agrees to receive it,
+    /// using `Expect: 100-continue`.
+    ///
+    /// The request head is sent first, and the body follows once the
+    /// server answers with `100 Continue`. If the server answers with
+    /// a final response instead, eg. `401 Unauthorized` or `413
+    /// Content Too Large`, that response is returned and the body is
+    /// never sent. Servers which don't support this mechanism don't
+    /// answer at all, so the body is sent anyway after waiting for
+    /// `timeout_ms` milliseconds.
+    ///
+    /// This has no effect on requests without a body.
+    pub fn with_expect_continue(mut self, timeout_ms: u32) -> Request {
+        self.expect_continue_timeout = Some(timeout_ms);
+        self
+    }
+
+    /// Sets the policy for retrying this request when it fails. See
+    /// [`RetryPolicy`](struct.RetryPolicy.html) for which failures are
+    /// retried. Requests are not retried by default.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Request {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Reports the progress of sending the request body and receiving
+    /// the response body to `observer`.
+    ///
+    /// A report is made whenever at least `granularity` more bytes
+    /// have been transferred, and once more when a body is complete.
+    /// The response body is tracked while it is read, so with
+    /// [`send_lazy`](#method.send_lazy) the reports follow the reads
+    /// of the [`ResponseLazy`](struct.ResponseLazy.html).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn main() -> Result<(), esp_minreq::Error> {
+    /// # fn show_on_display(transferred: u64, total: Option<u64>) {}
+    /// let response = esp_minreq::get("http://example.com")
+    ///     .with_progress(
+    ///         |progress: esp_minreq::Progress| {
+    ///             show_on_display(progress.transferred, progress.total)
+    ///         },
+    ///         4096,
+    ///     )
+    ///     .send::<esp_minreq::tcp::HttpStream>()
+    ///     .await?;
+    /// # Ok(()) }
+    /// ```
+    pub fn with_progress<O: ProgressObserver + 'static>(
+        mut self,
+        observer: O,
+        granularity: u64,
+    ) -> Request {
+        self.progress = Some(ProgressConfig::new(observer, granularity));
+        self
+    }
+
+    /// Aborts the request with
+    /// [`Cancelled`](enum.Error.html#variant.Cancelled) when `token`
+    /// is cancelled. See [`CancellationToken`].
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Request {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Adds a value to the request's [`Extensions`], replacing any
+    /// previous value of the same type. Extensions are not sent to
+    /// the server.
+    pub fn with_extension<T: Clone + 'static>(mut self, value: T) -> Request {
+        self.extensions.insert(value);
+        self
+    }
+
+    /// Returns the method of the request.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Changes the method of the request.
+    pub fn set_method(&mut self, method: Method) {
+        self.method = method;
+    }
+
+    /// Returns the URL of the request, without the parameters added
+    /// with [`with_param`](#method.with_param).
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Changes the URL of the request. The parameters added with
+    /// [`with_param`](#method.with_param) are kept, and appended to
+    /// the new URL.
+    pub fn set_url<T: Into<URL>>(&mut self, url: T) {
+        self.url = url.into();
+    }
+
+    /// Returns the headers of the request.
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    /// Returns a mutable reference to the headers of the request.
+    pub fn headers_mut(&mut self) -> &mut HashMap<String, String> {
+        &mut self.headers
+    }
+
+    /// Returns the body of the request, if any.
+    pub fn body(&self) -> Option<&[u8]> {
+        self.body.as_deref()
+    }
+
+    /// Replaces the body of the request, like
+    /// [`with_body`](#method.with_body).
+    pub fn set_body<T: Into<Vec<u8>>>(&mut self, body: T) {
+        let body = body.into();
+        self.headers
+            .insert("Content-Length".into(), format!("{}", body.len()));
+        self.body = Some(body);
+        #[cfg(feature = "compression")]
+        {
+            self.gzip_body = None;
+        }
+    }
+
+    /// Returns the request's [`Extensions`].
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Returns a mutable reference to the request's [`Extensions`].
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
     /// Sets the proxy to use.
     #[cfg(feature = "proxy")]
     pub fn with_proxy(mut self, proxy: Proxy) -> Request {
@@ -248,7 +672,26 @@ impl Request {
     /// [`esp_minreq::Error`](enum.Error.html) except
     /// [`SerdeJsonError`](enum.Error.html#variant.SerdeJsonError) and
     /// [`InvalidUtf8InBody`](enum.Error.html#variant.InvalidUtf8InBody).
-    pub async fn send<C: HttpConnect>(self) -> Result<Response, Error>
+    pub async fn send<C: HttpConnect>(mut self) -> Result<Response, Error>
+    where
+        Error: From<C::Error>,
+    {
+        let token = self.cancellation.clone();
+        let send = async {
+            match self.retry_policy.take() {
+                Some(policy) => {
+                    let request = self.keep_headers(&["retry-after"]);
+                    policy
+                        .send(request, |request| request.send_once::<C>())
+                        .await
+                }
+                None => self.send_once::<C>().await,
+            }
+        };
+        cancellable(token.as_ref(), send).await?
+    }
+
+    async fn send_once<C: HttpConnect>(self) -> Result<Response, Error>
     where
         Error: From<C::Error>,
     {
@@ -263,7 +706,81 @@ impl Request {
     /// # Errors
     ///
     /// See [`send`](struct.Request.html#method.send).
-    pub async fn send_lazy<C: HttpConnect>(self) -> Result<ResponseLazy<BufReader<C>>, Error>
+    pub async fn send_lazy<C: HttpConnect>(mut self) -> Result<ResponseLazy<BufReader<C>>, Error>
+    where
+        Error: From<C::Error>,
+    {
+        let token = self.cancellation.clone();
+        let send = async {
+            match self.retry_policy.take() {
+                Some(policy) => {
+                    let request = self.keep_headers(&["retry-after"]);
+                    policy
+                        .send(request, |request| request.send_lazy_once::<C>())
+                        .await
+                }
+                None => self.send_lazy_once::<C>().await,
+            }
+        };
+        cancellable(token.as_ref(), send).await?
+    }
+
+    /// Sends this request to the host, writing the request head into
+    /// `buf` and then reading the response head into it, with the
+    /// headers kept in `headers`. The rest of `buf` is used for
+    /// reading the body, so no memory is allocated for the response,
+    /// and memory use is known up front.
+    ///
+    /// The returned [`ResponseRef`](struct.ResponseRef.html) borrows
+    /// from the buffers. This is a bare mode: redirects are not
+    /// followed, and retries, decompression and `Expect:
+    /// 100-continue` are not used. Only the response side is free of
+    /// allocations: the request still owns its URL, headers and body,
+    /// which are built as usual. To send a request without allocating
+    /// either, use a [`RequestRef`](struct.RequestRef.html).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferTooSmall`](enum.Error.html#variant.BufferTooSmall)
+    /// if `buf` can't hold the request head or the response head, or
+    /// `headers` has less room than there are response headers, and
+    /// the errors of [`send`](#method.send).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn main() -> Result<(), esp_minreq::Error> {
+    /// use embedded_io_async::Read;
+    /// let mut buf = [0; 2048];
+    /// let mut headers = [("", ""); 16];
+    /// let mut response = esp_minreq::get("http://example.com")
+    ///     .send_into::<esp_minreq::tcp::HttpStream>(&mut buf, &mut headers)
+    ///     .await?;
+    /// println!("{} {:?}", response.status_code, response.header("content-type"));
+    /// let mut body = [0; 512];
+    /// let length = response.read(&mut body).await?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn send_into<'b, C: HttpConnect>(
+        mut self,
+        buf: &'b mut [u8],
+        headers: &'b mut [(&'b str, &'b str)],
+    ) -> Result<ResponseRef<'b, C>, Error>
+    where
+        Error: From<C::Error>,
+    {
+        self.expect_continue_timeout = None;
+        #[cfg(feature = "compression")]
+        {
+            self.decompression = false;
+        }
+        let token = self.cancellation.clone();
+        let parsed_request = ParsedRequest::new(self)?;
+        let send = Connection::new(parsed_request).send_into::<C>(buf, headers);
+        cancellable(token.as_ref(), send).await?
+    }
+
+    async fn send_lazy_once<C: HttpConnect>(self) -> Result<ResponseLazy<BufReader<C>>, Error>
     where
         Error: From<C::Error>,
     {
@@ -272,6 +789,24 @@ impl Request {
     }
 }
 
+impl Request {
+    /// Parses the URL of this request, including the parameters added
+    /// with [`with_param`](#method.with_param).
+    pub(crate) fn parse_url(&self) -> Result<HttpUrl, Error> {
+        let mut url = HttpUrl::parse(&self.url, None)?;
+
+        if !self.params.is_empty() {
+            if url.path_and_query.contains('?') {
+                url.path_and_query.push('&');
+            } else {
+                url.path_and_query.push('?');
+            }
+            url.path_and_query.push_str(&self.params);
+        }
+        Ok(url)
+    }
+}
+
 pub(crate) struct ParsedRequest {
     pub(crate) url: HttpUrl,
     pub(crate) redirects: Vec<HttpUrl>,
@@ -281,16 +816,7 @@ pub(crate) struct ParsedRequest {
 impl ParsedRequest {
     #[allow(unused_mut)]
     fn new(mut config: Request) -> Result<ParsedRequest, Error> {
-        let mut url = HttpUrl::parse(&config.url, None)?;
-
-        if !config.params.is_empty() {
-            if url.path_and_query.contains('?') {
-                url.path_and_query.push('&');
-            } else {
-                url.path_and_query.push('?');
-            }
-            url.path_and_query.push_str(&config.params);
-        }
+        let url = config.parse_url()?;
 
         #[cfg(feature = "proxy")]
         // Set default proxy from environment variables
@@ -334,9 +860,24 @@ impl ParsedRequest {
         })
     }
 
-    fn get_http_head(&self) -> String {
+    /// Returns how long to wait for `100 Continue` before sending the
+    /// body, or `None` if the body is sent right away.
+    pub(crate) fn expect_continue_timeout(&self) -> Option<u32> {
+        match self.config.body {
+            Some(ref body) if !body.is_empty() => self.config.expect_continue_timeout,
+            _ => None,
+        }
+    }
+
+    pub(crate) fn get_http_head(&self) -> String {
         let mut http = String::with_capacity(32);
+        self.write_http_head(&mut http).unwrap();
+        http
+    }
 
+    /// Writes the head of the request into `http`, eg. a buffer of
+    /// the caller's for [`Request::send_into`].
+    pub(crate) fn write_http_head<W: Write>(&self, http: &mut W) -> fmt::Result {
         // NOTE: As of 2.10.0, the fragment is intentionally left out of the request, based on:
         // - [RFC 3986 section 3.5](https://datatracker.ietf.org/doc/html/rfc3986#section-3.5):
         //   "...the fragment identifier is not used in the scheme-specific
@@ -346,21 +887,49 @@ impl ParsedRequest {
         //   "Although fragment identifiers used within URI references are not
         //   sent in requests..."
 
-        // Add the request line and the "Host" header
-        write!(
-            http,
-            "{} {} HTTP/1.1\r\nHost: {}",
-            self.config.method, self.url.path_and_query, self.url.host
-        )
-        .unwrap();
-        if let Port::Explicit(port) = self.url.port {
-            write!(http, ":{}", port).unwrap();
+        // Add the request line and the "Host" header. CONNECT requests
+        // name the host and port to tunnel to instead of a path, and
+        // always with the port, also in the "Host" header, see
+        // [RFC 9110 section 9.3.6](https://datatracker.ietf.org/doc/html/rfc9110#section-9.3.6).
+        if self.config.method == Method::Connect {
+            let port = self.url.port.port();
+            write!(
+                http,
+                "CONNECT {0}:{1} HTTP/1.1\r\nHost: {0}:{1}",
+                self.url.host, port
+            )?;
+        } else {
+            write!(
+                http,
+                "{} {} HTTP/1.1\r\nHost: {}",
+                self.config.method, self.url.path_and_query, self.url.host
+            )?;
+            if let Port::Explicit(port) = self.url.port {
+                write!(http, ":{}", port)?;
+            }
         }
-        http += "\r\n";
+        http.write_str("\r\n")?;
 
         // Add other headers
         for (k, v) in &self.config.headers {
-            write!(http, "{}: {}\r\n", k, v).unwrap();
+            write!(http, "{}: {}\r\n", k, v)?;
+        }
+        if self.expect_continue_timeout().is_some() {
+            http.write_str("Expect: 100-continue\r\n")?;
+        }
+        #[cfg(feature = "compression")]
+        if self.config.decompression
+            && !self
+                .config
+                .headers
+                .keys()
+                .any(|key| key.eq_ignore_ascii_case("accept-encoding"))
+        {
+            write!(
+                http,
+                "Accept-Encoding: {}\r\n",
+                compression::ACCEPT_ENCODING
+            )?;
         }
 
         if self.config.method == Method::Post
@@ -368,8 +937,8 @@ impl ParsedRequest {
             || self.config.method == Method::Patch
         {
             let not_length = |key: &String| {
-                let key = key.to_lowercase();
-                key != "content-length" && key != "transfer-encoding"
+                !key.eq_ignore_ascii_case("content-length")
+                    && !key.eq_ignore_ascii_case("transfer-encoding")
             };
             if self.config.headers.keys().all(not_length) {
                 // A user agent SHOULD send a Content-Length in a request message when no Transfer-Encoding
@@ -380,12 +949,37 @@ impl ParsedRequest {
                 // refer: https://tools.ietf.org/html/rfc7231#section-4.3.8
                 // similar line found for GET, HEAD, CONNECT and DELETE.
 
-                http += "Content-Length: 0\r\n";
+                http.write_str("Content-Length: 0\r\n")?;
             }
         }
 
-        http += "\r\n";
-        http
+        http.write_str("\r\n")
+    }
+
+    /// Returns whether the body has to be written with
+    /// [`write_body`](#method.write_body), instead of along with the
+    /// head by [`as_bytes`](#method.as_bytes).
+    pub(crate) fn writes_body_separately(&self) -> bool {
+        #[cfg(feature = "compression")]
+        if self.config.gzip_body.is_some() {
+            return true;
+        }
+        self.config.progress.is_some()
+    }
+
+    /// Writes the request body, compressing it and reporting the
+    /// progress if requested.
+    pub(crate) async fn write_body<W: embedded_io_async::Write>(
+        &self,
+        stream: &mut W,
+    ) -> Result<(), W::Error> {
+        let body = self.config.body.as_deref().unwrap_or_default();
+        let progress = self.config.progress.as_ref();
+        #[cfg(feature = "compression")]
+        if let Some(options) = self.config.gzip_body {
+            return compression::write_gzip_chunked(stream, body, options, progress).await;
+        }
+        progress::write_body(stream, body, progress).await
     }
 
     /// Returns the HTTP request as bytes, ready to be sent to
@@ -515,6 +1109,40 @@ mod parsing_tests {
             ParsedRequest::new(get("https://www.example.org/").with_param("foo", "bar")).unwrap();
         assert!(req.url.https);
     }
+
+    #[test]
+    fn test_connect_host() {
+        let req = ParsedRequest::new(super::connect("http://example.com")).unwrap();
+        assert!(req
+            .get_http_head()
+            .starts_with("CONNECT example.com:80 HTTP/1.1\r\nHost: example.com:80\r\n"));
+        let req = ParsedRequest::new(super::connect("http://example.com:8080")).unwrap();
+        assert!(req
+            .get_http_head()
+            .starts_with("CONNECT example.com:8080 HTTP/1.1\r\nHost: example.com:8080\r\n"));
+    }
+
+    #[test]
+    fn test_range() {
+        let range = |req: super::Request| req.headers["Range"].clone();
+        assert_eq!(range(get("http://a.b").with_range(5..10)), "bytes=5-9");
+        assert_eq!(range(get("http://a.b").with_range(5..=5)), "bytes=5-5");
+        assert_eq!(range(get("http://a.b").with_range(..1)), "bytes=0-0");
+        assert_eq!(range(get("http://a.b").with_range(7..)), "bytes=7-");
+    }
+
+    #[test]
+    #[should_panic(expected = "empty or reversed")]
+    fn test_empty_range() {
+        let _ = get("http://a.b").with_range(5..5);
+    }
+
+    #[test]
+    #[should_panic(expected = "empty or reversed")]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn test_reversed_range() {
+        let _ = get("http://a.b").with_range(5..=3);
+    }
 }
 
 #[cfg(all(test, feature = "urlencoding"))]
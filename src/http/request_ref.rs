@@ -0,0 +1,258 @@
+use crate::http::response_ref::SliceWriter;
+use crate::http::{Error, Method, ResponseRef};
+use crate::tcp::HttpConnect;
+use core::fmt::{self, Write};
+use esp_idf_hal::io::EspIOError;
+
+/// An HTTP request which borrows its URL, headers and body from the
+/// caller, for sending with [`send_into`](#method.send_into) without
+/// allocating.
+///
+/// Unlike a [`Request`](struct.Request.html), nothing is copied or
+/// parsed into owned values: the request head is written straight
+/// into the caller's buffer, and the response is read into it as a
+/// [`ResponseRef`](struct.ResponseRef.html). The headers and body can
+/// come from anywhere, eg. `heapless` types or `static` data.
+///
+/// This is a bare mode: the URL is sent as given, without
+/// percent-encoding or punycode, redirects are not followed, and
+/// there are no retries, decompression, progress reports or
+/// cancellation. The connection itself is opened with
+/// [`HttpConnect::connect_http`](tcp/trait.HttpConnect.html), which
+/// may allocate, eg. esp-tls allocates its own buffers.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn main() -> Result<(), esp_minreq::Error> {
+/// use embedded_io_async::Read;
+/// let mut buf = [0; 1024];
+/// let mut headers = [("", ""); 8];
+/// let mut response = esp_minreq::RequestRef::new(esp_minreq::Method::Post, "http://example.com/log")
+///     .with_headers(&[("Content-Type", "text/plain")])
+///     .with_body(b"booted")
+///     .with_header_filter(&["content-type"])
+///     .send_into::<esp_minreq::tcp::HttpStream>(&mut buf, &mut headers)
+///     .await?;
+/// let mut body = [0; 256];
+/// let length = response.read(&mut body).await?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RequestRef<'a> {
+    method: Method,
+    url: &'a str,
+    headers: &'a [(&'a str, &'a str)],
+    body: &'a [u8],
+    header_filter: Option<&'a [&'a str]>,
+}
+
+impl<'a> RequestRef<'a> {
+    /// Creates a request for `url`, which has to start with `http://`
+    /// or `https://`.
+    pub fn new(method: Method, url: &'a str) -> RequestRef<'a> {
+        RequestRef {
+            method,
+            url,
+            headers: &[],
+            body: &[],
+            header_filter: None,
+        }
+    }
+
+    /// Sets the headers of the request, which are sent as they are.
+    pub fn with_headers(mut self, headers: &'a [(&'a str, &'a str)]) -> RequestRef<'a> {
+        self.headers = headers;
+        self
+    }
+
+    /// Sets the request body. A `Content-Length` header is added,
+    /// unless the headers already have one, or a `Transfer-Encoding`.
+    pub fn with_body(mut self, body: &'a [u8]) -> RequestRef<'a> {
+        self.body = body;
+        self
+    }
+
+    /// Keeps only the response headers called one of `names`, see
+    /// [`Request::with_header_filter`](struct.Request.html#method.with_header_filter),
+    /// so fewer slots are needed for the headers.
+    pub fn with_header_filter(mut self, names: &'a [&'a str]) -> RequestRef<'a> {
+        self.header_filter = Some(names);
+        self
+    }
+
+    /// Sends this request, writing its head into `buf`, and reads the
+    /// response into `buf` and `headers`, see
+    /// [`Request::send_into`](struct.Request.html#method.send_into).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InvalidProtocol`](enum.Error.html#variant.InvalidProtocol)
+    /// if the URL isn't an `http` or `https` one,
+    /// [`PunycodeConversionFailed`](enum.Error.html#variant.PunycodeConversionFailed)
+    /// if its host isn't ASCII,
+    /// [`BufferTooSmall`](enum.Error.html#variant.BufferTooSmall) if
+    /// `buf` can't hold the request head or the response head, or
+    /// `headers` has less room than there are response headers, and
+    /// [`IoError`](enum.Error.html#variant.IoError) if the connection
+    /// fails.
+    pub async fn send_into<'b, C: HttpConnect>(
+        self,
+        buf: &'b mut [u8],
+        headers: &'b mut [(&'b str, &'b str)],
+    ) -> Result<ResponseRef<'b, C>, Error>
+    where
+        Error: From<C::Error>,
+    {
+        let url = UrlParts::parse(self.url)?;
+        if !url.host.is_ascii() {
+            return Err(Error::PunycodeConversionFailed);
+        }
+
+        log::trace!("Establishing TCP connection to {}.", url.host);
+        let mut tcp = C::connect_http(self.url, !url.https)
+            .await
+            .map_err(|e| Error::IoError(EspIOError(e)))?;
+
+        log::trace!("Writing HTTP request.");
+        let mut head = SliceWriter::new(buf);
+        self.write_head(&mut head, &url)
+            .map_err(|_| Error::BufferTooSmall)?;
+        tcp.write_all(head.written()).await?;
+        tcp.write_all(self.body).await?;
+
+        log::trace!("Reading HTTP response.");
+        let is_head = self.method == Method::Head;
+        ResponseRef::from_stream(tcp, buf, headers, self.header_filter, is_head).await
+    }
+
+    fn write_head<W: Write>(&self, http: &mut W, url: &UrlParts) -> fmt::Result {
+        if self.method == Method::Connect {
+            let port = url.port.unwrap_or(if url.https { 443 } else { 80 });
+            write!(
+                http,
+                "CONNECT {0}:{1} HTTP/1.1\r\nHost: {0}:{1}\r\n",
+                url.host, port
+            )?;
+        } else {
+            write!(http, "{} ", self.method)?;
+            if !url.path_and_query.starts_with('/') {
+                http.write_char('/')?;
+            }
+            write!(
+                http,
+                "{} HTTP/1.1\r\nHost: {}",
+                url.path_and_query, url.host
+            )?;
+            if let Some(port) = url.port {
+                write!(http, ":{}", port)?;
+            }
+            http.write_str("\r\n")?;
+        }
+
+        for (name, value) in self.headers {
+            write!(http, "{}: {}\r\n", name, value)?;
+        }
+        let has_length = self.headers.iter().any(|(name, _)| {
+            name.eq_ignore_ascii_case("content-length")
+                || name.eq_ignore_ascii_case("transfer-encoding")
+        });
+        let has_body = !self.body.is_empty()
+            || matches!(self.method, Method::Post | Method::Put | Method::Patch);
+        if has_body && !has_length {
+            write!(http, "Content-Length: {}\r\n", self.body.len())?;
+        }
+        http.write_str("\r\n")
+    }
+}
+
+/// The parts of a URL needed to send a request, borrowed from it.
+struct UrlParts<'a> {
+    https: bool,
+    host: &'a str,
+    /// The port, if the URL has a valid one.
+    port: Option<u32>,
+    /// The path and query, which may lack the leading `/`.
+    path_and_query: &'a str,
+}
+
+impl<'a> UrlParts<'a> {
+    fn parse(url: &'a str) -> Result<UrlParts<'a>, Error> {
+        let (rest, https) = if let Some(rest) = url.strip_prefix("http://") {
+            (rest, false)
+        } else if let Some(rest) = url.strip_prefix("https://") {
+            (rest, true)
+        } else {
+            return Err(Error::InvalidProtocol);
+        };
+        // The fragment is not sent, see Request.
+        let rest = rest.split_once('#').map_or(rest, |(rest, _)| rest);
+        let end = rest.find(['/', '?']).unwrap_or(rest.len());
+        let (authority, path_and_query) = rest.split_at(end);
+        // Like Request, invalid ports fall back to the scheme's one.
+        let (host, port) = match authority.split_once(':') {
+            Some((host, port)) => (host, port.parse().ok()),
+            None => (authority, None),
+        };
+        Ok(UrlParts {
+            https,
+            host,
+            port,
+            path_and_query,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RequestRef, UrlParts};
+    use crate::http::test_support::{block_on, Stream};
+    use crate::http::{Error, Method};
+    use alloc::string::String;
+    use embedded_io_async::Read;
+
+    fn head(request: &RequestRef) -> String {
+        let mut head = String::new();
+        let url = UrlParts::parse(request.url).unwrap();
+        request.write_head(&mut head, &url).unwrap();
+        head
+    }
+
+    #[test]
+    fn writes_head_from_borrowed_parts() {
+        let request = RequestRef::new(Method::Post, "http://example.com:8080?a=1#top")
+            .with_headers(&[("X-Id", "7")])
+            .with_body(b"hi");
+        assert_eq!(
+            head(&request),
+            "POST /?a=1 HTTP/1.1\r\nHost: example.com:8080\r\nX-Id: 7\r\nContent-Length: 2\r\n\r\n"
+        );
+        let request = RequestRef::new(Method::Connect, "https://example.com");
+        assert_eq!(
+            head(&request),
+            "CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn sends_into_buffers() {
+        Stream::script(Stream::new(
+            b"HTTP/1.1 200 OK\r\nServer: x\r\nContent-Type: text/plain\r\nContent-Length: 2\r\n\r\nok",
+            64,
+        ));
+        let mut buf = [0; 128];
+        let mut headers = [("", ""); 2];
+        let body = block_on(async {
+            let mut response = RequestRef::new(Method::Get, "http://example.com/a")
+                .with_header_filter(&["Content-Type"])
+                .send_into::<Stream>(&mut buf, &mut headers)
+                .await?;
+            assert_eq!(response.header("content-type"), Some("text/plain"));
+            let mut body = [0; 8];
+            let length = response.read(&mut body).await?;
+            Ok::<_, Error>(body[..length].to_vec())
+        })
+        .unwrap();
+        assert_eq!(body, b"ok");
+    }
+}
@@ -1,15 +1,35 @@
 use crate::buf_reader::BufReader;
-use crate::bytes_iter::BytesIter;
-use crate::http::Error;
+use crate::http::cancel::cancellable;
+#[cfg(feature = "compression")]
+use crate::http::compression::Decoder;
+#[cfg(feature = "json")]
+use crate::http::decode_task::decode_in_task;
+use crate::http::headers::{self, CacheControl, ContentDisposition, MediaType};
+use crate::http::lines::Lines;
+#[cfg(feature = "json")]
+use crate::http::lines::Ndjson;
+use crate::http::progress::{Direction, ProgressConfig, ProgressTracker};
+use crate::http::range::{self, ContentRange};
+use crate::http::request::ParsedRequest;
+use crate::http::text::{self, TextReader};
+use crate::http::{CancellationToken, Error, StatusCode};
+use crate::tcp::ConnectionInfo;
+use crate::timer;
+use alloc::borrow::Cow;
+#[cfg(feature = "compression")]
+use alloc::boxed::Box;
 use alloc::collections::btree_map::BTreeMap as HashMap;
 use alloc::str;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
-use embedded_io_async::{ErrorType, Read};
+use embedded_io_async::{BufRead, Error as _, ErrorType, Read, Write};
 // use std::io::{self, BufReader, Bytes, ErrorKind, Read};
 
 const BACKING_READ_BUFFER_LENGTH: usize = 16 * 1024;
 const MAX_CONTENT_LENGTH: usize = 16 * 1024;
+/// The stack of the task deserializing `ResponseLazy::json`.
+#[cfg(feature = "json")]
+const JSON_TASK_STACK_SIZE: u32 = 8 * 1024;
 
 /// An HTTP response.
 ///
@@ -32,41 +52,51 @@ pub struct Response {
     /// The headers of the response. The header field names (the
     /// keys) are all lowercase.
     pub headers: HashMap<String, String>,
+    /// The trailer fields sent after a chunked body, with lowercase
+    /// names. They are only known once the whole body has been read,
+    /// and are not added to the headers.
+    pub trailers: HashMap<String, String>,
     /// The URL of the resource returned in this response. May differ from the
     /// request URL if it was redirected or typo corrections were applied (e.g.
     /// <http://example.com?foo=bar> would be corrected to
     /// <http://example.com/?foo=bar>).
     pub url: String,
+    /// The redirections that were followed to arrive at this
+    /// response, in the order they happened.
+    pub redirects: Vec<Redirect>,
+    /// Metadata about the connection this response was received on.
+    pub connection: ConnectionInfo,
+    /// The informational (1xx) responses the server sent before this
+    /// response, eg. `103 Early Hints`.
+    pub informational: Vec<InformationalResponse>,
 
-    body: Vec<u8>,
+    pub(crate) body: Vec<u8>,
 }
 
 impl Response {
-    pub(crate) async fn create<R: Read>(
+    pub(crate) async fn create<R: Read + BufRead>(
         mut parent: ResponseLazy<R>,
         is_head: bool,
     ) -> Result<Response, Error>
     where
         R::Error: Into<Error>,
     {
-        let mut body = Vec::new();
+        let mut body = VecSink(Vec::new());
         if !is_head && parent.status_code != 204 && parent.status_code != 304 {
-            while let Some(byte) = parent.next().await {
-                match byte {
-                    Ok((byte, length)) => {
-                        body.reserve(length);
-                        body.push(byte);
-                    }
-                    Err(err) => return Err(err),
-                }
-            }
+            let length = parent.remaining_length().unwrap_or(0);
+            body.0.reserve(length.min(MAX_CONTENT_LENGTH));
+            parent.copy_to(&mut body).await?;
         }
 
         let ResponseLazy {
             status_code,
             reason_phrase,
             headers,
+            trailers,
             url,
+            redirects,
+            connection,
+            informational,
             ..
         } = parent;
 
@@ -74,8 +104,12 @@ impl Response {
             status_code,
             reason_phrase,
             headers,
+            trailers,
             url,
-            body,
+            redirects,
+            connection,
+            informational,
+            body: body.0,
         })
     }
 
@@ -105,6 +139,47 @@ impl Response {
         }
     }
 
+    /// Returns the body as text, decoded according to the `charset`
+    /// of the `Content-Type` header, or as UTF-8 if there is none.
+    ///
+    /// Besides UTF-8, the charsets of [`Charset`](enum.Charset.html)
+    /// are supported, eg. ISO-8859-1 and Windows-1252, which some
+    /// older devices send. UTF-8 bodies are borrowed, as with
+    /// [`as_str()`](#method.as_str).
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`InvalidUtf8InBody`](enum.Error.html#variant.InvalidUtf8InBody)
+    /// if a UTF-8 body is invalid, and
+    /// [`UnsupportedCharset`](enum.Error.html#variant.UnsupportedCharset)
+    /// if the charset is not supported. See
+    /// [`text_lossy()`](#method.text_lossy) for a variant which
+    /// doesn't fail.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn main() -> Result<(), esp_minreq::Error> {
+    /// let response = esp_minreq::get("http://192.168.1.20/status.txt")
+    ///     .send::<esp_minreq::tcp::HttpStream>()
+    ///     .await?;
+    /// println!("{}", response.text()?);
+    /// # Ok(()) }
+    /// ```
+    pub fn text(&self) -> Result<Cow<'_, str>, Error> {
+        let charset = text::charset(&self.headers, false)?;
+        text::decode(&self.body, charset, false)
+    }
+
+    /// Like [`text()`](#method.text), but replaces invalid UTF-8 with
+    /// `U+FFFD REPLACEMENT CHARACTER`, and decodes bodies with an
+    /// unsupported charset as UTF-8.
+    pub fn text_lossy(&self) -> Cow<'_, str> {
+        let charset = text::charset(&self.headers, true).unwrap_or(text::Charset::Utf8);
+        text::decode(&self.body, charset, true).unwrap_or_default()
+    }
+
     /// Returns a reference to the contained bytes of the body. If you
     /// want the `Vec<u8>` itself, use
     /// [`into_bytes()`](#method.into_bytes) instead.
@@ -199,6 +274,196 @@ impl Response {
             Err(err) => Err(Error::SerdeJsonError(err)),
         }
     }
+
+    /// Converts a CBOR body to a `struct` using Serde. The body is
+    /// decoded directly from its bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`SerdeCborDecodeError`](enum.Error.html#variant.SerdeCborDecodeError)
+    /// if Serde runs into a problem.
+    #[cfg(feature = "cbor")]
+    pub fn cbor<T>(&self) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        // Strings and byte strings up to this length are decoded
+        // without an allocation. Kept small for the stack's sake.
+        let mut scratch = [0; 256];
+        match ciborium::from_reader_with_buffer(self.as_bytes(), &mut scratch) {
+            Ok(cbor) => Ok(cbor),
+            Err(err) => Err(Error::SerdeCborDecodeError(err)),
+        }
+    }
+
+    /// Converts a MessagePack body to a `struct` using Serde. The
+    /// body is decoded directly from its bytes, so `T` can borrow
+    /// strings from it.
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`SerdeMsgpackDecodeError`](enum.Error.html#variant.SerdeMsgpackDecodeError)
+    /// if Serde runs into a problem.
+    #[cfg(feature = "msgpack")]
+    pub fn msgpack<'a, T>(&'a self) -> Result<T, Error>
+    where
+        T: serde::de::Deserialize<'a>,
+    {
+        match rmp_serde::from_slice(self.as_bytes()) {
+            Ok(msgpack) => Ok(msgpack),
+            Err(err) => Err(Error::SerdeMsgpackDecodeError(err)),
+        }
+    }
+
+    /// Converts a postcard body to a `struct` using Serde. The body
+    /// is decoded directly from its bytes, so `T` can borrow strings
+    /// from it.
+    ///
+    /// There is no `ResponseLazy::postcard`, as `T` borrows from the
+    /// body: read it with
+    /// [`ResponseLazy::copy_to`](struct.ResponseLazy.html#method.copy_to)
+    /// into a buffer and give that to `postcard::from_bytes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`SerdePostcardError`](enum.Error.html#variant.SerdePostcardError)
+    /// if Serde runs into a problem.
+    #[cfg(feature = "postcard")]
+    pub fn postcard<'a, T>(&'a self) -> Result<T, Error>
+    where
+        T: serde::de::Deserialize<'a>,
+    {
+        match postcard::from_bytes(self.as_bytes()) {
+            Ok(postcard) => Ok(postcard),
+            Err(err) => Err(Error::SerdePostcardError(err)),
+        }
+    }
+
+    /// Returns the status code of the response.
+    pub fn status(&self) -> StatusCode {
+        StatusCode::new(self.status_code)
+    }
+
+    /// Turns a client or server error (4xx or 5xx) response into an
+    /// error, so it can be handled with `?`.
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`UnexpectedStatus`](enum.Error.html#variant.UnexpectedStatus)
+    /// with the status code if it is 400 or above.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn main() -> Result<(), esp_minreq::Error> {
+    /// let response = esp_minreq::get("http://example.com")
+    ///     .send()
+    ///     .await?
+    ///     .error_for_status()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn error_for_status(self) -> Result<Response, Error> {
+        if self.status().is_error() {
+            Err(Error::UnexpectedStatus(self.status_code))
+        } else {
+            Ok(self)
+        }
+    }
+
+    /// Returns the parsed `Content-Range` header of a `206 Partial
+    /// Content` or `416 Range Not Satisfiable` response, if present
+    /// and valid.
+    pub fn content_range(&self) -> Option<ContentRange> {
+        range::content_range(&self.headers)
+    }
+
+    /// Returns whether the server advertised support for byte range
+    /// requests with `Accept-Ranges: bytes`.
+    pub fn accepts_ranges(&self) -> bool {
+        range::accepts_ranges(&self.headers)
+    }
+
+    /// Returns the parsed `Content-Type` header, if present and
+    /// valid.
+    pub fn content_type(&self) -> Option<MediaType<'_>> {
+        headers::content_type(&self.headers)
+    }
+
+    /// Returns the `Content-Length` header as a number, if present
+    /// and valid.
+    pub fn content_length(&self) -> Option<u64> {
+        headers::content_length(&self.headers)
+    }
+
+    /// Returns the `Date` header, in seconds since the Unix epoch, if
+    /// present and valid.
+    pub fn date(&self) -> Option<u64> {
+        headers::http_date(&self.headers, "date")
+    }
+
+    /// Returns the `Last-Modified` header, in seconds since the Unix
+    /// epoch, if present and valid.
+    pub fn last_modified(&self) -> Option<u64> {
+        headers::http_date(&self.headers, "last-modified")
+    }
+
+    /// Returns the directives of the `Cache-Control` header, which
+    /// are all unset if it is missing.
+    pub fn cache_control(&self) -> CacheControl {
+        headers::cache_control(&self.headers)
+    }
+
+    /// Returns the parsed `Content-Disposition` header, if present.
+    /// Use [`ContentDisposition::filename`] for the suggested file
+    /// name.
+    pub fn content_disposition(&self) -> Option<ContentDisposition<'_>> {
+        headers::content_disposition(&self.headers)
+    }
+}
+
+/// A redirection that was followed while sending a request.
+///
+/// See [`Response::redirects`](struct.Response.html#structfield.redirects).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Redirect {
+    /// The URL that responded with the redirection.
+    pub url: String,
+    /// The status code of the redirection, eg. 301.
+    pub status_code: i32,
+    /// The value of the `Location` header, as sent by the server.
+    pub location: String,
+}
+
+/// An informational (1xx) response, which precedes the final
+/// response.
+///
+/// See [`Response::informational`](struct.Response.html#structfield.informational).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct InformationalResponse {
+    /// The status code of the response, eg. 103.
+    pub status_code: i32,
+    /// The reason phrase of the response, eg. "Early Hints".
+    pub reason_phrase: String,
+    /// The headers of the response. The header field names (the
+    /// keys) are all lowercase.
+    pub headers: HashMap<String, String>,
+}
+
+/// A chunk of a `Transfer-Encoding: chunked` body, see
+/// [`ResponseLazy::next_chunk`](struct.ResponseLazy.html#method.next_chunk).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Chunk {
+    /// The data of the chunk as it was sent, ie. still compressed if
+    /// the response has a `Content-Encoding`.
+    pub data: Vec<u8>,
+    /// The chunk extensions, eg. `("sig", "abc")` for `;sig=abc`, in
+    /// the order they were sent. The names are lowercase, and
+    /// extensions without a value have an empty one.
+    pub extensions: Vec<(String, String)>,
 }
 
 /// An HTTP response, which is loaded lazily.
@@ -209,29 +474,28 @@ impl Response {
 /// [`Response`](struct.Response.html) is returned from
 /// [`send()`](struct.Request.html#method.send).
 ///
-/// In practice, "lazy loading" means that the bytes are only loaded
-/// as you iterate through them. The bytes are provided in the form of
-/// a `Result<(u8, usize), esp_minreq::Error>`, as the reading operation
-/// can fail in various ways. The `u8` is the actual byte that was
-/// read, and `usize` is how many bytes we are expecting to read in
-/// the future (including this byte). Note, however, that the `usize`
-/// can change, particularly when the `Transfer-Encoding` is
-/// `chunked`: then it will reflect how many bytes are left of the
-/// current chunk. The expected size is capped at 16 KiB to avoid
-/// server-side DoS attacks targeted at clients accidentally reserving
-/// too much memory.
+/// In practice, "lazy loading" means that the body is only read from
+/// the connection as you read it, eg. with
+/// [`Read::read`](embedded_io_async::Read::read),
+/// [`copy_to`](#method.copy_to) or [`lines`](#method.lines). Each
+/// read takes as much as is already buffered, up to the end of the
+/// body or of the current chunk, so the body can be read in large
+/// pieces without holding all of it in memory.
 ///
 /// # Example
 /// ```no_run
-/// // This is how the normal Response works behind the scenes, and
-/// // how you might use ResponseLazy.
 /// # async fn main() -> Result<(), esp_minreq::Error> {
-/// let response = esp_minreq::get("http://example.com").send_lazy().await?;
-/// let mut vec = Vec::new();
-/// for result in response {
-///     let (byte, length) = result?;
-///     vec.reserve(length);
-///     vec.push(byte);
+/// use embedded_io_async::Read;
+/// let mut response = esp_minreq::get("http://example.com")
+///     .send_lazy::<esp_minreq::tcp::HttpStream>()
+///     .await?;
+/// let mut buf = [0; 1024];
+/// loop {
+///     let length = response.read(&mut buf).await?;
+///     if length == 0 {
+///         break;
+///     }
+///     println!("Received {} bytes.", length);
 /// }
 /// # Ok(())
 /// # }
@@ -245,15 +509,41 @@ pub struct ResponseLazy<R: Read> {
     /// The headers of the response. The header field names (the
     /// keys) are all lowercase.
     pub headers: HashMap<String, String>,
+    /// The trailer fields sent after a chunked body, with lowercase
+    /// names. They are only known once the whole body has been read,
+    /// and are not added to the headers.
+    pub trailers: HashMap<String, String>,
     /// The URL of the resource returned in this response. May differ from the
     /// request URL if it was redirected or typo corrections were applied (e.g.
     /// <http://example.com?foo=bar> would be corrected to
     /// <http://example.com/?foo=bar>).
     pub url: String,
+    /// The redirections that were followed to arrive at this
+    /// response, in the order they happened.
+    pub redirects: Vec<Redirect>,
+    /// Metadata about the connection this response was received on.
+    pub connection: ConnectionInfo,
+    /// The informational (1xx) responses the server sent before this
+    /// response, eg. `103 Early Hints`.
+    pub informational: Vec<InformationalResponse>,
 
     stream: R,
     state: HttpStreamState,
     max_trailing_headers_size: Option<usize>,
+    /// The extensions of the current chunk of a chunked body.
+    chunk_extensions: Vec<(String, String)>,
+    progress: Option<ProgressTracker>,
+    pub(crate) cancellation: Option<CancellationToken>,
+    /// Whether the connection switched to another protocol, after a
+    /// `101 Switching Protocols` or a successful `CONNECT`.
+    pub(crate) upgraded: bool,
+    /// The [uptime](crate::timer::uptime_ms) when the request was
+    /// written to the connection, once it was established.
+    pub(crate) sent_at_ms: u64,
+    max_body_size: Option<usize>,
+    body_size: usize,
+    #[cfg(feature = "compression")]
+    decoder: Option<Box<Decoder>>,
 }
 
 impl<R: Read> ResponseLazy<R>
@@ -262,235 +552,980 @@ where
 {
     pub(crate) async fn from_stream(
         stream: R,
-        max_headers_size: Option<usize>,
+        mut max_headers_size: Option<usize>,
         max_status_line_len: Option<usize>,
+        header_filter: Option<&[String]>,
     ) -> Result<ResponseLazy<BufReader<R>>, Error> {
         let mut stream = BufReader::with_capacity(BACKING_READ_BUFFER_LENGTH, stream);
+        let mut informational = Vec::new();
+        let metadata = read_metadata(
+            &mut stream,
+            &mut max_headers_size,
+            max_status_line_len,
+            header_filter,
+            &mut informational,
+            false,
+        )
+        .await?
+        .ok_or(Error::Other(
+            "read_metadata stopped without stop_at_continue",
+        ))?;
+        Ok(ResponseLazy::from_metadata(stream, metadata, informational))
+    }
+
+    /// Like [`from_stream`](#method.from_stream), but only writes
+    /// the body of `request` after the server answers with `100
+    /// Continue`, or after `timeout_ms` passes without an answer. If
+    /// the server answers with a final response instead, the body is
+    /// never sent.
+    pub(crate) async fn from_stream_expecting_continue(
+        stream: R,
+        request: &ParsedRequest,
+        timeout_ms: u32,
+        mut max_headers_size: Option<usize>,
+        max_status_line_len: Option<usize>,
+    ) -> Result<ResponseLazy<BufReader<R>>, Error>
+    where
+        R: Write,
+    {
+        let mut stream = BufReader::with_capacity(BACKING_READ_BUFFER_LENGTH, stream);
+        let mut informational = Vec::new();
+        let header_filter = request.config.header_filter.as_deref();
+        let answered = timer::timeout(timeout_ms, stream.fill_buf())
+            .await
+            .map(|result| result.map(|_| ()));
+        match answered {
+            Some(Ok(())) => {
+                if let Some(metadata) = read_metadata(
+                    &mut stream,
+                    &mut max_headers_size,
+                    max_status_line_len,
+                    header_filter,
+                    &mut informational,
+                    true,
+                )
+                .await?
+                {
+                    log::debug!(
+                        "Server answered {} before the request body was sent.",
+                        metadata.status_code
+                    );
+                    return Ok(ResponseLazy::from_metadata(stream, metadata, informational));
+                }
+            }
+            Some(Err(err)) => return Err(err.into()),
+            None => log::trace!("No 100 Continue received in time, sending the body anyway."),
+        }
+
+        log::trace!("Writing HTTP request body.");
+        request
+            .write_body(stream.get_mut())
+            .await
+            .map_err(Into::into)?;
+        let metadata = read_metadata(
+            &mut stream,
+            &mut max_headers_size,
+            max_status_line_len,
+            header_filter,
+            &mut informational,
+            false,
+        )
+        .await?
+        .ok_or(Error::Other(
+            "read_metadata stopped without stop_at_continue",
+        ))?;
+        Ok(ResponseLazy::from_metadata(stream, metadata, informational))
+    }
+
+    fn from_metadata(
+        stream: R,
+        metadata: ResponseMetadata,
+        informational: Vec<InformationalResponse>,
+    ) -> ResponseLazy<R> {
         let ResponseMetadata {
             status_code,
             reason_phrase,
             headers,
             state,
             max_trailing_headers_size,
-        } = read_metadata(&mut stream, max_headers_size, max_status_line_len).await?;
+        } = metadata;
 
-        Ok(ResponseLazy {
+        ResponseLazy {
             status_code,
             reason_phrase,
             headers,
+            trailers: HashMap::new(),
             url: String::new(),
+            redirects: Vec::new(),
+            connection: ConnectionInfo::default(),
+            informational,
             stream,
             state,
             max_trailing_headers_size,
-        })
+            chunk_extensions: Vec::new(),
+            progress: None,
+            cancellation: None,
+            upgraded: status_code == 101,
+            sent_at_ms: 0,
+            max_body_size: None,
+            body_size: 0,
+            #[cfg(feature = "compression")]
+            decoder: None,
+        }
     }
 
-    async fn next(&mut self) -> Option<Result<(u8, usize), Error>> {
-        use HttpStreamState::*;
-        match self.state {
-            EndOnClose => read_until_closed(&mut self.stream).await,
-            ContentLength(ref mut length) => {
-                read_with_content_length(&mut self.stream, length).await
-            }
-            Chunked(ref mut expecting_chunks, ref mut length, ref mut content_length) => {
-                read_chunked(
-                    &mut self.stream,
-                    &mut self.headers,
-                    expecting_chunks,
-                    length,
-                    content_length,
-                    self.max_trailing_headers_size,
-                )
-                .await
-            }
-        }
+    /// Returns the status code of the response.
+    pub fn status(&self) -> StatusCode {
+        StatusCode::new(self.status_code)
     }
-}
 
-impl<R: Read> ErrorType for ResponseLazy<R> {
-    type Error = Error;
-}
+    /// Returns the parsed `Content-Range` header of a `206 Partial
+    /// Content` or `416 Range Not Satisfiable` response, if present
+    /// and valid.
+    pub fn content_range(&self) -> Option<ContentRange> {
+        range::content_range(&self.headers)
+    }
 
-impl<R: Read> Read for ResponseLazy<R>
-where
-    R::Error: Into<Self::Error>,
-{
-    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
-        let mut index = 0;
-        while let Some(res) = self.next().await {
-            // there is no use for the estimated length in the read implementation
-            // so it is ignored.
-            let (byte, _) = res?;
+    /// Returns whether the server advertised support for byte range
+    /// requests with `Accept-Ranges: bytes`.
+    pub fn accepts_ranges(&self) -> bool {
+        range::accepts_ranges(&self.headers)
+    }
 
-            buf[index] = byte;
-            index += 1;
+    /// Returns the parsed `Content-Type` header, if present and
+    /// valid.
+    pub fn content_type(&self) -> Option<MediaType<'_>> {
+        headers::content_type(&self.headers)
+    }
 
-            // if the buffer is full, it should stop reading
-            if index >= buf.len() {
-                break;
+    /// Returns the `Content-Length` header as a number, if present
+    /// and valid.
+    pub fn content_length(&self) -> Option<u64> {
+        headers::content_length(&self.headers)
+    }
+
+    /// Returns the `Date` header, in seconds since the Unix epoch, if
+    /// present and valid.
+    pub fn date(&self) -> Option<u64> {
+        headers::http_date(&self.headers, "date")
+    }
+
+    /// Returns the `Last-Modified` header, in seconds since the Unix
+    /// epoch, if present and valid.
+    pub fn last_modified(&self) -> Option<u64> {
+        headers::http_date(&self.headers, "last-modified")
+    }
+
+    /// Returns the directives of the `Cache-Control` header, which
+    /// are all unset if it is missing.
+    pub fn cache_control(&self) -> CacheControl {
+        headers::cache_control(&self.headers)
+    }
+
+    /// Returns the parsed `Content-Disposition` header, if present.
+    /// Use [`ContentDisposition::filename`] for the suggested file
+    /// name.
+    pub fn content_disposition(&self) -> Option<ContentDisposition<'_>> {
+        headers::content_disposition(&self.headers)
+    }
+
+    /// Starts reporting the progress of reading the body.
+    pub(crate) fn track_progress(&mut self, config: &ProgressConfig) {
+        let total = match self.state {
+            HttpStreamState::ContentLength(length) => Some(length as u64),
+            _ => None,
+        };
+        self.progress = Some(config.tracker(Direction::Download, total));
+    }
+
+    /// Limits the size of the body to `max_body_size`. If
+    /// `has_body`, a larger `Content-Length` is rejected right away.
+    pub(crate) fn limit_body_size(
+        &mut self,
+        max_body_size: usize,
+        has_body: bool,
+    ) -> Result<(), Error> {
+        if let HttpStreamState::ContentLength(length) = self.state {
+            if has_body && length > max_body_size {
+                return Err(Error::BodyOverflow);
             }
         }
-
-        // index of the next byte is the number of bytes thats have been read
-        Ok(index)
+        self.max_body_size = Some(max_body_size);
+        Ok(())
     }
-}
 
-async fn read_until_closed<R: Read>(bytes: &mut R) -> Option<Result<(u8, usize), Error>>
-where
-    R::Error: Into<Error>,
-{
-    if let Some(byte) = bytes.next_byte().await {
-        match byte {
-            Ok(byte) => Some(Ok((byte, 1))),
-            Err(err) => Some(Err(err.into())),
+    /// Returns the connection of a response which switched to another
+    /// protocol, after a `101 Switching Protocols` or a successful
+    /// (2xx) response to a [`Method::Connect`](enum.Method.html)
+    /// request. The bytes the server sent after the head are already
+    /// buffered in the returned stream, and are read first.
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`UnexpectedStatus`](enum.Error.html#variant.UnexpectedStatus)
+    /// with the status code if the connection was not upgraded.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn main() -> Result<(), esp_minreq::Error> {
+    /// use embedded_io_async::{Read, Write};
+    /// let response = esp_minreq::get("http://example.com/tunnel")
+    ///     .with_header("Connection", "Upgrade")
+    ///     .with_header("Upgrade", "vendor-protocol/1")
+    ///     .send_lazy::<esp_minreq::tcp::HttpStream>()
+    ///     .await?;
+    /// let mut stream = response.into_upgraded()?;
+    /// stream.write_all(b"hello").await?;
+    /// let mut buf = [0; 64];
+    /// let len = stream.read(&mut buf).await?;
+    /// # Ok(()) }
+    /// ```
+    pub fn into_upgraded(self) -> Result<R, Error> {
+        if self.upgraded {
+            Ok(self.stream)
+        } else {
+            Err(Error::UnexpectedStatus(self.status_code))
         }
-    } else {
-        None
     }
-}
 
-async fn read_with_content_length<R: Read>(
-    bytes: &mut R,
-    content_length: &mut usize,
-) -> Option<Result<(u8, usize), Error>>
-where
-    R::Error: Into<Error>,
-{
-    if *content_length > 0 {
-        *content_length -= 1;
-
-        if let Some(byte) = bytes.next_byte().await {
-            match byte {
-                // Cap Content-Length to 16KiB, to avoid out-of-memory issues.
-                Ok(byte) => return Some(Ok((byte, (*content_length).min(MAX_CONTENT_LENGTH) + 1))),
-                Err(err) => return Some(Err(err.into())),
-            }
+    /// Decompresses the body while it is read, if it has a supported
+    /// `Content-Encoding`.
+    #[cfg(feature = "compression")]
+    pub(crate) fn decompress(&mut self, max_ratio: u32, has_body: bool) {
+        if has_body && self.status_code != 204 && self.status_code != 304 {
+            self.decoder = Decoder::for_headers(&mut self.headers, max_ratio);
         }
     }
-    None
 }
 
-async fn read_trailers<R: Read>(
-    bytes: &mut R,
-    headers: &mut HashMap<String, String>,
-    mut max_headers_size: Option<usize>,
-) -> Result<(), Error>
+impl<R: Read + BufRead> ResponseLazy<R>
 where
     R::Error: Into<Error>,
 {
-    loop {
-        let trailer_line = read_line(bytes, max_headers_size, Error::HeadersOverflow).await?;
-        if let Some(ref mut max_headers_size) = max_headers_size {
-            *max_headers_size -= trailer_line.len() + 2;
+    /// Writes the rest of the body into `sink`, returning the number
+    /// of bytes written.
+    ///
+    /// The body is copied straight from the connection's buffer, in
+    /// as large pieces as possible, so it never has to fit in memory.
+    /// This makes it possible to eg. write a firmware image directly
+    /// into an OTA partition or a file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`IncompleteBody`](enum.Error.html#variant.IncompleteBody)
+    /// if the connection is closed before the whole body announced by
+    /// `Content-Length` or the chunk sizes was received, and
+    /// [`SinkError`](enum.Error.html#variant.SinkError) if writing
+    /// into `sink` fails. The bytes received before the error have
+    /// already been written into `sink`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn main() -> Result<(), esp_minreq::Error> {
+    /// let mut buffer = [0; 4096];
+    /// let mut sink = &mut buffer[..];
+    /// let mut response = esp_minreq::get("http://example.com")
+    ///     .send_lazy::<esp_minreq::tcp::HttpStream>()
+    ///     .await?;
+    /// let length = response.copy_to(&mut sink).await?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn copy_to<W: Write>(&mut self, sink: &mut W) -> Result<u64, Error> {
+        #[cfg(feature = "compression")]
+        let copied = match self.decoder {
+            Some(_) => self.copy_decoded_to(sink).await?,
+            None => self.copy_raw_to(sink).await?,
+        };
+        #[cfg(not(feature = "compression"))]
+        let copied = self.copy_raw_to(sink).await?;
+        sink.flush()
+            .await
+            .map_err(|err| Error::SinkError(err.kind()))?;
+        Ok(copied)
+    }
+
+    async fn copy_raw_to<W: Write>(&mut self, sink: &mut W) -> Result<u64, Error> {
+        use HttpStreamState::*;
+        let mut copied = 0;
+        loop {
+            let available = self.available().await?;
+            if available == 0 {
+                break;
+            }
+
+            let buf = cancellable(self.cancellation.as_ref(), self.stream.fill_buf())
+                .await?
+                .map_err(Into::into)?;
+            if buf.is_empty() {
+                match self.state {
+                    EndOnClose => break,
+                    _ => return Err(Error::IncompleteBody),
+                }
+            }
+            let length = buf.len().min(available);
+            count_body_bytes(&mut self.body_size, self.max_body_size, length)?;
+            write_to_sink(sink, &buf[..length]).await?;
+            self.consume_raw(length);
+            copied += length as u64;
         }
-        if let Some((header, value)) = parse_header(trailer_line) {
-            headers.insert(header, value);
-        } else {
-            break;
+        if let Some(ref mut progress) = self.progress {
+            progress.finish();
         }
+        Ok(copied)
     }
-    Ok(())
-}
 
-async fn read_chunked<R: Read>(
-    bytes: &mut R,
-    headers: &mut HashMap<String, String>,
-    expecting_more_chunks: &mut bool,
-    chunk_length: &mut usize,
-    content_length: &mut usize,
-    max_trailing_headers_size: Option<usize>,
-) -> Option<Result<(u8, usize), Error>>
-where
-    R::Error: Into<Error>,
-{
-    if !*expecting_more_chunks && *chunk_length == 0 {
-        return None;
+    /// Returns how many bytes of the body can be taken straight from
+    /// the stream's buffer, first reading the framing up to the next
+    /// chunk's data if the current chunk is finished. Returns 0 at
+    /// the end of the body, which for `EndOnClose` is only known when
+    /// the stream ends.
+    async fn available(&mut self) -> Result<usize, Error> {
+        if let HttpStreamState::Chunked(..) = self.state {
+            let token = self.cancellation.take();
+            let started = cancellable(token.as_ref(), self.start_chunk()).await;
+            self.cancellation = token;
+            started??;
+        }
+        Ok(match self.state {
+            HttpStreamState::EndOnClose => usize::MAX,
+            HttpStreamState::ContentLength(remaining)
+            | HttpStreamState::Chunked(_, remaining, _) => remaining,
+        })
     }
 
-    if *chunk_length == 0 {
-        // Max length of the chunk length line is 1KB: not too long to
-        // take up much memory, long enough to tolerate some chunk
-        // extensions (which are ignored).
+    /// Consumes `length` bytes of the body taken from the stream's
+    /// buffer.
+    fn consume_raw(&mut self, length: usize) {
+        self.stream.consume(length);
+        if let Some(ref mut progress) = self.progress {
+            progress.advance(length);
+        }
+        if let HttpStreamState::ContentLength(ref mut remaining)
+        | HttpStreamState::Chunked(_, ref mut remaining, _) = self.state
+        {
+            *remaining -= length;
+        }
+    }
 
-        // Get the size of the next chunk
-        let length_line = match read_line(bytes, Some(1024), Error::MalformedChunkLength).await {
-            Ok(line) => line,
-            Err(err) => return Some(Err(err)),
-        };
+    /// Returns the length of the rest of the body, if known.
+    fn remaining_length(&self) -> Option<usize> {
+        #[cfg(feature = "compression")]
+        if self.decoder.is_some() {
+            return None;
+        }
+        match self.state {
+            HttpStreamState::ContentLength(length) => Some(length),
+            _ => None,
+        }
+    }
 
-        // Note: the trim() and check for empty lines shouldn't be
-        // needed according to the RFC, but we might as well, it's a
-        // small change and it fixes a few servers.
-        let incoming_length = if length_line.is_empty() {
-            0
-        } else {
-            let length = if let Some(i) = length_line.find(';') {
-                length_line[..i].trim()
-            } else {
-                length_line.trim()
-            };
-            match usize::from_str_radix(length, 16) {
-                Ok(length) => length,
-                Err(_) => return Some(Err(Error::MalformedChunkLength)),
-            }
+    /// Reads the `\r\n` after the current chunk and the size line of
+    /// the next one if the current one is finished, or the trailers
+    /// after the last chunk. Returns whether there is chunk data left
+    /// to read.
+    async fn start_chunk(&mut self) -> Result<bool, Error> {
+        let HttpStreamState::Chunked(
+            ref mut expecting_more_chunks,
+            ref mut chunk_length,
+            ref mut content_length,
+        ) = self.state
+        else {
+            return Ok(false);
         };
+        if *chunk_length > 0 {
+            return Ok(true);
+        }
+        if !*expecting_more_chunks {
+            return Ok(false);
+        }
 
+        // Every chunk but the first is preceded by the \r\n ending
+        // the previous one.
+        if *content_length > 0 {
+            read_line(&mut self.stream, Some(2), Error::MalformedChunkEnd).await?;
+        }
+        let (incoming_length, extensions) = read_chunk_size(&mut self.stream).await?;
+        self.chunk_extensions = extensions;
         if incoming_length == 0 {
-            if let Err(err) = read_trailers(bytes, headers, max_trailing_headers_size).await {
-                return Some(Err(err));
-            }
+            read_trailers(
+                &mut self.stream,
+                &mut self.trailers,
+                self.max_trailing_headers_size,
+            )
+            .await?;
 
             *expecting_more_chunks = false;
-            headers.insert("content-length".to_string(), (*content_length).to_string());
-            headers.remove("transfer-encoding");
-            return None;
+            return Ok(false);
         }
         *chunk_length = incoming_length;
         *content_length += incoming_length;
+        Ok(true)
     }
 
-    if *chunk_length > 0 {
-        *chunk_length -= 1;
-        if let Some(byte) = bytes.next_byte().await {
-            match byte {
-                Ok(byte) => {
-                    // If we're at the end of the chunk...
-                    if *chunk_length == 0 {
-                        //...read the trailing \r\n of the chunk, and
-                        // possibly return an error instead.
-
-                        // TODO: Maybe this could be written in a way
-                        // that doesn't discard the last ok byte if
-                        // the \r\n reading fails?
-                        if let Err(err) = read_line(bytes, Some(2), Error::MalformedChunkEnd).await
-                        {
-                            return Some(Err(err));
-                        }
-                    }
+    #[cfg(feature = "compression")]
+    async fn copy_decoded_to<W: Write>(&mut self, sink: &mut W) -> Result<u64, Error> {
+        let mut copied = 0;
+        while self.fill_decoded().await? {
+            let Some(ref mut decoder) = self.decoder else {
+                break;
+            };
+            let output = decoder.output();
+            count_body_bytes(&mut self.body_size, self.max_body_size, output.len())?;
+            write_to_sink(sink, output).await?;
+            copied += output.len() as u64;
+            decoder.consume(output.len());
+        }
+        Ok(copied)
+    }
+
+    /// Decompresses more of the body, unless some decompressed bytes
+    /// are left. Returns `false` at the end of the body.
+    #[cfg(feature = "compression")]
+    async fn fill_decoded(&mut self) -> Result<bool, Error> {
+        let Some(mut decoder) = self.decoder.take() else {
+            return Ok(false);
+        };
+        let filled = self.fill_decoder(&mut decoder).await;
+        self.decoder = Some(decoder);
+        filled
+    }
 
-                    return Some(Ok((byte, (*chunk_length).min(MAX_CONTENT_LENGTH) + 1)));
+    #[cfg(feature = "compression")]
+    async fn fill_decoder(&mut self, decoder: &mut Decoder) -> Result<bool, Error> {
+        loop {
+            if !decoder.output().is_empty() {
+                return Ok(true);
+            }
+            if decoder.is_done() {
+                // Read the rest of the body, eg. the trailers of a
+                // chunked body, so they are available.
+                let mut rest = [0; 64];
+                while self.read_raw(&mut rest).await? > 0 {
+                    log::debug!("Ignoring data after the end of the compressed body.");
                 }
-                Err(err) => return Some(Err(err.into())),
+                return Ok(false);
+            }
+            if decoder.decode()? {
+                continue;
+            }
+            let input = decoder.input();
+            if input.is_empty() {
+                return Err(Error::DecompressionFailed);
+            }
+            match self.read_raw(input).await? {
+                0 => decoder.finish()?,
+                length => decoder.fill(length),
             }
         }
     }
 
-    None
-}
+    /// Reads the next piece of the body into `buf`, without waiting
+    /// for more than is already available. Returns 0 at the end of
+    /// the body.
+    pub(crate) async fn read_some(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        #[cfg(feature = "compression")]
+        if self.decoder.is_some() {
+            if !self.fill_decoded().await? {
+                return Ok(0);
+            }
+            let Some(ref mut decoder) = self.decoder else {
+                return Ok(0);
+            };
+            let length = decoder.output().len().min(buf.len());
+            buf[..length].copy_from_slice(&decoder.output()[..length]);
+            decoder.consume(length);
+            count_body_bytes(&mut self.body_size, self.max_body_size, length)?;
+            return Ok(length);
+        }
+        let length = self.read_raw(buf).await?;
+        count_body_bytes(&mut self.body_size, self.max_body_size, length)?;
+        Ok(length)
+    }
 
-enum HttpStreamState {
-    // No Content-Length, and Transfer-Encoding != chunked, so we just
-    // read unti lthe server closes the connection (this should be the
-    // fallback, if I read the rfc right).
-    EndOnClose,
-    // Content-Length was specified, read that amount of bytes
+    /// Reads more of the body as it was sent into `buf`, in as large
+    /// pieces as the stream's buffer allows. Returns 0 at the end of
+    /// the body.
+    async fn read_raw(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let available = self.available().await?;
+        if available == 0 {
+            if let Some(ref mut progress) = self.progress {
+                progress.finish();
+            }
+            return Ok(0);
+        }
+
+        let data = cancellable(self.cancellation.as_ref(), self.stream.fill_buf())
+            .await?
+            .map_err(Into::into)?;
+        if data.is_empty() {
+            return match self.state {
+                HttpStreamState::EndOnClose => {
+                    if let Some(ref mut progress) = self.progress {
+                        progress.finish();
+                    }
+                    Ok(0)
+                }
+                _ => Err(Error::IncompleteBody),
+            };
+        }
+        let length = data.len().min(available).min(buf.len());
+        buf[..length].copy_from_slice(&data[..length]);
+        self.consume_raw(length);
+        Ok(length)
+    }
+
+    /// Returns a reader which decodes the rest of the body into text
+    /// piece by piece, as it arrives, like
+    /// [`Response::text`](struct.Response.html#method.text) does for
+    /// a whole body. If `lossy`, invalid UTF-8 is replaced with
+    /// `U+FFFD REPLACEMENT CHARACTER`, and unsupported charsets are
+    /// decoded as UTF-8.
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`UnsupportedCharset`](enum.Error.html#variant.UnsupportedCharset)
+    /// if the charset is not supported, unless `lossy`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn main() -> Result<(), esp_minreq::Error> {
+    /// let mut response = esp_minreq::get("http://192.168.1.20/log")
+    ///     .send_lazy::<esp_minreq::tcp::HttpStream>()
+    ///     .await?;
+    /// let mut reader = response.text_reader(true)?;
+    /// let mut text = String::new();
+    /// while reader.read_into(&mut text).await? > 0 {
+    ///     print!("{}", text);
+    ///     text.clear();
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn text_reader(&mut self, lossy: bool) -> Result<TextReader<'_, R>, Error> {
+        let charset = text::charset(&self.headers, lossy)?;
+        Ok(TextReader::new(self, charset, lossy))
+    }
+
+    /// Reads the rest of the current chunk, or the next chunk, of a
+    /// `Transfer-Encoding: chunked` body, along with its chunk
+    /// extensions. Returns `None` after the last chunk, after which
+    /// the [`trailers`](#structfield.trailers) have been read.
+    ///
+    /// Chunks are returned as they were sent, so for a body with a
+    /// `Content-Encoding`, don't mix this with the other ways of
+    /// reading the body.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`NotChunked`](enum.Error.html#variant.NotChunked) if
+    /// the body is not chunked,
+    /// [`ChunkTooLarge`](enum.Error.html#variant.ChunkTooLarge) if the
+    /// chunk is larger than `max_chunk_size` bytes, after which it is
+    /// skipped and reading continues with the next one, and the
+    /// errors of reading the body.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn main() -> Result<(), esp_minreq::Error> {
+    /// let mut response = esp_minreq::get("http://example.com/stream")
+    ///     .send_lazy::<esp_minreq::tcp::HttpStream>()
+    ///     .await?;
+    /// while let Some(chunk) = response.next_chunk(4096).await? {
+    ///     println!("{} bytes, extensions {:?}", chunk.data.len(), chunk.extensions);
+    /// }
+    /// println!("checksum: {:?}", response.trailers.get("x-checksum"));
+    /// # Ok(()) }
+    /// ```
+    pub async fn next_chunk(&mut self, max_chunk_size: usize) -> Result<Option<Chunk>, Error> {
+        if !matches!(self.state, HttpStreamState::Chunked(..)) {
+            return Err(Error::NotChunked);
+        }
+        let token = self.cancellation.take();
+        let chunk = cancellable(token.as_ref(), self.read_chunk(max_chunk_size)).await;
+        self.cancellation = token;
+        chunk?
+    }
+
+    async fn read_chunk(&mut self, max_chunk_size: usize) -> Result<Option<Chunk>, Error> {
+        let mut remaining = self.available().await?;
+        if remaining == 0 {
+            if let Some(ref mut progress) = self.progress {
+                progress.finish();
+            }
+            return Ok(None);
+        }
+
+        let too_large = remaining > max_chunk_size;
+        let mut data = Vec::with_capacity(if too_large { 0 } else { remaining });
+        while remaining > 0 {
+            let buf = self.stream.fill_buf().await.map_err(Into::into)?;
+            if buf.is_empty() {
+                return Err(Error::IncompleteBody);
+            }
+            let length = buf.len().min(remaining);
+            count_body_bytes(&mut self.body_size, self.max_body_size, length)?;
+            if !too_large {
+                data.extend_from_slice(&buf[..length]);
+            }
+            self.consume_raw(length);
+            remaining -= length;
+        }
+
+        if too_large {
+            return Err(Error::ChunkTooLarge);
+        }
+        Ok(Some(Chunk {
+            data,
+            extensions: self.chunk_extensions.clone(),
+        }))
+    }
+
+    /// Returns a reader for the body line by line, eg. of a log tail
+    /// sent with a long-lived chunked response. Each line is returned
+    /// as soon as it arrived, and lines longer than `max_line_length`
+    /// bytes are skipped with an error.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn main() -> Result<(), esp_minreq::Error> {
+    /// let mut response = esp_minreq::get("http://example.com/logs")
+    ///     .send_lazy::<esp_minreq::tcp::HttpStream>()
+    ///     .await?;
+    /// let mut lines = response.lines(1024);
+    /// while let Some(line) = lines.next().await? {
+    ///     println!("{}", line);
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn lines(&mut self, max_line_length: usize) -> Lines<'_, R> {
+        Lines::new(self, max_line_length)
+    }
+
+    /// Returns a reader for a body of newline-delimited JSON, which
+    /// deserializes each record as soon as its line arrived. See
+    /// [`lines`](#method.lines) for `max_line_length`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn main() -> Result<(), esp_minreq::Error> {
+    /// #[derive(serde::Deserialize)]
+    /// struct Reading {
+    ///     sensor: String,
+    ///     value: f32,
+    /// }
+    ///
+    /// let mut response = esp_minreq::get("http://example.com/telemetry")
+    ///     .send_lazy::<esp_minreq::tcp::HttpStream>()
+    ///     .await?;
+    /// let mut readings = response.ndjson::<Reading>(1024);
+    /// while let Some(reading) = readings.next().await? {
+    ///     println!("{}: {}", reading.sensor, reading.value);
+    /// }
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn ndjson<T>(&mut self, max_line_length: usize) -> Ndjson<'_, R, T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        Ndjson::new(Lines::new(self, max_line_length))
+    }
+
+    /// Reads the rest of the body and converts it from JSON to a
+    /// `struct` using Serde, deserializing it as it arrives.
+    ///
+    /// Unlike [`Response::json`](struct.Response.html#method.json),
+    /// the body is never in memory as a whole. Serde runs in a
+    /// FreeRTOS task of its own, with a stack of 8 KiB, and is handed
+    /// the body through a 512 byte buffer as it is read, so the
+    /// memory used doesn't grow with the body. While Serde waits for
+    /// more of the body, only its task is blocked, not the executor.
+    /// To deserialize into borrowed types without allocating, see
+    /// [`json_in`](#method.json_in).
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`SerdeJsonError`](enum.Error.html#variant.SerdeJsonError) if
+    /// Serde runs into a problem,
+    /// [`Other`](enum.Error.html#variant.Other) if the task can't be
+    /// started, and the errors of [`copy_to`](#method.copy_to).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// #[derive(serde::Deserialize)]
+    /// struct Release {
+    ///     version: String,
+    ///     notes: String,
+    /// }
+    ///
+    /// # async fn main() -> Result<(), esp_minreq::Error> {
+    /// let mut response = esp_minreq::get("http://example.com/releases/latest")
+    ///     .send_lazy::<esp_minreq::tcp::HttpStream>()
+    ///     .await?;
+    /// let release: Release = response.json().await?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "json")]
+    pub async fn json<T>(&mut self) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned + Send + 'static,
+    {
+        decode_in_task(self, JSON_TASK_STACK_SIZE, |body| {
+            serde_json::from_reader(body).map_err(Error::SerdeJsonError)
+        })
+        .await
+    }
+
+    /// Reads the rest of the body into `buf` and converts it from
+    /// JSON to a `struct` using
+    /// [serde-json-core](https://crates.io/crates/serde-json-core),
+    /// without allocating. `T` can borrow strings from `buf`, eg.
+    /// `&str` fields without escape sequences.
+    ///
+    /// Unlike with [`json`](#method.json), the whole body is read into
+    /// `buf` before it is deserialized, so `buf` bounds the memory
+    /// used, and no task is started. For bodies made of many small
+    /// JSON values, see [`ndjson`](#method.ndjson).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BodyOverflow`](enum.Error.html#variant.BodyOverflow)
+    /// if the body doesn't fit in `buf`,
+    /// [`SerdeJsonCoreError`](enum.Error.html#variant.SerdeJsonCoreError)
+    /// if Serde runs into a problem, and the errors of
+    /// [`copy_to`](#method.copy_to).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// #[derive(serde::Deserialize)]
+    /// struct Status<'a> {
+    ///     state: &'a str,
+    ///     uptime: u32,
+    /// }
+    ///
+    /// # async fn main() -> Result<(), esp_minreq::Error> {
+    /// let mut buf = [0; 512];
+    /// let mut response = esp_minreq::get("http://example.com/status")
+    ///     .send_lazy::<esp_minreq::tcp::HttpStream>()
+    ///     .await?;
+    /// let status: Status = response.json_in(&mut buf).await?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "json-core")]
+    pub async fn json_in<'b, T>(&mut self, buf: &'b mut [u8]) -> Result<T, Error>
+    where
+        T: serde::de::Deserialize<'b>,
+    {
+        if self
+            .remaining_length()
+            .is_some_and(|length| length > buf.len())
+        {
+            return Err(Error::BodyOverflow);
+        }
+        let length = {
+            let mut sink = &mut buf[..];
+            match self.copy_to(&mut sink).await {
+                Ok(length) => length as usize,
+                Err(Error::SinkError(_)) => return Err(Error::BodyOverflow),
+                Err(err) => return Err(err),
+            }
+        };
+        let buf: &'b [u8] = buf;
+        match serde_json_core::from_slice(&buf[..length]) {
+            Ok((json, _)) => Ok(json),
+            Err(err) => Err(Error::SerdeJsonCoreError(err)),
+        }
+    }
+
+    /// Reads the rest of the body and converts it from CBOR to a
+    /// `struct` using Serde.
+    ///
+    /// The CBOR decoder can't wait for more of the body to arrive in
+    /// the middle of a value, so the body is collected in memory
+    /// first. Its size is bounded by
+    /// [`Request::with_max_body_size`](struct.Request.html#method.with_max_body_size).
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`SerdeCborDecodeError`](enum.Error.html#variant.SerdeCborDecodeError)
+    /// if Serde runs into a problem, and the errors of
+    /// [`copy_to`](#method.copy_to).
+    #[cfg(feature = "cbor")]
+    pub async fn cbor<T>(&mut self) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let body = self.collect_body().await?;
+        // Strings and byte strings up to this length are decoded
+        // without an allocation. Kept small for the stack's sake.
+        let mut scratch = [0; 256];
+        match ciborium::from_reader_with_buffer(body.as_slice(), &mut scratch) {
+            Ok(cbor) => Ok(cbor),
+            Err(err) => Err(Error::SerdeCborDecodeError(err)),
+        }
+    }
+
+    /// Reads the rest of the body and converts it from MessagePack to
+    /// a `struct` using Serde.
+    ///
+    /// Like with [`cbor`](#method.cbor), the body is collected in
+    /// memory first.
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`SerdeMsgpackDecodeError`](enum.Error.html#variant.SerdeMsgpackDecodeError)
+    /// if Serde runs into a problem, and the errors of
+    /// [`copy_to`](#method.copy_to).
+    #[cfg(feature = "msgpack")]
+    pub async fn msgpack<T>(&mut self) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let body = self.collect_body().await?;
+        match rmp_serde::from_slice(&body) {
+            Ok(msgpack) => Ok(msgpack),
+            Err(err) => Err(Error::SerdeMsgpackDecodeError(err)),
+        }
+    }
+
+    /// Reads the rest of the body into memory.
+    #[cfg(any(feature = "cbor", feature = "msgpack"))]
+    async fn collect_body(&mut self) -> Result<Vec<u8>, Error> {
+        let mut body = VecSink(Vec::new());
+        let length = self.remaining_length().unwrap_or(0);
+        body.0.reserve(length.min(MAX_CONTENT_LENGTH));
+        self.copy_to(&mut body).await?;
+        Ok(body.0)
+    }
+}
+
+/// Collects a body in memory.
+struct VecSink(Vec<u8>);
+
+impl ErrorType for VecSink {
+    type Error = core::convert::Infallible;
+}
+
+impl Write for VecSink {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+}
+
+/// Counts `length` more bytes of the body, failing if the body gets
+/// larger than `max_body_size`.
+fn count_body_bytes(
+    body_size: &mut usize,
+    max_body_size: Option<usize>,
+    length: usize,
+) -> Result<(), Error> {
+    *body_size += length;
+    match max_body_size {
+        Some(max_body_size) if *body_size > max_body_size => Err(Error::BodyOverflow),
+        _ => Ok(()),
+    }
+}
+
+async fn write_to_sink<W: Write>(sink: &mut W, buf: &[u8]) -> Result<(), Error> {
+    sink.write_all(buf)
+        .await
+        .map_err(|err| Error::SinkError(err.kind()))
+}
+
+impl<R: Read> ErrorType for ResponseLazy<R> {
+    type Error = Error;
+}
+
+impl<R: Read + BufRead> Read for ResponseLazy<R>
+where
+    R::Error: Into<Self::Error>,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.read_some(buf).await
+    }
+}
+
+async fn read_trailers<R: BufRead>(
+    bytes: &mut R,
+    headers: &mut HashMap<String, String>,
+    mut max_headers_size: Option<usize>,
+) -> Result<(), Error>
+where
+    R::Error: Into<Error>,
+{
+    loop {
+        let trailer_line = read_line(bytes, max_headers_size, Error::HeadersOverflow).await?;
+        if let Some(ref mut max_headers_size) = max_headers_size {
+            *max_headers_size = max_headers_size.saturating_sub(trailer_line.len() + 2);
+        }
+        if let Some((header, value)) = parse_header(trailer_line) {
+            headers.insert(header, value);
+        } else {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Reads the size line of a chunk, returning the size and the chunk
+/// extensions.
+async fn read_chunk_size<R: BufRead>(bytes: &mut R) -> Result<(usize, Vec<(String, String)>), Error>
+where
+    R::Error: Into<Error>,
+{
+    // Max length of the chunk length line is 1KB: not too long to
+    // take up much memory, long enough to tolerate some chunk
+    // extensions.
+    let length_line = read_line(bytes, Some(1024), Error::MalformedChunkLength).await?;
+
+    // Note: the trim() and check for empty lines shouldn't be
+    // needed according to the RFC, but we might as well, it's a
+    // small change and it fixes a few servers.
+    if length_line.is_empty() {
+        return Ok((0, Vec::new()));
+    }
+    let (length, extensions) = match length_line.find(';') {
+        Some(i) => (
+            length_line[..i].trim(),
+            headers::chunk_extensions(&length_line[i..]),
+        ),
+        None => (length_line.trim(), Vec::new()),
+    };
+    match usize::from_str_radix(length, 16) {
+        Ok(length) => Ok((length, extensions)),
+        Err(_) => Err(Error::MalformedChunkLength),
+    }
+}
+
+pub(crate) enum HttpStreamState {
+    // No Content-Length, and Transfer-Encoding != chunked, so we just
+    // read unti lthe server closes the connection (this should be the
+    // fallback, if I read the rfc right).
+    EndOnClose,
+    // Content-Length was specified, read that amount of bytes
     ContentLength(usize),
     // Transfer-Encoding == chunked, so we need to save two pieces of
     // information: are we expecting more chunks, how much is there
     // left of the current chunk, and how much have we read? The last
     // number is needed in order to provide an accurate Content-Length
-    // header after loading all the bytes.
+    // header after loading all the bytes, and tells whether a chunk's
+    // trailing \r\n is still to be read before the next size line.
     Chunked(bool, usize, usize),
 }
 
@@ -506,31 +1541,37 @@ struct ResponseMetadata {
     max_trailing_headers_size: Option<usize>,
 }
 
-async fn read_metadata<R: Read>(
+/// Reads the head of the final response, skipping and collecting
+/// informational (1xx) responses on the way. If `stop_at_continue`
+/// is set, returns `None` after reading a `100 Continue`.
+async fn read_metadata<R: BufRead>(
     stream: &mut R,
-    mut max_headers_size: Option<usize>,
+    max_headers_size: &mut Option<usize>,
     max_status_line_len: Option<usize>,
-) -> Result<ResponseMetadata, Error>
+    header_filter: Option<&[String]>,
+    informational: &mut Vec<InformationalResponse>,
+    stop_at_continue: bool,
+) -> Result<Option<ResponseMetadata>, Error>
 where
     R::Error: Into<Error>,
 {
-    let line = read_line(stream, max_status_line_len, Error::StatusLineOverflow).await?;
-    let (status_code, reason_phrase) = parse_status_line(&line);
-
-    let mut headers = HashMap::new();
-    loop {
-        let line = read_line(stream, max_headers_size, Error::HeadersOverflow).await?;
-        if line.is_empty() {
-            // Body starts here
-            break;
-        }
-        if let Some(ref mut max_headers_size) = max_headers_size {
-            *max_headers_size -= line.len() + 2;
+    let (status_code, reason_phrase, headers) = loop {
+        let (status_code, reason_phrase, headers) =
+            read_head(stream, max_headers_size, max_status_line_len, header_filter).await?;
+        // 101 Switching Protocols is the final response of an upgrade.
+        if !(100..200).contains(&status_code) || status_code == 101 {
+            break (status_code, reason_phrase, headers);
         }
-        if let Some(header) = parse_header(line) {
-            headers.insert(header.0, header.1);
+        log::trace!("Received informational response {}.", status_code);
+        informational.push(InformationalResponse {
+            status_code,
+            reason_phrase,
+            headers,
+        });
+        if stop_at_continue && status_code == 100 {
+            return Ok(None);
         }
-    }
+    };
 
     let mut chunked = false;
     let mut content_length = None;
@@ -559,16 +1600,51 @@ where
         HttpStreamState::EndOnClose
     };
 
-    Ok(ResponseMetadata {
+    Ok(Some(ResponseMetadata {
         status_code,
         reason_phrase,
         headers,
         state,
-        max_trailing_headers_size: max_headers_size,
-    })
+        max_trailing_headers_size: *max_headers_size,
+    }))
 }
 
-async fn read_line<R: Read>(
+async fn read_head<R: BufRead>(
+    stream: &mut R,
+    max_headers_size: &mut Option<usize>,
+    max_status_line_len: Option<usize>,
+    header_filter: Option<&[String]>,
+) -> Result<(i32, String, HashMap<String, String>), Error>
+where
+    R::Error: Into<Error>,
+{
+    let line = read_line(stream, max_status_line_len, Error::StatusLineOverflow).await?;
+    let (status_code, reason_phrase) = parse_status_line(&line);
+
+    let mut headers = HashMap::new();
+    loop {
+        let line = read_line(stream, *max_headers_size, Error::HeadersOverflow).await?;
+        if line.is_empty() {
+            // Body starts here
+            break;
+        }
+        if let Some(ref mut max_headers_size) = max_headers_size {
+            *max_headers_size = max_headers_size.saturating_sub(line.len() + 2);
+        }
+        if let Some((name, value)) = parse_header(line) {
+            if headers::is_kept(header_filter, &name) {
+                headers.insert(name, value);
+            }
+        }
+    }
+    Ok((status_code, reason_phrase, headers))
+}
+
+/// Reads a line, without its `\n` or `\r\n`, by scanning the
+/// stream's buffer. Fails with `overflow_error` if the line,
+/// including its newline, is longer than `max_len`. At the end of
+/// the stream, the line read so far is returned.
+async fn read_line<R: BufRead>(
     stream: &mut R,
     max_len: Option<usize>,
     overflow_error: Error,
@@ -577,24 +1653,28 @@ where
     R::Error: Into<Error>,
 {
     let mut bytes = Vec::with_capacity(32);
-    while let Some(byte) = stream.next_byte().await {
-        match byte {
-            Ok(byte) => {
-                if let Some(max_len) = max_len {
-                    if bytes.len() >= max_len {
-                        return Err(overflow_error);
-                    }
-                }
-                if byte == b'\n' {
-                    if let Some(b'\r') = bytes.last() {
-                        bytes.pop();
-                    }
-                    break;
-                } else {
-                    bytes.push(byte);
-                }
+    loop {
+        let buf = stream.fill_buf().await.map_err(Into::into)?;
+        if buf.is_empty() {
+            break;
+        }
+        let (piece, length) = match buf.iter().position(|&byte| byte == b'\n') {
+            Some(newline) => (&buf[..newline], newline + 1),
+            None => (buf, buf.len()),
+        };
+        if let Some(max_len) = max_len {
+            if bytes.len() + length > max_len {
+                return Err(overflow_error);
+            }
+        }
+        bytes.extend_from_slice(piece);
+        let ends_line = length > piece.len();
+        stream.consume(length);
+        if ends_line {
+            if let Some(b'\r') = bytes.last() {
+                bytes.pop();
             }
-            Err(err) => return Err(err.into()),
+            break;
         }
     }
     String::from_utf8(bytes).map_err(|_error| Error::InvalidUtf8InResponse)
@@ -651,3 +1731,445 @@ fn parse_header(mut line: String) -> Option<(String, String)> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Response, ResponseLazy};
+    use crate::buf_reader::BufReader;
+    use crate::http::test_support::{block_on, Stream};
+    use crate::http::Error;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use embedded_io_async::Read;
+
+    /// How a test response is read.
+    #[derive(Default)]
+    struct Options {
+        max_headers_size: Option<usize>,
+        header_filter: Option<Vec<String>>,
+        max_body_size: Option<usize>,
+        #[cfg(feature = "compression")]
+        decompress: bool,
+    }
+
+    /// Reads the head of the response in `data`, which arrives 7 bytes
+    /// at a time.
+    fn lazy_response(
+        data: &[u8],
+        options: Options,
+    ) -> Result<ResponseLazy<BufReader<Stream>>, Error> {
+        block_on(async {
+            let stream = Stream::new(data, 7);
+            let filter = options.header_filter.as_deref();
+            let mut lazy =
+                ResponseLazy::from_stream(stream, options.max_headers_size, None, filter).await?;
+            if let Some(max_body_size) = options.max_body_size {
+                lazy.limit_body_size(max_body_size, true)?;
+            }
+            #[cfg(feature = "compression")]
+            if options.decompress {
+                lazy.decompress(100, true);
+            }
+            Ok(lazy)
+        })
+    }
+
+    fn response_with(data: &[u8], options: Options) -> Result<Response, Error> {
+        let lazy = lazy_response(data, options)?;
+        block_on(Response::create(lazy, false))
+    }
+
+    fn response(data: &[u8]) -> Result<Response, Error> {
+        response_with(data, Options::default())
+    }
+
+    #[test]
+    fn skips_informational_responses() {
+        let response = response(
+            b"HTTP/1.1 100 Continue\r\n\r\n\
+              HTTP/1.1 103 Early Hints\r\nLink: </style.css>\r\n\r\n\
+              HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello",
+        )
+        .unwrap();
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.as_bytes(), b"hello");
+        assert_eq!(response.informational.len(), 2);
+        assert_eq!(response.informational[1].status_code, 103);
+        assert_eq!(
+            response.informational[1].headers.get("link").unwrap(),
+            "</style.css>"
+        );
+    }
+
+    #[test]
+    fn hands_over_upgraded_stream() {
+        let data = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: raw\r\n\r\nhello, raw world";
+        let rest = block_on(async {
+            let stream = Stream::new(data, 40);
+            let lazy = ResponseLazy::from_stream(stream, None, None, None).await?;
+            let mut stream = lazy.into_upgraded()?;
+            let mut rest = [0; 32];
+            let mut length = 0;
+            loop {
+                match stream.read(&mut rest[length..]).await? {
+                    0 => break,
+                    read => length += read,
+                }
+            }
+            Ok::<_, Error>(rest[..length].to_vec())
+        })
+        .unwrap();
+        assert_eq!(rest, b"hello, raw world");
+
+        let result = block_on(async {
+            let stream = Stream::new(b"HTTP/1.1 200 OK\r\n\r\n", 40);
+            let lazy = ResponseLazy::from_stream(stream, None, None, None).await?;
+            lazy.into_upgraded().map(|_| ())
+        });
+        assert!(matches!(result, Err(Error::UnexpectedStatus(200))));
+    }
+
+    #[test]
+    fn reads_lines_as_they_arrive() {
+        let data = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                     7\r\nfirst\r\n\r\n8\r\nsec\xc3\xb6nd\n\r\n\
+                     12\r\nmuch too long\nlast\r\n0\r\n\r\n";
+        let lines = block_on(async {
+            let stream = Stream::new(data, 5);
+            let mut lazy = ResponseLazy::from_stream(stream, None, None, None).await?;
+            let mut lines = lazy.lines(8);
+            let mut results = Vec::new();
+            loop {
+                match lines.next().await {
+                    Ok(Some(line)) => results.push(Ok(line)),
+                    Ok(None) => break,
+                    Err(err) => results.push(Err(err)),
+                }
+            }
+            Ok::<_, Error>(results)
+        })
+        .unwrap();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0].as_ref().unwrap(), "first");
+        assert_eq!(lines[1].as_ref().unwrap(), "secönd");
+        assert!(matches!(lines[2], Err(Error::LineTooLong)));
+        assert_eq!(lines[3].as_ref().unwrap(), "last");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn reads_ndjson_records() {
+        let data = b"HTTP/1.1 200 OK\r\n\r\n[1,2]\n\n[3]\r\n[4";
+        let records = block_on(async {
+            let stream = Stream::new(data, 3);
+            let mut lazy = ResponseLazy::from_stream(stream, None, None, None).await?;
+            let mut records = lazy.ndjson::<Vec<u8>>(16);
+            let mut results = Vec::new();
+            while let Some(record) = records.next().await? {
+                results.push(record);
+            }
+            Ok::<_, Error>(results)
+        });
+        assert!(matches!(records, Err(Error::SerdeJsonError(_))));
+
+        let data = b"HTTP/1.1 200 OK\r\n\r\n[1,2]\n\n[3]\r\n";
+        let records = block_on(async {
+            let stream = Stream::new(data, 3);
+            let mut lazy = ResponseLazy::from_stream(stream, None, None, None).await?;
+            let mut records = lazy.ndjson::<Vec<u8>>(16);
+            let mut results = Vec::new();
+            while let Some(record) = records.next().await? {
+                results.push(record);
+            }
+            Ok::<_, Error>(results)
+        })
+        .unwrap();
+        assert_eq!(records, [alloc::vec![1, 2], alloc::vec![3]]);
+    }
+
+    #[test]
+    fn reads_chunked_body() {
+        let response = response(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+              5\r\nhello\r\n7;ext=1\r\n, world\r\n0\r\nx-sum: 1\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(response.as_bytes(), b"hello, world");
+        assert_eq!(response.trailers.get("x-sum").unwrap(), "1");
+        assert!(!response.headers.contains_key("x-sum"));
+        assert!(!response.headers.contains_key("content-length"));
+        assert_eq!(
+            response.headers.get("transfer-encoding").unwrap(),
+            "chunked"
+        );
+    }
+
+    #[test]
+    fn limits_trailers_to_remaining_headers_size() {
+        // The head uses 28 of the 37 bytes, and the trailer line the
+        // other 9, leaving no room for the empty line ending them.
+        let result = response_with(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n0\r\nx-sum: 1\n\r\n",
+            Options {
+                max_headers_size: Some(37),
+                ..Options::default()
+            },
+        );
+        assert!(matches!(result, Err(Error::HeadersOverflow)));
+    }
+
+    fn filtered_response(data: &[u8], max_headers_size: Option<usize>) -> Result<Response, Error> {
+        let options = Options {
+            max_headers_size,
+            header_filter: Some(alloc::vec![String::from("etag")]),
+            ..Options::default()
+        };
+        response_with(data, options)
+    }
+
+    #[test]
+    fn keeps_only_filtered_headers() {
+        let data = b"HTTP/1.1 200 OK\r\nServer: cdn\r\nX-Cache: HIT\r\nETag: \"1\"\r\n\
+                     Content-Length: 5\r\n\r\nhello";
+        let response = filtered_response(data, None).unwrap();
+        assert_eq!(
+            response.headers.keys().collect::<Vec<_>>(),
+            ["content-length", "etag"]
+        );
+        assert_eq!(response.as_bytes(), b"hello");
+        // The dropped headers still count towards the max headers size.
+        assert!(matches!(
+            filtered_response(data, Some(40)),
+            Err(Error::HeadersOverflow)
+        ));
+    }
+
+    #[test]
+    fn reads_body_in_slices() {
+        let data = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                     5\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n";
+        let lengths = block_on(async {
+            let stream = Stream::new(data, 256);
+            let mut lazy = ResponseLazy::from_stream(stream, None, None, None).await?;
+            let mut buf = [0; 32];
+            let mut lengths = Vec::new();
+            loop {
+                match lazy.read(&mut buf).await? {
+                    0 => break,
+                    length => lengths.push(length),
+                }
+            }
+            Ok::<_, Error>(lengths)
+        })
+        .unwrap();
+        assert_eq!(lengths, [5, 7]);
+    }
+
+    #[test]
+    fn reads_chunks_with_extensions() {
+        let data = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                     5;Sig=\"a;b\";last\r\nhello\r\nd\r\nmuch too long\r\n\
+                     7\r\n, world\r\n0\r\nx-sum: 1\r\n\r\n";
+        let (first, second, third, end, trailers) = block_on(async {
+            let stream = Stream::new(data, 5);
+            let mut lazy = ResponseLazy::from_stream(stream, None, None, None).await?;
+            let mut start = [0; 2];
+            lazy.read(&mut start).await?;
+            assert_eq!(&start, b"he");
+            let first = lazy.next_chunk(8).await?;
+            let second = lazy.next_chunk(8).await;
+            let third = lazy.next_chunk(8).await?;
+            let end = lazy.next_chunk(8).await?;
+            Ok::<_, Error>((first, second, third, end, lazy.trailers))
+        })
+        .unwrap();
+        let first = first.unwrap();
+        assert_eq!(first.data, b"llo");
+        assert_eq!(
+            first.extensions,
+            alloc::vec![("sig".into(), "a;b".into()), ("last".into(), "".into())]
+        );
+        assert!(matches!(second, Err(Error::ChunkTooLarge)));
+        let third = third.unwrap();
+        assert_eq!(third.data, b", world");
+        assert!(third.extensions.is_empty());
+        assert!(end.is_none());
+        assert_eq!(trailers.get("x-sum").unwrap(), "1");
+    }
+
+    fn limited_response(data: &[u8], max_body_size: usize) -> Result<Response, Error> {
+        let options = Options {
+            max_body_size: Some(max_body_size),
+            ..Options::default()
+        };
+        response_with(data, options)
+    }
+
+    #[test]
+    fn limits_body_size() {
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                        5\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n";
+        assert!(limited_response(chunked, 12).is_ok());
+        assert!(matches!(
+            limited_response(chunked, 11),
+            Err(Error::BodyOverflow)
+        ));
+        assert!(matches!(
+            limited_response(b"HTTP/1.1 200 OK\r\nContent-Length: 5000\r\n\r\n", 4096),
+            Err(Error::BodyOverflow)
+        ));
+    }
+
+    fn copy(data: &[u8], buf: &mut [u8]) -> Result<u64, Error> {
+        let mut lazy = lazy_response(data, Options::default())?;
+        block_on(lazy.copy_to(&mut &mut buf[..]))
+    }
+
+    #[test]
+    fn copies_body_to_sink() {
+        let mut buf = [0; 16];
+        let copied = copy(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+              5\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n",
+            &mut buf,
+        );
+        assert_eq!(copied.unwrap(), 12);
+        assert_eq!(&buf[..12], b"hello, world");
+
+        let copied = copy(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello",
+            &mut buf,
+        );
+        assert_eq!(copied.unwrap(), 5);
+        let copied = copy(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\nhello",
+            &mut buf,
+        );
+        assert!(matches!(copied, Err(Error::IncompleteBody)));
+    }
+
+    #[test]
+    fn reads_text_in_pieces() {
+        let data = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=windows-1252\r\n\
+                     Transfer-Encoding: chunked\r\n\r\n3\r\n\x80 1\r\n2\r\n\x93x\r\n0\r\n\r\n";
+        let (text, pieces) = block_on(async {
+            let stream = Stream::new(data, 7);
+            let mut lazy = ResponseLazy::from_stream(stream, None, None, None).await?;
+            let mut reader = lazy.text_reader(false)?;
+            let mut text = alloc::string::String::new();
+            let mut pieces = 0;
+            while reader.read_into(&mut text).await? > 0 {
+                pieces += 1;
+            }
+            Ok::<_, Error>((text, pieces))
+        })
+        .unwrap();
+        assert_eq!(text, "€ 1“x");
+        assert!(pieces > 1);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn decompresses_body() {
+        let body = b"hello, world! ".repeat(50);
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&body, 6);
+        let mut data = alloc::format!(
+            "HTTP/1.1 200 OK\r\nContent-Encoding: deflate\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n",
+            compressed.len()
+        )
+        .into_bytes();
+        data.extend_from_slice(&compressed);
+        data.extend_from_slice(b"\r\n0\r\n\r\n");
+
+        let options = Options {
+            decompress: true,
+            ..Options::default()
+        };
+        let response = response_with(&data, options).unwrap();
+        assert_eq!(response.as_bytes(), body);
+        assert!(!response.headers.contains_key("content-length"));
+        assert!(!response.headers.contains_key("content-encoding"));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn decompresses_chunked_gzip_body() {
+        use crate::http::compression::write_gzip_chunked;
+        use crate::http::GzipOptions;
+
+        let body = b"hello, world! ".repeat(200);
+        let mut data = [0; 4096];
+        let head =
+            b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nTransfer-Encoding: chunked\r\n\r\n";
+        data[..head.len()].copy_from_slice(head);
+        let length = {
+            let mut sink = &mut data[head.len()..];
+            block_on(write_gzip_chunked(
+                &mut sink,
+                &body,
+                GzipOptions::new(),
+                None,
+            ))
+            .unwrap();
+            4096 - sink.len()
+        };
+
+        let options = Options {
+            decompress: true,
+            ..Options::default()
+        };
+        let response = response_with(&data[..length], options).unwrap();
+        assert_eq!(response.as_bytes(), body);
+    }
+
+    #[cfg(any(feature = "json", feature = "cbor", feature = "msgpack"))]
+    fn lazy_body(body: &[u8]) -> ResponseLazy<BufReader<Stream>> {
+        let mut data = alloc::format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len())
+            .into_bytes();
+        data.extend_from_slice(body);
+        lazy_response(&data, Options::default()).unwrap()
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn decodes_json_while_reading() {
+        let value: Vec<u32> = (0..400).collect();
+        let body = serde_json::to_vec(&value).unwrap();
+        let decoded = block_on(lazy_body(&body).json::<Vec<u32>>());
+        assert_eq!(decoded.unwrap(), value);
+
+        let invalid = block_on(lazy_body(&body[..body.len() - 1]).json::<Vec<u32>>());
+        assert!(matches!(invalid, Err(Error::SerdeJsonError(_))));
+
+        let data = b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\n[1, 2";
+        let mut lazy = lazy_response(data, Options::default()).unwrap();
+        let cut_off = block_on(lazy.json::<Vec<u32>>());
+        assert!(matches!(cut_off, Err(Error::IncompleteBody)));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn decodes_cbor_from_lazy_body() {
+        let value = (String::from("hello"), alloc::vec![1u32, 2, 3]);
+        let mut body = Vec::new();
+        ciborium::into_writer(&value, &mut body).unwrap();
+        let decoded = block_on(lazy_body(&body).cbor::<(String, Vec<u32>)>());
+        assert_eq!(decoded.unwrap(), value);
+
+        let truncated = block_on(lazy_body(&body[..body.len() - 1]).cbor::<(String, Vec<u32>)>());
+        assert!(matches!(truncated, Err(Error::SerdeCborDecodeError(_))));
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn decodes_msgpack_from_lazy_body() {
+        let value = (String::from("hello"), alloc::vec![1u32, 2, 3]);
+        let body = rmp_serde::to_vec(&value).unwrap();
+        let decoded = block_on(lazy_body(&body).msgpack::<(String, Vec<u32>)>());
+        assert_eq!(decoded.unwrap(), value);
+
+        let truncated =
+            block_on(lazy_body(&body[..body.len() - 1]).msgpack::<(String, Vec<u32>)>());
+        assert!(matches!(truncated, Err(Error::SerdeMsgpackDecodeError(_))));
+    }
+}
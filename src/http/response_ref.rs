@@ -0,0 +1,383 @@
+use crate::http::headers;
+use crate::http::response::HttpStreamState;
+use crate::http::{Error, StatusCode};
+use core::fmt;
+use core::ops::Range;
+use core::str;
+use embedded_io_async::{ErrorType, Read};
+
+/// An HTTP response which lives in buffers supplied by the caller,
+/// returned by [`Request::send_into`](struct.Request.html#method.send_into)
+/// and [`RequestRef::send_into`](struct.RequestRef.html#method.send_into).
+///
+/// The head is parsed in place, and the body is read through the
+/// rest of the buffer with [`Read`], so reading the response never
+/// allocates. Trailers after a chunked body are skipped.
+pub struct ResponseRef<'b, C> {
+    /// The status code of the response, eg. 404.
+    pub status_code: i32,
+    /// The reason phrase of the response, eg. "Not Found".
+    pub reason_phrase: &'b str,
+    /// The headers of the response, in the order they were sent. The
+    /// header field names are all lowercase.
+    pub headers: &'b [(&'b str, &'b str)],
+
+    stream: C,
+    buf: &'b mut [u8],
+    pos: usize,
+    cap: usize,
+    state: HttpStreamState,
+}
+
+impl<'b, C: Read> ResponseRef<'b, C>
+where
+    Error: From<C::Error>,
+{
+    /// Reads the head of the final response into `buf`, skipping
+    /// informational (1xx) responses, and keeps the rest of `buf` for
+    /// reading the body.
+    pub(crate) async fn from_stream<S: AsRef<str>>(
+        mut stream: C,
+        buf: &'b mut [u8],
+        headers: &'b mut [(&'b str, &'b str)],
+        header_filter: Option<&[S]>,
+        is_head: bool,
+    ) -> Result<ResponseRef<'b, C>, Error> {
+        let mut filled = 0;
+        let head_length = loop {
+            if let Some(length) = head_length(&buf[..filled]) {
+                let status_code =
+                    str::from_utf8(&buf[..length]).map_or(0, |head| parse_status_line(head).0);
+                // 101 Switching Protocols is the final response of an upgrade.
+                if !(100..200).contains(&status_code) || status_code == 101 {
+                    break length;
+                }
+                log::trace!("Skipping informational response {}.", status_code);
+                buf.copy_within(length..filled, 0);
+                filled -= length;
+                continue;
+            }
+            if filled == buf.len() {
+                return Err(Error::BufferTooSmall);
+            }
+            match stream.read(&mut buf[filled..]).await? {
+                // Like ResponseLazy, make do with what was received.
+                0 => break filled,
+                length => filled += length,
+            }
+        };
+
+        let (head, rest) = buf.split_at_mut(head_length);
+        for line in head.split_mut(|&byte| byte == b'\n').skip(1) {
+            if let Some(colon) = line.iter().position(|&byte| byte == b':') {
+                line[..colon].make_ascii_lowercase();
+            }
+        }
+        let head: &'b [u8] = head;
+        let head = str::from_utf8(head).map_err(|_| Error::InvalidUtf8InResponse)?;
+
+        let mut lines = head
+            .split('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line));
+        let (status_code, reason_phrase) = parse_status_line(lines.next().unwrap_or(""));
+        let mut count = 0;
+        for line in lines.take_while(|line| !line.is_empty()) {
+            if let Some((name, value)) = line.split_once(':') {
+                if !headers::is_kept(header_filter, name) {
+                    continue;
+                }
+                let value = value.strip_prefix(' ').unwrap_or(value);
+                *headers.get_mut(count).ok_or(Error::BufferTooSmall)? = (name, value);
+                count += 1;
+            }
+        }
+        let (headers, _) = headers.split_at_mut(count);
+        let headers: &'b [(&'b str, &'b str)] = headers;
+
+        let has_body = !is_head && status_code != 204 && status_code != 304 && status_code >= 200;
+        let state = if !has_body {
+            HttpStreamState::ContentLength(0)
+        } else if header(headers, "transfer-encoding")
+            .is_some_and(|value| value.trim().eq_ignore_ascii_case("chunked"))
+        {
+            HttpStreamState::Chunked(true, 0, 0)
+        } else if let Some(length) = header(headers, "content-length") {
+            match length.trim().parse() {
+                Ok(length) => HttpStreamState::ContentLength(length),
+                Err(_) => return Err(Error::MalformedContentLength),
+            }
+        } else {
+            HttpStreamState::EndOnClose
+        };
+
+        Ok(ResponseRef {
+            status_code,
+            reason_phrase,
+            headers,
+            stream,
+            buf: rest,
+            pos: 0,
+            cap: filled - head_length,
+            state,
+        })
+    }
+
+    /// Returns the status code of the response.
+    pub fn status(&self) -> StatusCode {
+        StatusCode::new(self.status_code)
+    }
+
+    /// Returns the value of the first header called `name`, compared
+    /// case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&'b str> {
+        header(self.headers, name)
+    }
+
+    /// Returns how much of the body is left before the end of the body
+    /// or of the current chunk, first reading the framing up to the
+    /// next chunk's data if the current chunk is finished.
+    async fn available(&mut self) -> Result<usize, Error> {
+        if let HttpStreamState::Chunked(true, 0, read) = self.state {
+            // Every chunk but the first is preceded by the \r\n ending
+            // the previous one.
+            if read > 0 && !self.read_line().await?.is_empty() {
+                return Err(Error::MalformedChunkEnd);
+            }
+            let line = self.read_line().await?;
+            let length = str::from_utf8(&self.buf[line])
+                .map_err(|_| Error::MalformedChunkLength)?
+                .split(';')
+                .next()
+                .unwrap_or("")
+                .trim();
+            let length = match length {
+                "" => 0,
+                length => {
+                    usize::from_str_radix(length, 16).map_err(|_| Error::MalformedChunkLength)?
+                }
+            };
+            if length == 0 {
+                // Skip the trailers.
+                while !self.read_line().await?.is_empty() {}
+                self.state = HttpStreamState::Chunked(false, 0, read);
+            } else {
+                self.state = HttpStreamState::Chunked(true, length, read + length);
+            }
+        }
+        Ok(match self.state {
+            HttpStreamState::EndOnClose => usize::MAX,
+            HttpStreamState::ContentLength(remaining)
+            | HttpStreamState::Chunked(_, remaining, _) => remaining,
+        })
+    }
+
+    /// Returns the position of the next line in the buffer, without
+    /// its `\n` or `\r\n`. At the end of the stream, the rest of the
+    /// buffer is returned.
+    async fn read_line(&mut self) -> Result<Range<usize>, Error> {
+        loop {
+            let buffered = &self.buf[self.pos..self.cap];
+            if let Some(newline) = buffered.iter().position(|&byte| byte == b'\n') {
+                let start = self.pos;
+                let end = match buffered[..newline].last() {
+                    Some(b'\r') => start + newline - 1,
+                    _ => start + newline,
+                };
+                self.pos += newline + 1;
+                return Ok(start..end);
+            }
+            if !self.fill().await? {
+                let line = self.pos..self.cap;
+                self.pos = self.cap;
+                return Ok(line);
+            }
+        }
+    }
+
+    /// Moves the unread bytes to the start of the buffer, and reads
+    /// more after them. Returns `false` at the end of the stream.
+    async fn fill(&mut self) -> Result<bool, Error> {
+        self.buf.copy_within(self.pos..self.cap, 0);
+        self.cap -= self.pos;
+        self.pos = 0;
+        if self.cap == self.buf.len() {
+            return Err(Error::BufferTooSmall);
+        }
+        let length = self.stream.read(&mut self.buf[self.cap..]).await?;
+        self.cap += length;
+        Ok(length > 0)
+    }
+}
+
+impl<C> fmt::Debug for ResponseRef<'_, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseRef")
+            .field("status_code", &self.status_code)
+            .field("reason_phrase", &self.reason_phrase)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C> ErrorType for ResponseRef<'_, C> {
+    type Error = Error;
+}
+
+impl<C: Read> Read for ResponseRef<'_, C>
+where
+    Error: From<C::Error>,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let available = self.available().await?;
+        if available == 0 {
+            return Ok(0);
+        }
+        if self.pos == self.cap && !self.fill().await? {
+            return match self.state {
+                HttpStreamState::EndOnClose => Ok(0),
+                _ => Err(Error::IncompleteBody),
+            };
+        }
+        let length = (self.cap - self.pos).min(available).min(buf.len());
+        buf[..length].copy_from_slice(&self.buf[self.pos..self.pos + length]);
+        self.pos += length;
+        if let HttpStreamState::ContentLength(ref mut remaining)
+        | HttpStreamState::Chunked(_, ref mut remaining, _) = self.state
+        {
+            *remaining -= length;
+        }
+        Ok(length)
+    }
+}
+
+/// Writes into a fixed buffer, failing when it is full.
+pub(crate) struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> SliceWriter<'a> {
+    pub(crate) fn new(buf: &'a mut [u8]) -> SliceWriter<'a> {
+        SliceWriter { buf, len: 0 }
+    }
+
+    pub(crate) fn written(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+impl fmt::Write for SliceWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Returns the length of the head at the start of `data`, up to and
+/// including the empty line ending it, if it is complete.
+fn head_length(data: &[u8]) -> Option<usize> {
+    let mut line_start = 0;
+    for (i, &byte) in data.iter().enumerate() {
+        if byte == b'\n' {
+            let line = &data[line_start..i];
+            if line_start > 0 && (line.is_empty() || line == b"\r") {
+                return Some(i + 1);
+            }
+            line_start = i + 1;
+        }
+    }
+    None
+}
+
+fn parse_status_line(line: &str) -> (i32, &str) {
+    // sample status line format
+    // HTTP/1.1 200 OK
+    let mut parts = line.splitn(3, ' ').skip(1);
+    match parts.next().map(str::parse) {
+        Some(Ok(status_code)) => (status_code, parts.next().unwrap_or("")),
+        _ => (503, "Server did not provide a status line"),
+    }
+}
+
+fn header<'b>(headers: &[(&'b str, &'b str)], name: &str) -> Option<&'b str> {
+    headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|&(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResponseRef;
+    use crate::http::test_support::{block_on, Stream};
+    use crate::http::Error;
+    use embedded_io_async::Read;
+
+    #[test]
+    fn reads_response_in_place() {
+        let data = b"HTTP/1.1 100 Continue\r\n\r\n\
+                     HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nTransfer-Encoding: chunked\r\n\r\n\
+                     5;ext=1\r\nhello\r\n7\r\n, world\r\n0\r\nx-sum: 1\r\n\r\n";
+        let mut buf = [0; 128];
+        let mut headers = [("", ""); 4];
+        let mut body = [0; 16];
+        let length = block_on(async {
+            let stream = Stream::new(data, 5);
+            let mut response =
+                ResponseRef::from_stream(stream, &mut buf, &mut headers, None::<&[&str]>, false)
+                    .await?;
+            assert_eq!(response.status_code, 200);
+            assert_eq!(response.reason_phrase, "OK");
+            assert_eq!(response.headers.len(), 2);
+            assert_eq!(response.header("Content-Type"), Some("text/plain"));
+            let mut length = 0;
+            loop {
+                match response.read(&mut body[length..]).await? {
+                    0 => break,
+                    read => length += read,
+                }
+            }
+            Ok::<_, Error>(length)
+        })
+        .unwrap();
+        assert_eq!(&body[..length], b"hello, world");
+    }
+
+    fn header_count<'b>(
+        data: &[u8],
+        buf: &'b mut [u8],
+        headers: &'b mut [(&'b str, &'b str)],
+    ) -> Result<usize, Error> {
+        block_on(async {
+            let stream = Stream::new(data, 64);
+            let response =
+                ResponseRef::from_stream(stream, buf, headers, None::<&[&str]>, false).await?;
+            Ok(response.headers.len())
+        })
+    }
+
+    #[test]
+    fn fails_when_buffers_are_too_small() {
+        let data = b"HTTP/1.1 200 OK\r\nA: 1\r\nB: 2\r\nContent-Length: 0\r\n\r\n";
+        assert_eq!(
+            header_count(data, &mut [0; 64], &mut [("", ""); 3]).unwrap(),
+            3
+        );
+        assert!(matches!(
+            header_count(data, &mut [0; 64], &mut [("", ""); 2]),
+            Err(Error::BufferTooSmall)
+        ));
+        assert!(matches!(
+            header_count(data, &mut [0; 32], &mut [("", ""); 3]),
+            Err(Error::BufferTooSmall)
+        ));
+    }
+}
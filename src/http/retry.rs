@@ -0,0 +1,309 @@
+use crate::buf_reader::BufReader;
+use crate::http::date::parse_http_date;
+use crate::http::{Error, Request, Response, ResponseLazy};
+use crate::timer;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::future::Future;
+use embedded_io_async::Read;
+
+/// Configures how failed requests are retried.
+///
+/// Requests are retried when connecting or reading fails with an
+/// [`IoError`](enum.Error.html#variant.IoError), or when the server
+/// responds with one of the retryable status codes (429, 502, 503
+/// and 504 by default). Between attempts, the policy waits with an
+/// exponential backoff, or as long as the server asked for with the
+/// `Retry-After` header.
+///
+/// Only idempotent methods (`GET`, `HEAD`, `PUT`, `DELETE`,
+/// `OPTIONS` and `TRACE`) are retried, unless an idempotency key
+/// header is configured with
+/// [`with_idempotency_key`](#method.with_idempotency_key). Every
+/// attempt sends the request body again, which is always possible
+/// for bodies set with [`Request::with_body`].
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn main() -> Result<(), esp_minreq::Error> {
+/// let policy = esp_minreq::RetryPolicy::new(3).with_backoff(500, 10_000);
+/// let response = esp_minreq::get("http://example.com")
+///     .with_retry_policy(policy)
+///     .send::<esp_minreq::tcp::HttpStream>()
+///     .await?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RetryPolicy {
+    pub(crate) max_retries: u32,
+    base_delay_ms: u32,
+    max_delay_ms: u32,
+    jitter: bool,
+    retry_statuses: Vec<i32>,
+    idempotency_key_header: Option<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(3)
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a new `RetryPolicy` which retries a request at most
+    /// `max_retries` times after the first attempt.
+    ///
+    /// The backoff starts at 1 second and is capped at 30 seconds,
+    /// with jitter enabled.
+    pub fn new(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            base_delay_ms: 1_000,
+            max_delay_ms: 30_000,
+            jitter: true,
+            retry_statuses: Vec::from([429, 502, 503, 504]),
+            idempotency_key_header: None,
+        }
+    }
+
+    /// Sets the delay before the first retry, and the maximum delay
+    /// between two attempts, in milliseconds. The delay doubles after
+    /// every attempt.
+    ///
+    /// `max_delay_ms` also caps the `Retry-After` delay: if the
+    /// server asks for a longer wait, its response is returned
+    /// instead of retrying.
+    pub fn with_backoff(mut self, base_delay_ms: u32, max_delay_ms: u32) -> RetryPolicy {
+        self.base_delay_ms = base_delay_ms;
+        self.max_delay_ms = max_delay_ms.max(base_delay_ms);
+        self
+    }
+
+    /// Enables or disables randomizing the backoff delays. Jitter
+    /// prevents a fleet of devices from retrying in lockstep, and is
+    /// enabled by default.
+    pub fn with_jitter(mut self, jitter: bool) -> RetryPolicy {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets the status codes which cause a retry.
+    pub fn with_retry_statuses<T: Into<Vec<i32>>>(mut self, statuses: T) -> RetryPolicy {
+        self.retry_statuses = statuses.into();
+        self
+    }
+
+    /// Allows retrying non-idempotent methods, such as `POST`, by
+    /// sending a random idempotency key in the given header (for
+    /// example `Idempotency-Key`). The same key is sent with every
+    /// attempt, so the server can recognize repeated requests. If the
+    /// request already has this header, its value is kept.
+    pub fn with_idempotency_key<T: Into<String>>(mut self, header: T) -> RetryPolicy {
+        self.idempotency_key_header = Some(header.into());
+        self
+    }
+
+    /// Returns how long to wait before attempt number `attempt + 1`.
+    pub(crate) fn backoff_ms(&self, attempt: u32) -> u32 {
+        let delay = self
+            .base_delay_ms
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max_delay_ms);
+        if self.jitter && delay > 1 {
+            delay / 2 + timer::random_u32() % (delay / 2 + 1)
+        } else {
+            delay
+        }
+    }
+
+    /// Returns the delay requested by the server, or `None` if it
+    /// didn't ask for one.
+    fn retry_after_ms(&self, value: &str) -> Option<u32> {
+        let seconds = match value.trim().parse::<u64>() {
+            Ok(seconds) => seconds,
+            Err(_) => parse_http_date(value)?.saturating_sub(timer::unix_time()?),
+        };
+        Some(seconds.saturating_mul(1000).min(u32::MAX as u64) as u32)
+    }
+
+    pub(crate) async fn send<T, F, Fut>(
+        &self,
+        mut request: Request,
+        mut send: F,
+    ) -> Result<T, Error>
+    where
+        T: RetryableResponse,
+        F: FnMut(Request) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        if let Some(ref header) = self.idempotency_key_header {
+            let has_key = request
+                .headers
+                .keys()
+                .any(|k| k.eq_ignore_ascii_case(header));
+            if !has_key {
+                let key = format!(
+                    "{:08x}{:08x}{:08x}{:08x}",
+                    timer::random_u32(),
+                    timer::random_u32(),
+                    timer::random_u32(),
+                    timer::random_u32()
+                );
+                request.headers.insert(header.clone(), key);
+            }
+        } else if !request.method.is_idempotent() {
+            return send(request).await;
+        }
+
+        let mut attempt = 0;
+        loop {
+            let result = send(request.clone()).await;
+            if attempt >= self.max_retries {
+                return result;
+            }
+            let delay = match result {
+                Ok(ref response) if self.retry_statuses.contains(&response.status_code()) => {
+                    match response.retry_after().and_then(|v| self.retry_after_ms(v)) {
+                        Some(delay) if delay > self.max_delay_ms => return result,
+                        Some(delay) => delay,
+                        None => self.backoff_ms(attempt),
+                    }
+                }
+                Err(Error::IoError(_)) => self.backoff_ms(attempt),
+                _ => return result,
+            };
+            match result {
+                Ok(ref response) => log::debug!(
+                    "Retrying after status {} in {} ms.",
+                    response.status_code(),
+                    delay
+                ),
+                Err(ref err) => log::debug!("Retrying after error ({}) in {} ms.", err, delay),
+            }
+            drop(result);
+            timer::delay_ms(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// The parts of a response needed to decide whether to retry.
+pub(crate) trait RetryableResponse {
+    fn status_code(&self) -> i32;
+    fn retry_after(&self) -> Option<&str>;
+}
+
+impl RetryableResponse for Response {
+    fn status_code(&self) -> i32 {
+        self.status_code
+    }
+
+    fn retry_after(&self) -> Option<&str> {
+        self.headers.get("retry-after").map(String::as_str)
+    }
+}
+
+impl<R: Read> RetryableResponse for ResponseLazy<BufReader<R>> {
+    fn status_code(&self) -> i32 {
+        self.status_code
+    }
+
+    fn retry_after(&self) -> Option<&str> {
+        self.headers.get("retry-after").map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetryPolicy;
+    use crate::http::test_support::{block_on, Stream};
+    use crate::http::Method;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    const UNAVAILABLE: &[u8] = b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n";
+    const OK: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy::new(2).with_backoff(1, 1).with_jitter(false)
+    }
+
+    fn send(method: Method, policy: RetryPolicy) -> i32 {
+        let request = crate::Request::new(method, "http://example.com").with_retry_policy(policy);
+        block_on(request.send::<Stream>()).unwrap().status_code
+    }
+
+    #[test]
+    fn retries_unavailable_server() {
+        Stream::script(Stream::new(UNAVAILABLE, 64));
+        Stream::script(Stream::new(OK, 64));
+        assert_eq!(send(Method::Get, policy()), 200);
+        assert_eq!(Stream::connected_urls().len(), 2);
+    }
+
+    #[test]
+    fn retries_io_errors() {
+        Stream::script_connect_error();
+        Stream::script(Stream::new(OK, 64));
+        assert_eq!(send(Method::Get, policy()), 200);
+        assert_eq!(Stream::connected_urls().len(), 2);
+    }
+
+    #[test]
+    fn does_not_retry_post_without_idempotency_key() {
+        Stream::script(Stream::new(UNAVAILABLE, 64));
+        assert_eq!(send(Method::Post, policy()), 503);
+        assert_eq!(Stream::connected_urls().len(), 1);
+    }
+
+    #[test]
+    fn sends_same_idempotency_key_on_every_attempt() {
+        Stream::script(Stream::new(UNAVAILABLE, 64));
+        Stream::script(Stream::new(OK, 64));
+        let policy = policy().with_idempotency_key("Idempotency-Key");
+        assert_eq!(send(Method::Post, policy), 200);
+        let keys: Vec<String> = Stream::sent()
+            .iter()
+            .map(|request| {
+                let line = request
+                    .lines()
+                    .find(|line| line.starts_with("Idempotency-Key:"));
+                String::from(line.unwrap())
+            })
+            .collect();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0], keys[1]);
+    }
+
+    #[test]
+    fn returns_response_asking_for_longer_wait() {
+        Stream::script(Stream::new(
+            b"HTTP/1.1 503 Service Unavailable\r\nRetry-After: 120\r\nContent-Length: 0\r\n\r\n",
+            64,
+        ));
+        let policy = policy().with_backoff(1, 1_000);
+        assert_eq!(send(Method::Get, policy), 503);
+        assert_eq!(Stream::connected_urls().len(), 1);
+    }
+
+    #[test]
+    fn backoff_is_exponential_and_capped() {
+        let policy = RetryPolicy::new(10)
+            .with_backoff(100, 1_000)
+            .with_jitter(false);
+        assert_eq!(policy.backoff_ms(0), 100);
+        assert_eq!(policy.backoff_ms(1), 200);
+        assert_eq!(policy.backoff_ms(3), 800);
+        assert_eq!(policy.backoff_ms(4), 1_000);
+        assert_eq!(policy.backoff_ms(40), 1_000);
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.retry_after_ms("120"), Some(120_000));
+        assert_eq!(policy.retry_after_ms("soon"), None);
+    }
+}
@@ -0,0 +1,459 @@
+use crate::buf_reader::BufReader;
+use crate::http::cancel::cancellable;
+use crate::http::{Error, Request, ResponseLazy};
+use crate::tcp::HttpConnect;
+use crate::timer;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::mem;
+
+// The stream is read in pieces of this size.
+const SSE_READ_LENGTH: usize = 256;
+const DEFAULT_MAX_EVENT_SIZE: usize = 16 * 1024;
+const DEFAULT_RETRY_MS: u32 = 3000;
+
+/// An event received by an [`EventSource`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Event {
+    /// The type of the event, from the `event` field, or `message`
+    /// if it had none.
+    pub event: String,
+    /// The data of the event: its `data` fields, joined with `\n`.
+    pub data: String,
+    /// The last event ID the server sent with an `id` field, at this
+    /// event or before, if any.
+    pub id: Option<String>,
+}
+
+/// Parses an event stream, see the [HTML Standard section
+/// 9.2.6](https://html.spec.whatwg.org/multipage/server-sent-events.html#event-stream-interpretation).
+struct EventParser {
+    line: Vec<u8>,
+    /// Whether the last line ended with `\r`, so a `\n` right after
+    /// it does not end another one.
+    pending_cr: bool,
+    first_line: bool,
+    event_type: String,
+    data: String,
+    last_event_id: String,
+    retry_ms: Option<u32>,
+    max_event_size: usize,
+    /// Whether the rest of an oversized event is being skipped.
+    skipping: bool,
+    skipped_line: bool,
+}
+
+impl EventParser {
+    fn new(max_event_size: usize) -> EventParser {
+        EventParser {
+            line: Vec::new(),
+            pending_cr: false,
+            first_line: true,
+            event_type: String::new(),
+            data: String::new(),
+            last_event_id: String::new(),
+            retry_ms: None,
+            max_event_size,
+            skipping: false,
+            skipped_line: false,
+        }
+    }
+
+    /// Forgets the partial event of a closed connection, before
+    /// reconnecting. The last event ID is kept.
+    fn reset(&mut self) {
+        self.line.clear();
+        self.pending_cr = false;
+        self.first_line = true;
+        self.event_type.clear();
+        self.data.clear();
+        self.skipping = false;
+        self.skipped_line = false;
+    }
+
+    /// Parses `bytes` until an event is complete. Returns how many of
+    /// the bytes were used, and the event, if one was completed.
+    fn push(&mut self, bytes: &[u8]) -> (usize, Option<Result<Event, Error>>) {
+        for (i, &byte) in bytes.iter().enumerate() {
+            if mem::take(&mut self.pending_cr) && byte == b'\n' {
+                continue;
+            }
+            match byte {
+                b'\r' | b'\n' => {
+                    self.pending_cr = byte == b'\r';
+                    if let Some(event) = self.end_line() {
+                        return (i + 1, Some(Ok(event)));
+                    }
+                }
+                _ if self.skipping => self.skipped_line = true,
+                _ if self.line.len() + self.data.len() >= self.max_event_size => {
+                    log::debug!(
+                        "Skipping an event larger than {} bytes.",
+                        self.max_event_size
+                    );
+                    self.line.clear();
+                    self.data.clear();
+                    self.event_type.clear();
+                    self.skipping = true;
+                    self.skipped_line = true;
+                    return (i + 1, Some(Err(Error::EventTooLarge)));
+                }
+                _ => self.line.push(byte),
+            }
+        }
+        (bytes.len(), None)
+    }
+
+    fn end_line(&mut self) -> Option<Event> {
+        if self.skipping {
+            // The oversized event ends at the next blank line.
+            self.skipping = mem::take(&mut self.skipped_line);
+            return None;
+        }
+        let line = mem::take(&mut self.line);
+        let mut line = String::from_utf8_lossy(&line);
+        if mem::take(&mut self.first_line) {
+            if let Some(rest) = line.strip_prefix('\u{feff}') {
+                line = String::from(rest).into();
+            }
+        }
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            // A comment, eg. a keep-alive.
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (&*line, ""),
+        };
+        match field {
+            "event" => self.event_type = String::from(value),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_event_id = String::from(value),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                self.retry_ms = value.parse().ok();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        let event_type = mem::take(&mut self.event_type);
+        if self.data.is_empty() {
+            return None;
+        }
+        let mut data = mem::take(&mut self.data);
+        data.pop();
+        Some(Event {
+            event: if event_type.is_empty() {
+                String::from("message")
+            } else {
+                event_type
+            },
+            data,
+            id: Some(self.last_event_id.clone()).filter(|id| !id.is_empty()),
+        })
+    }
+}
+
+/// A client for [Server-Sent
+/// Events](https://html.spec.whatwg.org/multipage/server-sent-events.html),
+/// which reads events from a `text/event-stream` response as they
+/// arrive.
+///
+/// When the connection is closed or fails, the event source waits for
+/// the delay requested by the server with a `retry` field (3 seconds
+/// by default) and reconnects, sending the last event ID in the
+/// `Last-Event-ID` header, so the server can continue where it left
+/// off. It stops for good when the server responds with `204 No
+/// Content`.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn main() -> Result<(), esp_minreq::Error> {
+/// let request = esp_minreq::get("http://example.com/commands");
+/// let mut events = esp_minreq::EventSource::<esp_minreq::tcp::HttpStream>::new(request);
+/// while let Some(event) = events.next().await? {
+///     println!("{}: {}", event.event, event.data);
+/// }
+/// # Ok(()) }
+/// ```
+pub struct EventSource<C: HttpConnect> {
+    request: Request,
+    response: Option<ResponseLazy<BufReader<C>>>,
+    parser: EventParser,
+    retry_ms: u32,
+    reconnecting: bool,
+    closed: bool,
+    buf: [u8; SSE_READ_LENGTH],
+    start: usize,
+    end: usize,
+}
+
+impl<C: HttpConnect> EventSource<C>
+where
+    Error: From<C::Error>,
+{
+    /// Creates an event source which connects with `request` when the
+    /// first event is requested. `Accept: text/event-stream` and
+    /// `Cache-Control: no-cache` headers are added to it.
+    pub fn new(request: Request) -> EventSource<C> {
+        EventSource {
+            request: request
+                .with_header("Accept", "text/event-stream")
+                .with_header("Cache-Control", "no-cache")
+                .keep_headers(&["content-type"]),
+            response: None,
+            parser: EventParser::new(DEFAULT_MAX_EVENT_SIZE),
+            retry_ms: DEFAULT_RETRY_MS,
+            reconnecting: false,
+            closed: false,
+            buf: [0; SSE_READ_LENGTH],
+            start: 0,
+            end: 0,
+        }
+    }
+
+    /// Sets the largest event, in bytes, which is buffered. Larger
+    /// events are skipped. The default is 16 KiB.
+    pub fn with_max_event_size(mut self, max_event_size: usize) -> EventSource<C> {
+        self.parser.max_event_size = max_event_size;
+        self
+    }
+
+    /// Sets the delay before reconnecting, in milliseconds, until the
+    /// server asks for another one. The default is 3 seconds.
+    pub fn with_retry_ms(mut self, retry_ms: u32) -> EventSource<C> {
+        self.retry_ms = retry_ms;
+        self
+    }
+
+    /// Sets the last event ID to send when connecting, eg. one that
+    /// was saved before a reboot.
+    pub fn with_last_event_id<T: Into<String>>(mut self, id: T) -> EventSource<C> {
+        self.parser.last_event_id = id.into();
+        self
+    }
+
+    /// Returns the ID of the last event received, which is sent when
+    /// reconnecting. Empty if there was none.
+    pub fn last_event_id(&self) -> &str {
+        &self.parser.last_event_id
+    }
+
+    /// Returns the current delay before reconnecting, in milliseconds.
+    pub fn retry_ms(&self) -> u32 {
+        self.parser.retry_ms.unwrap_or(self.retry_ms)
+    }
+
+    /// Closes the connection. [`next`](#method.next) returns `None`
+    /// from now on.
+    pub fn close(&mut self) {
+        self.response = None;
+        self.closed = true;
+    }
+
+    /// Waits for the next event. Returns `None` once the server
+    /// responded with `204 No Content`, or after
+    /// [`close`](#method.close).
+    ///
+    /// Connection failures are not returned: the event source
+    /// reconnects instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`EventTooLarge`](enum.Error.html#variant.EventTooLarge)
+    /// if an event is larger than the limit, after which it is
+    /// skipped and reading continues with the next one.
+    ///
+    /// When connecting, returns
+    /// [`UnexpectedStatus`](enum.Error.html#variant.UnexpectedStatus)
+    /// if the response is not `200 OK`,
+    /// [`NotAnEventStream`](enum.Error.html#variant.NotAnEventStream)
+    /// if it is not a `text/event-stream`, and the errors of
+    /// [`Request::send_lazy`](struct.Request.html#method.send_lazy)
+    /// other than I/O errors. The event source is closed after these.
+    pub async fn next(&mut self) -> Result<Option<Event>, Error> {
+        loop {
+            if self.closed {
+                return Ok(None);
+            }
+            if self.start < self.end {
+                let (used, event) = self.parser.push(&self.buf[self.start..self.end]);
+                self.start += used;
+                if let Some(event) = event {
+                    return event.map(Some);
+                }
+                continue;
+            }
+
+            let Some(ref mut response) = self.response else {
+                if let Err(err) = self.connect().await {
+                    self.close();
+                    return Err(err);
+                }
+                continue;
+            };
+            match response.read_some(&mut self.buf).await {
+                Ok(0) => log::debug!("The event stream ended."),
+                Ok(length) => {
+                    self.start = 0;
+                    self.end = length;
+                    continue;
+                }
+                Err(Error::IoError(err)) => log::debug!("Reading the event stream failed: {}", err),
+                Err(Error::IncompleteBody) => log::debug!("The event stream was cut off."),
+                Err(err) => return Err(err),
+            }
+            self.response = None;
+            self.reconnecting = true;
+        }
+    }
+
+    /// Connects, after the retry delay if reconnecting. Connection
+    /// failures are retried.
+    async fn connect(&mut self) -> Result<(), Error> {
+        loop {
+            if self.reconnecting {
+                let delay = timer::delay_ms(self.retry_ms());
+                cancellable(self.request.cancellation.as_ref(), delay).await?;
+            }
+            self.reconnecting = true;
+            self.parser.reset();
+
+            let mut request = self.request.clone();
+            if !self.parser.last_event_id.is_empty() {
+                request = request.with_header("Last-Event-ID", self.parser.last_event_id.clone());
+            }
+            let response = match request.send_lazy::<C>().await {
+                Ok(response) => response,
+                Err(Error::IoError(err)) => {
+                    log::debug!("Connecting to the event stream failed: {}", err);
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+
+            match response.status_code {
+                200 => {}
+                204 => {
+                    log::debug!("The server closed the event stream.");
+                    self.closed = true;
+                    return Ok(());
+                }
+                status => return Err(Error::UnexpectedStatus(status)),
+            }
+            if !response
+                .content_type()
+                .is_some_and(|media_type| media_type.is("text/event-stream"))
+            {
+                return Err(Error::NotAnEventStream);
+            }
+            self.response = Some(response);
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, EventParser, EventSource};
+    use crate::http::test_support::{block_on, Stream};
+    use crate::http::Error;
+    use alloc::format;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    fn parse(parser: &mut EventParser, pieces: &[&[u8]]) -> Vec<Result<Event, Error>> {
+        let mut events = Vec::new();
+        for piece in pieces {
+            let mut piece = *piece;
+            while !piece.is_empty() {
+                let (used, event) = parser.push(piece);
+                piece = &piece[used..];
+                events.extend(event);
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn parses_events_split_between_pieces() {
+        let stream: &[u8] = b"\xef\xbb\xbf: keep-alive\r\nretry: 1500\r\n\r\ndata: first\r\ndata:second\r\nid: 7\r\n\r\nevent: command\ndata: reboot\rdata\r\r";
+        let expected = [
+            Event {
+                event: String::from("message"),
+                data: String::from("first\nsecond"),
+                id: Some(String::from("7")),
+            },
+            Event {
+                event: String::from("command"),
+                data: String::from("reboot\n"),
+                id: Some(String::from("7")),
+            },
+        ];
+        for piece in [1, 2, 5, stream.len()] {
+            let mut parser = EventParser::new(1024);
+            let pieces: Vec<&[u8]> = stream.chunks(piece).collect();
+            let events: Vec<Event> = parse(&mut parser, &pieces)
+                .into_iter()
+                .map(Result::unwrap)
+                .collect();
+            assert_eq!(events, expected);
+            assert_eq!(parser.retry_ms, Some(1500));
+            assert_eq!(parser.last_event_id, "7");
+        }
+    }
+
+    #[test]
+    fn skips_oversized_events() {
+        let mut parser = EventParser::new(16);
+        let events = parse(
+            &mut parser,
+            &[b"data: 0123456789\ndata: 0123456789\n\ndata: ok\n\n"],
+        );
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], Err(Error::EventTooLarge)));
+        assert_eq!(events[1].as_ref().unwrap().data, "ok");
+    }
+
+    fn event_stream(body: &str) -> Stream {
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        Stream::new(response.as_bytes(), 64)
+    }
+
+    #[test]
+    fn reconnects_with_last_event_id() {
+        Stream::script(event_stream("retry: 1\nid: 7\ndata: first\n\n"));
+        Stream::script(event_stream("data: second\n\n"));
+        Stream::script(Stream::new(b"HTTP/1.1 204 No Content\r\n\r\n", 64));
+        let mut events = EventSource::<Stream>::new(crate::get("http://example.com/events"));
+        let data: Vec<String> = block_on(async {
+            let mut data = Vec::new();
+            while let Some(event) = events.next().await? {
+                data.push(event.data);
+            }
+            Ok::<_, Error>(data)
+        })
+        .unwrap();
+        assert_eq!(data, ["first", "second"]);
+        assert_eq!(events.retry_ms(), 1);
+
+        let sent = Stream::sent();
+        assert_eq!(sent.len(), 3);
+        assert!(!sent[0].contains("Last-Event-ID"));
+        assert!(sent[1].contains("\r\nLast-Event-ID: 7\r\n"));
+        assert!(sent[2].contains("\r\nLast-Event-ID: 7\r\n"));
+    }
+}
@@ -0,0 +1,176 @@
+use core::fmt;
+
+/// The status code of a response, eg. `404`, see [RFC 9110 section
+/// 15](https://datatracker.ietf.org/doc/html/rfc9110#section-15).
+///
+/// Returned by
+/// [`Response::status`](struct.Response.html#method.status) and
+/// [`ResponseLazy::status`](struct.ResponseLazy.html#method.status).
+/// It compares equal to the plain number, so
+/// `response.status() == 404` works.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct StatusCode(u16);
+
+impl StatusCode {
+    /// Creates a status code from its number. Numbers outside of
+    /// `0..=65535` become 0.
+    pub fn new(code: i32) -> StatusCode {
+        StatusCode(u16::try_from(code).unwrap_or(0))
+    }
+
+    /// Returns the number of the status code.
+    pub fn as_u16(self) -> u16 {
+        self.0
+    }
+
+    /// Returns whether the status code is informational (1xx).
+    pub fn is_informational(self) -> bool {
+        (100..200).contains(&self.0)
+    }
+
+    /// Returns whether the status code is successful (2xx).
+    pub fn is_success(self) -> bool {
+        (200..300).contains(&self.0)
+    }
+
+    /// Returns whether the status code is a redirection (3xx).
+    pub fn is_redirection(self) -> bool {
+        (300..400).contains(&self.0)
+    }
+
+    /// Returns whether the status code is a client error (4xx).
+    pub fn is_client_error(self) -> bool {
+        (400..500).contains(&self.0)
+    }
+
+    /// Returns whether the status code is a server error (5xx).
+    pub fn is_server_error(self) -> bool {
+        (500..600).contains(&self.0)
+    }
+
+    /// Returns whether the status code is a client or server error
+    /// (4xx or 5xx).
+    pub fn is_error(self) -> bool {
+        self.is_client_error() || self.is_server_error()
+    }
+
+    /// Returns the reason phrase registered for the status code, eg.
+    /// "Not Found" for 404. This may differ from the
+    /// [`reason_phrase`](struct.Response.html#structfield.reason_phrase)
+    /// the server sent.
+    pub fn canonical_reason(self) -> Option<&'static str> {
+        Some(match self.0 {
+            100 => "Continue",
+            101 => "Switching Protocols",
+            102 => "Processing",
+            103 => "Early Hints",
+            200 => "OK",
+            201 => "Created",
+            202 => "Accepted",
+            203 => "Non-Authoritative Information",
+            204 => "No Content",
+            205 => "Reset Content",
+            206 => "Partial Content",
+            207 => "Multi-Status",
+            208 => "Already Reported",
+            226 => "IM Used",
+            300 => "Multiple Choices",
+            301 => "Moved Permanently",
+            302 => "Found",
+            303 => "See Other",
+            304 => "Not Modified",
+            305 => "Use Proxy",
+            307 => "Temporary Redirect",
+            308 => "Permanent Redirect",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            402 => "Payment Required",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            406 => "Not Acceptable",
+            407 => "Proxy Authentication Required",
+            408 => "Request Timeout",
+            409 => "Conflict",
+            410 => "Gone",
+            411 => "Length Required",
+            412 => "Precondition Failed",
+            413 => "Content Too Large",
+            414 => "URI Too Long",
+            415 => "Unsupported Media Type",
+            416 => "Range Not Satisfiable",
+            417 => "Expectation Failed",
+            418 => "I'm a teapot",
+            421 => "Misdirected Request",
+            422 => "Unprocessable Content",
+            423 => "Locked",
+            424 => "Failed Dependency",
+            425 => "Too Early",
+            426 => "Upgrade Required",
+            428 => "Precondition Required",
+            429 => "Too Many Requests",
+            431 => "Request Header Fields Too Large",
+            451 => "Unavailable For Legal Reasons",
+            500 => "Internal Server Error",
+            501 => "Not Implemented",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            505 => "HTTP Version Not Supported",
+            506 => "Variant Also Negotiates",
+            507 => "Insufficient Storage",
+            508 => "Loop Detected",
+            510 => "Not Extended",
+            511 => "Network Authentication Required",
+            _ => return None,
+        })
+    }
+}
+
+impl From<StatusCode> for i32 {
+    fn from(status: StatusCode) -> i32 {
+        status.0 as i32
+    }
+}
+
+impl PartialEq<i32> for StatusCode {
+    fn eq(&self, other: &i32) -> bool {
+        self.0 as i32 == *other
+    }
+}
+
+impl PartialEq<u16> for StatusCode {
+    fn eq(&self, other: &u16) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.canonical_reason() {
+            Some(reason) => write!(f, "{} {}", self.0, reason),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatusCode;
+    use alloc::string::ToString;
+
+    #[test]
+    fn classifies_status_codes() {
+        let not_found = StatusCode::new(404);
+        assert!(not_found.is_client_error() && not_found.is_error());
+        assert!(!not_found.is_success() && !not_found.is_server_error());
+        assert_eq!(not_found, 404);
+        assert_eq!(not_found.to_string(), "404 Not Found");
+        assert!(StatusCode::new(204).is_success());
+        assert!(StatusCode::new(308).is_redirection());
+        assert!(StatusCode::new(103).is_informational());
+        assert!(StatusCode::new(503).is_server_error());
+        assert_eq!(StatusCode::new(299).to_string(), "299");
+        assert_eq!(StatusCode::new(-1).as_u16(), 0);
+    }
+}
@@ -0,0 +1,130 @@
+//! Mocks and helpers shared by the tests of this module.
+
+extern crate std;
+
+use crate::http::Error;
+use crate::tcp::{ConnectionInfo, HttpConnect};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::{pin, Pin};
+use core::task::{Context, Poll, Waker};
+use embedded_io_async::{ErrorType, Read, Write};
+use esp_idf_sys::EspError;
+use std::collections::VecDeque;
+
+std::thread_local! {
+    /// The connections handed out by `connect_http` on this thread,
+    /// in order, or `None` to fail connecting.
+    static CONNECTIONS: RefCell<VecDeque<Option<Stream>>> = const { RefCell::new(VecDeque::new()) };
+    /// The URLs passed to `connect_http` on this thread.
+    static URLS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    /// What was written to the connections dropped on this thread.
+    static SENT: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Polls `future` once, with a waker which does nothing.
+pub(crate) fn poll_once<F: Future>(future: Pin<&mut F>) -> Poll<F::Output> {
+    future.poll(&mut Context::from_waker(Waker::noop()))
+}
+
+/// Polls `future` until it completes.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(output) = poll_once(future.as_mut()) {
+            return output;
+        }
+    }
+}
+
+/// A connection which returns at most `max_read` bytes of `input` per
+/// read, and records what is written to it.
+pub(crate) struct Stream {
+    pub(crate) input: Vec<u8>,
+    pub(crate) output: Vec<u8>,
+    max_read: usize,
+    info: Option<ConnectionInfo>,
+}
+
+impl Stream {
+    pub(crate) fn new(input: &[u8], max_read: usize) -> Stream {
+        Stream {
+            input: input.to_vec(),
+            output: Vec::new(),
+            max_read,
+            info: None,
+        }
+    }
+
+    /// Makes this connection report `info` from `connection_info`.
+    pub(crate) fn with_info(mut self, info: ConnectionInfo) -> Stream {
+        self.info = Some(info);
+        self
+    }
+
+    /// Makes the next call to `connect_http` on this thread return
+    /// `stream`.
+    pub(crate) fn script(stream: Stream) {
+        CONNECTIONS.with(|connections| connections.borrow_mut().push_back(Some(stream)));
+    }
+
+    /// Makes the next call to `connect_http` on this thread fail.
+    pub(crate) fn script_connect_error() {
+        CONNECTIONS.with(|connections| connections.borrow_mut().push_back(None));
+    }
+
+    /// Returns the URLs passed to `connect_http` on this thread so
+    /// far, and forgets them.
+    pub(crate) fn connected_urls() -> Vec<String> {
+        URLS.with(|urls| urls.take())
+    }
+
+    /// Returns what was written to the connections dropped on this
+    /// thread so far, and forgets it.
+    pub(crate) fn sent() -> Vec<String> {
+        SENT.with(|sent| sent.take())
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        let output = String::from_utf8_lossy(&self.output).into_owned();
+        SENT.with(|sent| sent.borrow_mut().push(output));
+    }
+}
+
+impl ErrorType for Stream {
+    type Error = Error;
+}
+
+impl Read for Stream {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let len = buf.len().min(self.input.len()).min(self.max_read);
+        buf[..len].copy_from_slice(&self.input[..len]);
+        self.input.drain(..len);
+        Ok(len)
+    }
+}
+
+impl Write for Stream {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.output.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+}
+
+impl HttpConnect for Stream {
+    async fn connect_http(url: &str, _is_plain_tcp: bool) -> Result<Self, EspError> {
+        URLS.with(|urls| urls.borrow_mut().push(url.to_string()));
+        CONNECTIONS
+            .with(|connections| connections.borrow_mut().pop_front())
+            .flatten()
+            .ok_or_else(|| EspError::from(-1).unwrap())
+    }
+
+    fn connection_info(&self) -> Option<ConnectionInfo> {
+        self.info.clone()
+    }
+}
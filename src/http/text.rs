@@ -0,0 +1,320 @@
+use crate::http::headers;
+use crate::http::{Error, ResponseLazy};
+use alloc::borrow::Cow;
+use alloc::collections::btree_map::BTreeMap as HashMap;
+use alloc::string::{String, ToString};
+use core::str;
+use embedded_io_async::{BufRead, Read};
+
+// The bytes of a body are decoded in pieces of this size.
+const TEXT_READ_LENGTH: usize = 256;
+
+/// The characters of windows-1252 in `0x80..0xa0`, where it differs
+/// from ISO-8859-1. Undefined bytes map to the C1 controls, as in the
+/// WHATWG Encoding Standard.
+const WINDOWS_1252: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+/// A character encoding of a text body, see
+/// [`Response::text`](struct.Response.html#method.text).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Charset {
+    /// UTF-8, the default when the `Content-Type` has no `charset`.
+    Utf8,
+    /// ISO-8859-1 (Latin-1), which maps each byte to the code point of
+    /// the same value.
+    Iso8859_1,
+    /// ISO-8859-15 (Latin-9), which replaces eight characters of
+    /// ISO-8859-1, eg. with `€`.
+    Iso8859_15,
+    /// Windows-1252, which adds printable characters to ISO-8859-1 in
+    /// `0x80..0xa0`. Also used for `US-ASCII`, as servers claiming
+    /// ASCII often send it.
+    Windows1252,
+}
+
+impl Charset {
+    /// Returns the charset with the given name, as used in the
+    /// `charset` parameter of a `Content-Type`, or `None` if it is not
+    /// supported. Names are case-insensitive.
+    pub fn from_label(label: &str) -> Option<Charset> {
+        let label = label.trim().to_ascii_lowercase();
+        match label.as_str() {
+            "utf-8" | "utf8" | "unicode-1-1-utf-8" => Some(Charset::Utf8),
+            "iso-8859-1" | "iso8859-1" | "iso_8859-1" | "latin1" | "l1" => Some(Charset::Iso8859_1),
+            "iso-8859-15" | "iso8859-15" | "iso_8859-15" | "latin9" | "l9" => {
+                Some(Charset::Iso8859_15)
+            }
+            "windows-1252" | "cp1252" | "x-cp1252" | "us-ascii" | "ascii" => {
+                Some(Charset::Windows1252)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the character a byte decodes to in a single-byte
+    /// charset.
+    fn decode_byte(self, byte: u8) -> char {
+        match (self, byte) {
+            (Charset::Windows1252, 0x80..=0x9f) => WINDOWS_1252[byte as usize - 0x80],
+            (Charset::Iso8859_15, 0xa4) => '€',
+            (Charset::Iso8859_15, 0xa6) => 'Š',
+            (Charset::Iso8859_15, 0xa8) => 'š',
+            (Charset::Iso8859_15, 0xb4) => 'Ž',
+            (Charset::Iso8859_15, 0xb8) => 'ž',
+            (Charset::Iso8859_15, 0xbc) => 'Œ',
+            (Charset::Iso8859_15, 0xbd) => 'œ',
+            (Charset::Iso8859_15, 0xbe) => 'Ÿ',
+            _ => byte as char,
+        }
+    }
+}
+
+/// Returns the charset of a body with the given headers. Unsupported
+/// charsets fail, unless `lossy`, in which case UTF-8 is assumed.
+pub(crate) fn charset(headers: &HashMap<String, String>, lossy: bool) -> Result<Charset, Error> {
+    match headers::content_type(headers).and_then(|media_type| media_type.parameter("charset")) {
+        None => Ok(Charset::Utf8),
+        Some(label) => match Charset::from_label(label) {
+            Some(charset) => Ok(charset),
+            None if lossy => Ok(Charset::Utf8),
+            None => Err(Error::UnsupportedCharset(label.to_string())),
+        },
+    }
+}
+
+/// Decodes a whole body.
+pub(crate) fn decode(body: &[u8], charset: Charset, lossy: bool) -> Result<Cow<'_, str>, Error> {
+    if charset == Charset::Utf8 {
+        return match str::from_utf8(body) {
+            Ok(text) => Ok(Cow::Borrowed(text)),
+            Err(_) if lossy => Ok(String::from_utf8_lossy(body)),
+            Err(err) => Err(Error::InvalidUtf8InBody(err)),
+        };
+    }
+    if body.is_ascii() {
+        // ASCII decodes to itself in all of them.
+        return Ok(Cow::Borrowed(str::from_utf8(body).unwrap_or_default()));
+    }
+    Ok(Cow::Owned(
+        body.iter().map(|&byte| charset.decode_byte(byte)).collect(),
+    ))
+}
+
+/// Decodes a body piece by piece. UTF-8 sequences split between the
+/// pieces are kept until the rest arrives.
+struct TextDecoder {
+    charset: Charset,
+    lossy: bool,
+    pending: [u8; 4],
+    pending_length: usize,
+}
+
+impl TextDecoder {
+    fn decode(&mut self, bytes: &[u8], text: &mut String) -> Result<(), Error> {
+        if self.charset != Charset::Utf8 {
+            text.extend(bytes.iter().map(|&byte| self.charset.decode_byte(byte)));
+            return Ok(());
+        }
+
+        // Complete the sequence left over from the previous piece.
+        let mut taken = 0;
+        while self.pending_length > 0 && taken < bytes.len() {
+            self.pending[self.pending_length] = bytes[taken];
+            self.pending_length += 1;
+            taken += 1;
+            match str::from_utf8(&self.pending[..self.pending_length]) {
+                Ok(char) => {
+                    text.push_str(char);
+                    self.pending_length = 0;
+                }
+                Err(err) => {
+                    let Some(invalid_length) = err.error_len() else {
+                        continue;
+                    };
+                    self.invalid(err, text)?;
+                    // The bytes after the invalid sequence were taken
+                    // from `bytes`, so they are decoded from there.
+                    taken -= self.pending_length - invalid_length;
+                    self.pending_length = 0;
+                }
+            }
+        }
+
+        let mut rest = &bytes[taken..];
+        while !rest.is_empty() {
+            match str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    break;
+                }
+                Err(err) => {
+                    let (valid, after) = rest.split_at(err.valid_up_to());
+                    text.push_str(str::from_utf8(valid).unwrap_or_default());
+                    match err.error_len() {
+                        Some(length) => {
+                            self.invalid(err, text)?;
+                            rest = &after[length..];
+                        }
+                        None => {
+                            self.pending[..after.len()].copy_from_slice(after);
+                            self.pending_length = after.len();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails on a sequence left incomplete at the end of the body.
+    fn finish(&mut self, text: &mut String) -> Result<(), Error> {
+        if self.pending_length == 0 {
+            return Ok(());
+        }
+        let pending = self.pending;
+        let length = self.pending_length;
+        self.pending_length = 0;
+        match str::from_utf8(&pending[..length]) {
+            Ok(_) => Ok(()),
+            Err(err) => self.invalid(err, text),
+        }
+    }
+
+    fn invalid(&self, err: str::Utf8Error, text: &mut String) -> Result<(), Error> {
+        if self.lossy {
+            text.push(char::REPLACEMENT_CHARACTER);
+            Ok(())
+        } else {
+            Err(Error::InvalidUtf8InBody(err))
+        }
+    }
+}
+
+/// Reads the body of a [`ResponseLazy`](struct.ResponseLazy.html) as
+/// text, piece by piece, see
+/// [`ResponseLazy::text_reader`](struct.ResponseLazy.html#method.text_reader).
+pub struct TextReader<'a, R: Read> {
+    response: &'a mut ResponseLazy<R>,
+    decoder: TextDecoder,
+    finished: bool,
+}
+
+impl<'a, R: Read + BufRead> TextReader<'a, R>
+where
+    R::Error: Into<Error>,
+{
+    pub(crate) fn new(
+        response: &'a mut ResponseLazy<R>,
+        charset: Charset,
+        lossy: bool,
+    ) -> TextReader<'a, R> {
+        TextReader {
+            response,
+            decoder: TextDecoder {
+                charset,
+                lossy,
+                pending: [0; 4],
+                pending_length: 0,
+            },
+            finished: false,
+        }
+    }
+
+    /// Returns the charset the body is decoded from.
+    pub fn charset(&self) -> Charset {
+        self.decoder.charset
+    }
+
+    /// Appends the next piece of the body to `text`, decoded into
+    /// UTF-8, as soon as it arrives. Returns the number of bytes
+    /// appended, which is 0 only at the end of the body.
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`InvalidUtf8InBody`](enum.Error.html#variant.InvalidUtf8InBody)
+    /// if a UTF-8 body is invalid, unless the reader is lossy, and the
+    /// errors of reading the body.
+    pub async fn read_into(&mut self, text: &mut String) -> Result<usize, Error> {
+        let start = text.len();
+        let mut buf = [0; TEXT_READ_LENGTH];
+        while text.len() == start && !self.finished {
+            match self.response.read_some(&mut buf).await? {
+                0 => {
+                    self.finished = true;
+                    self.decoder.finish(text)?;
+                }
+                length => self.decoder.decode(&buf[..length], text)?,
+            }
+        }
+        Ok(text.len() - start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, Charset, TextDecoder};
+    use crate::http::Error;
+    use alloc::string::String;
+
+    #[test]
+    fn decodes_single_byte_charsets() {
+        let body = b"caf\xe9 \x80 \xa4";
+        assert_eq!(
+            decode(body, Charset::Iso8859_1, false).unwrap(),
+            "café \u{80} ¤"
+        );
+        assert_eq!(
+            decode(body, Charset::Iso8859_15, false).unwrap(),
+            "café \u{80} €"
+        );
+        assert_eq!(
+            decode(body, Charset::Windows1252, false).unwrap(),
+            "café € ¤"
+        );
+        assert_eq!(Charset::from_label(" Latin1"), Some(Charset::Iso8859_1));
+        assert_eq!(Charset::from_label("shift_jis"), None);
+    }
+
+    #[test]
+    fn decodes_utf8_split_between_pieces() {
+        let body = "€uro: 1 € ✓".as_bytes();
+        for piece in 1..4 {
+            let mut decoder = TextDecoder {
+                charset: Charset::Utf8,
+                lossy: false,
+                pending: [0; 4],
+                pending_length: 0,
+            };
+            let mut text = String::new();
+            for bytes in body.chunks(piece) {
+                decoder.decode(bytes, &mut text).unwrap();
+            }
+            decoder.finish(&mut text).unwrap();
+            assert_eq!(text, "€uro: 1 € ✓");
+        }
+
+        let mut decoder = TextDecoder {
+            charset: Charset::Utf8,
+            lossy: true,
+            pending: [0; 4],
+            pending_length: 0,
+        };
+        let mut text = String::new();
+        decoder.decode(b"a\xe2\x82", &mut text).unwrap();
+        decoder.decode(b"b\xff", &mut text).unwrap();
+        decoder.decode(b"\xe2", &mut text).unwrap();
+        decoder.finish(&mut text).unwrap();
+        assert_eq!(text, "a\u{fffd}b\u{fffd}\u{fffd}");
+
+        decoder.lossy = false;
+        assert!(matches!(
+            decoder.decode(b"\xc3(", &mut text),
+            Err(Error::InvalidUtf8InBody(_))
+        ));
+    }
+}
@@ -0,0 +1,712 @@
+use crate::base64;
+use crate::buf_reader::BufReader;
+use crate::digest::Sha1;
+use crate::http::{Error, Request};
+use crate::tcp::HttpConnect;
+use crate::timer;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use embedded_io_async::{Read, ReadExactError, Write};
+
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024;
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024;
+const MAX_CONTROL_PAYLOAD: usize = 125;
+// Payloads are masked in pieces of this size while sending. Must be a
+// multiple of 4, the length of the mask.
+const MASK_CHUNK_LENGTH: usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Opcode {
+    Continuation = 0,
+    Text = 1,
+    Binary = 2,
+    Close = 8,
+    Ping = 9,
+    Pong = 10,
+}
+
+impl Opcode {
+    fn from_bits(bits: u8) -> Option<Opcode> {
+        match bits {
+            0 => Some(Opcode::Continuation),
+            1 => Some(Opcode::Text),
+            2 => Some(Opcode::Binary),
+            8 => Some(Opcode::Close),
+            9 => Some(Opcode::Ping),
+            10 => Some(Opcode::Pong),
+            _ => None,
+        }
+    }
+
+    fn is_control(self) -> bool {
+        self as u8 >= 8
+    }
+}
+
+/// The status code and reason of a closed WebSocket connection, see
+/// [RFC 6455 section
+/// 7.4](https://datatracker.ietf.org/doc/html/rfc6455#section-7.4).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CloseFrame {
+    /// The status code, eg. [`CloseFrame::NORMAL`].
+    pub code: u16,
+    /// Why the connection was closed, if the peer said.
+    pub reason: String,
+}
+
+impl CloseFrame {
+    /// The purpose of the connection has been fulfilled.
+    pub const NORMAL: u16 = 1000;
+    /// The endpoint is going away, eg. to reboot.
+    pub const GOING_AWAY: u16 = 1001;
+    /// The peer violated the WebSocket protocol.
+    pub const PROTOCOL_ERROR: u16 = 1002;
+    /// The peer sent a type of data which can't be accepted.
+    pub const UNSUPPORTED_DATA: u16 = 1003;
+    /// The peer sent a text message which is not valid UTF-8.
+    pub const INVALID_PAYLOAD: u16 = 1007;
+    /// The peer sent a frame or message larger than the limits.
+    pub const MESSAGE_TOO_BIG: u16 = 1009;
+
+    /// Creates a close frame with the given status code and reason.
+    /// The reason must fit in 123 bytes.
+    pub fn new<T: Into<String>>(code: u16, reason: T) -> CloseFrame {
+        CloseFrame {
+            code,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// A message sent or received over a [`WebSocket`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Message {
+    /// A text message, which is valid UTF-8.
+    Text(String),
+    /// A binary message.
+    Binary(Vec<u8>),
+    /// A ping, with up to 125 bytes of payload. Received pings are
+    /// answered automatically.
+    Ping(Vec<u8>),
+    /// A pong, the answer to a ping.
+    Pong(Vec<u8>),
+    /// A close frame, with the status code if one was given.
+    Close(Option<CloseFrame>),
+}
+
+/// Configures a [`WebSocket`] connection.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct WebSocketOptions {
+    protocols: Vec<String>,
+    max_frame_size: usize,
+    max_message_size: usize,
+}
+
+impl WebSocketOptions {
+    /// Creates options with no subprotocols, a max frame size of 16
+    /// KiB and a max message size of 64 KiB.
+    pub fn new() -> WebSocketOptions {
+        WebSocketOptions {
+            protocols: Vec::new(),
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+
+    /// Offers a subprotocol to the server, in order of preference. The
+    /// one the server picked is returned by
+    /// [`WebSocket::protocol`](struct.WebSocket.html#method.protocol).
+    pub fn with_protocol<T: Into<String>>(mut self, protocol: T) -> WebSocketOptions {
+        self.protocols.push(protocol.into());
+        self
+    }
+
+    /// Sets the largest frame which is received, and the size of the
+    /// frames longer messages are split into when sending.
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> WebSocketOptions {
+        self.max_frame_size = max_frame_size.max(1);
+        self
+    }
+
+    /// Sets the largest message, made of one or more frames, which is
+    /// received.
+    pub fn with_max_message_size(mut self, max_message_size: usize) -> WebSocketOptions {
+        self.max_message_size = max_message_size;
+        self
+    }
+}
+
+impl Default for WebSocketOptions {
+    fn default() -> WebSocketOptions {
+        WebSocketOptions::new()
+    }
+}
+
+/// A WebSocket client, see [RFC
+/// 6455](https://datatracker.ietf.org/doc/html/rfc6455).
+///
+/// The connection is opened with an HTTP/1.1 upgrade over any
+/// [`HttpConnect`] stream, with `ws://` or `wss://` URLs as well as
+/// `http://` and `https://` ones. Received pings are answered
+/// automatically, and a close frame from the server is echoed, after
+/// which [`receive`](#method.receive) returns `None`.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn main() -> Result<(), esp_minreq::Error> {
+/// use esp_minreq::{Message, WebSocket, WebSocketOptions};
+/// let request = esp_minreq::get("ws://example.com/control");
+/// let options = WebSocketOptions::new().with_protocol("control.v1");
+/// let mut socket =
+///     WebSocket::<esp_minreq::tcp::HttpStream>::connect(request, options).await?;
+/// socket.send_text("hello").await?;
+/// while let Some(message) = socket.receive().await? {
+///     if let Message::Text(text) = message {
+///         println!("{}", text);
+///     }
+/// }
+/// # Ok(()) }
+/// ```
+pub struct WebSocket<C: HttpConnect> {
+    stream: BufReader<C>,
+    protocol: Option<String>,
+    max_frame_size: usize,
+    max_message_size: usize,
+    /// The opcode and data of a fragmented message being received.
+    fragments: Option<(Opcode, Vec<u8>)>,
+    close_sent: bool,
+    closed: bool,
+}
+
+impl<C: HttpConnect> WebSocket<C>
+where
+    Error: From<C::Error>,
+{
+    /// Opens a WebSocket connection with `request`, usually a `GET`,
+    /// to which the handshake headers are added.
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`UnexpectedStatus`](enum.Error.html#variant.UnexpectedStatus)
+    /// if the server did not answer with `101 Switching Protocols`,
+    /// [`WebSocketHandshakeFailed`](enum.Error.html#variant.WebSocketHandshakeFailed)
+    /// if its handshake headers are wrong, and the errors of
+    /// [`Request::send_lazy`](struct.Request.html#method.send_lazy).
+    pub async fn connect(
+        mut request: Request,
+        options: WebSocketOptions,
+    ) -> Result<WebSocket<C>, Error> {
+        if let Some(rest) = request.url.strip_prefix("ws://") {
+            request.url = format!("http://{}", rest);
+        } else if let Some(rest) = request.url.strip_prefix("wss://") {
+            request.url = format!("https://{}", rest);
+        }
+
+        let mut key = [0; 16];
+        for bytes in key.chunks_mut(4) {
+            bytes.copy_from_slice(&timer::random_u32().to_le_bytes());
+        }
+        let key = base64::encode(key);
+        let mut request = request
+            .with_header("Upgrade", "websocket")
+            .with_header("Connection", "Upgrade")
+            .with_header("Sec-WebSocket-Key", key.clone())
+            .with_header("Sec-WebSocket-Version", "13")
+            .keep_headers(&[
+                "sec-websocket-accept",
+                "sec-websocket-protocol",
+                "sec-websocket-extensions",
+            ]);
+        if !options.protocols.is_empty() {
+            request = request.with_header("Sec-WebSocket-Protocol", options.protocols.join(", "));
+        }
+        #[cfg(feature = "compression")]
+        let request = request.with_decompression(false);
+
+        let response = request.send_lazy::<C>().await?;
+        if response.status_code != 101 {
+            return Err(Error::UnexpectedStatus(response.status_code));
+        }
+        let has_token = |name: &str, token: &str| {
+            response.headers.get(name).is_some_and(|value| {
+                value
+                    .split(',')
+                    .any(|value| value.trim().eq_ignore_ascii_case(token))
+            })
+        };
+        if !has_token("upgrade", "websocket") || !has_token("connection", "upgrade") {
+            log::debug!("The server did not upgrade to WebSocket.");
+            return Err(Error::WebSocketHandshakeFailed);
+        }
+        let accept = response.headers.get("sec-websocket-accept");
+        if accept.map(|accept| accept.trim()) != Some(&accept_key(&key)) {
+            log::debug!("Wrong Sec-WebSocket-Accept: {:?}", accept);
+            return Err(Error::WebSocketHandshakeFailed);
+        }
+        let protocol = response
+            .headers
+            .get("sec-websocket-protocol")
+            .map(|protocol| protocol.trim().to_string());
+        if let Some(ref protocol) = protocol {
+            if !options.protocols.contains(protocol) {
+                log::debug!("The server picked an unoffered subprotocol: {}", protocol);
+                return Err(Error::WebSocketHandshakeFailed);
+            }
+        }
+        if response.headers.contains_key("sec-websocket-extensions") {
+            log::debug!("The server picked an unoffered extension.");
+            return Err(Error::WebSocketHandshakeFailed);
+        }
+
+        Ok(WebSocket::from_stream(
+            response.into_upgraded()?,
+            protocol,
+            &options,
+        ))
+    }
+
+    fn from_stream(
+        stream: BufReader<C>,
+        protocol: Option<String>,
+        options: &WebSocketOptions,
+    ) -> WebSocket<C> {
+        WebSocket {
+            stream,
+            protocol,
+            max_frame_size: options.max_frame_size,
+            max_message_size: options.max_message_size,
+            fragments: None,
+            close_sent: false,
+            closed: false,
+        }
+    }
+
+    /// Returns the subprotocol the server picked, if any.
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+
+    /// Sends a message. Text and binary messages longer than the max
+    /// frame size are split into several frames. Sending a
+    /// [`Message::Close`] starts the close handshake, see
+    /// [`close`](#method.close).
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`WebSocketClosed`](enum.Error.html#variant.WebSocketClosed)
+    /// if a close frame was already sent,
+    /// [`MessageTooLarge`](enum.Error.html#variant.MessageTooLarge)
+    /// if a control message is longer than 125 bytes, and I/O errors.
+    pub async fn send(&mut self, message: Message) -> Result<(), Error> {
+        match message {
+            Message::Text(text) => self.send_text(&text).await,
+            Message::Binary(data) => self.send_binary(&data).await,
+            Message::Ping(data) => self.send_control(Opcode::Ping, &data).await,
+            Message::Pong(data) => self.send_control(Opcode::Pong, &data).await,
+            Message::Close(frame) => self.send_close(frame).await,
+        }
+    }
+
+    /// Sends a text message, see [`send`](#method.send).
+    pub async fn send_text(&mut self, text: &str) -> Result<(), Error> {
+        self.send_data(Opcode::Text, text.as_bytes()).await
+    }
+
+    /// Sends a binary message, see [`send`](#method.send).
+    pub async fn send_binary(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.send_data(Opcode::Binary, data).await
+    }
+
+    /// Waits for the next message. Returns `None` once the connection
+    /// is closed.
+    ///
+    /// # Errors
+    ///
+    /// Returns
+    /// [`WebSocketProtocolError`](enum.Error.html#variant.WebSocketProtocolError)
+    /// if the server violates the protocol,
+    /// [`InvalidUtf8InBody`](enum.Error.html#variant.InvalidUtf8InBody)
+    /// if a text message is not valid UTF-8,
+    /// [`MessageTooLarge`](enum.Error.html#variant.MessageTooLarge)
+    /// if a frame or message is larger than the limits,
+    /// [`WebSocketClosed`](enum.Error.html#variant.WebSocketClosed)
+    /// if the connection was closed without a close frame, and I/O
+    /// errors. The connection is closed after any of these.
+    pub async fn receive(&mut self) -> Result<Option<Message>, Error> {
+        while !self.closed {
+            let result = self.receive_frame().await;
+            if result.is_err() {
+                self.closed = true;
+            }
+            if let Some(message) = result? {
+                return Ok(Some(message));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Closes the connection: sends a close frame, unless one was
+    /// already sent, and waits for the server's, discarding the
+    /// messages received until then.
+    pub async fn close(&mut self, frame: Option<CloseFrame>) -> Result<(), Error> {
+        if !self.close_sent {
+            self.send_close(frame).await?;
+        }
+        loop {
+            match self.receive().await {
+                Ok(Some(_)) => {}
+                Ok(None) | Err(Error::WebSocketClosed) => return Ok(()),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    async fn send_data(&mut self, opcode: Opcode, data: &[u8]) -> Result<(), Error> {
+        if self.close_sent {
+            return Err(Error::WebSocketClosed);
+        }
+        let mut frames = data.chunks(self.max_frame_size).peekable();
+        if frames.peek().is_none() {
+            return self.write_frame(opcode, true, &[]).await;
+        }
+        let mut opcode = opcode;
+        while let Some(frame) = frames.next() {
+            self.write_frame(opcode, frames.peek().is_none(), frame)
+                .await?;
+            opcode = Opcode::Continuation;
+        }
+        Ok(())
+    }
+
+    async fn send_control(&mut self, opcode: Opcode, data: &[u8]) -> Result<(), Error> {
+        if self.close_sent {
+            return Err(Error::WebSocketClosed);
+        }
+        if data.len() > MAX_CONTROL_PAYLOAD {
+            return Err(Error::MessageTooLarge);
+        }
+        self.write_frame(opcode, true, data).await
+    }
+
+    async fn send_close(&mut self, frame: Option<CloseFrame>) -> Result<(), Error> {
+        if self.close_sent {
+            return Err(Error::WebSocketClosed);
+        }
+        let mut payload = Vec::new();
+        if let Some(frame) = frame {
+            payload.extend_from_slice(&frame.code.to_be_bytes());
+            payload.extend_from_slice(frame.reason.as_bytes());
+        }
+        if payload.len() > MAX_CONTROL_PAYLOAD {
+            return Err(Error::MessageTooLarge);
+        }
+        self.close_sent = true;
+        self.write_frame(Opcode::Close, true, &payload).await
+    }
+
+    /// Writes a frame, masked with a random key as clients must.
+    async fn write_frame(
+        &mut self,
+        opcode: Opcode,
+        fin: bool,
+        payload: &[u8],
+    ) -> Result<(), Error> {
+        let mut head = [0; 14];
+        head[0] = (fin as u8) << 7 | opcode as u8;
+        let mut length = 2;
+        match payload.len() {
+            len if len < 126 => head[1] = 0x80 | len as u8,
+            len if len <= 0xffff => {
+                head[1] = 0x80 | 126;
+                head[2..4].copy_from_slice(&(len as u16).to_be_bytes());
+                length = 4;
+            }
+            len => {
+                head[1] = 0x80 | 127;
+                head[2..10].copy_from_slice(&(len as u64).to_be_bytes());
+                length = 10;
+            }
+        }
+        let mask = timer::random_u32().to_le_bytes();
+        head[length..length + 4].copy_from_slice(&mask);
+        self.stream.write_all(&head[..length + 4]).await?;
+
+        let mut buf = [0; MASK_CHUNK_LENGTH];
+        for chunk in payload.chunks(MASK_CHUNK_LENGTH) {
+            for (i, (masked, byte)) in buf.iter_mut().zip(chunk).enumerate() {
+                *masked = byte ^ mask[i % 4];
+            }
+            self.stream.write_all(&buf[..chunk.len()]).await?;
+        }
+        self.stream.flush().await?;
+        Ok(())
+    }
+
+    /// Reads a frame, returning a message if it completed one.
+    async fn receive_frame(&mut self) -> Result<Option<Message>, Error> {
+        let mut head = [0; 2];
+        self.read_exact(&mut head).await?;
+        let fin = head[0] & 0x80 != 0;
+        if head[0] & 0x70 != 0 {
+            return self.fail("reserved bits set").await;
+        }
+        let Some(opcode) = Opcode::from_bits(head[0] & 0x0f) else {
+            return self.fail("unknown opcode").await;
+        };
+        if head[1] & 0x80 != 0 {
+            return self.fail("masked frame from the server").await;
+        }
+        let length = match head[1] & 0x7f {
+            126 => {
+                let mut length = [0; 2];
+                self.read_exact(&mut length).await?;
+                u16::from_be_bytes(length) as u64
+            }
+            127 => {
+                let mut length = [0; 8];
+                self.read_exact(&mut length).await?;
+                u64::from_be_bytes(length)
+            }
+            length => length as u64,
+        };
+        if opcode.is_control() && (!fin || length > MAX_CONTROL_PAYLOAD as u64) {
+            return self.fail("fragmented or long control frame").await;
+        }
+        if length > self.max_frame_size as u64 {
+            return self.fail_too_large().await;
+        }
+        let mut payload = vec![0; length as usize];
+        self.read_exact(&mut payload).await?;
+
+        match opcode {
+            Opcode::Ping => {
+                if !self.close_sent {
+                    self.write_frame(Opcode::Pong, true, &payload).await?;
+                }
+                Ok(Some(Message::Ping(payload)))
+            }
+            Opcode::Pong => Ok(Some(Message::Pong(payload))),
+            Opcode::Close => self.receive_close(&payload).await,
+            Opcode::Continuation => {
+                let Some((opcode, mut data)) = self.fragments.take() else {
+                    return self.fail("continuation without a message").await;
+                };
+                if data.len() + payload.len() > self.max_message_size {
+                    return self.fail_too_large().await;
+                }
+                data.extend_from_slice(&payload);
+                if fin {
+                    self.complete(opcode, data).await.map(Some)
+                } else {
+                    self.fragments = Some((opcode, data));
+                    Ok(None)
+                }
+            }
+            Opcode::Text | Opcode::Binary => {
+                if self.fragments.is_some() {
+                    return self.fail("new message before the last one ended").await;
+                }
+                if payload.len() > self.max_message_size {
+                    return self.fail_too_large().await;
+                }
+                if fin {
+                    self.complete(opcode, payload).await.map(Some)
+                } else {
+                    self.fragments = Some((opcode, payload));
+                    Ok(None)
+                }
+            }
+        }
+    }
+
+    async fn complete(&mut self, opcode: Opcode, data: Vec<u8>) -> Result<Message, Error> {
+        if opcode == Opcode::Binary {
+            return Ok(Message::Binary(data));
+        }
+        match String::from_utf8(data) {
+            Ok(text) => Ok(Message::Text(text)),
+            Err(err) => {
+                self.abort(CloseFrame::INVALID_PAYLOAD).await;
+                Err(Error::InvalidUtf8InBody(err.utf8_error()))
+            }
+        }
+    }
+
+    async fn receive_close(&mut self, payload: &[u8]) -> Result<Option<Message>, Error> {
+        let frame = match payload {
+            [] => None,
+            [_] => return self.fail("truncated close code").await,
+            [high, low, reason @ ..] => {
+                let code = u16::from_be_bytes([*high, *low]);
+                if !matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999) {
+                    return self.fail("invalid close code").await;
+                }
+                let Ok(reason) = core::str::from_utf8(reason) else {
+                    return self.fail("close reason is not UTF-8").await;
+                };
+                Some(CloseFrame::new(code, reason))
+            }
+        };
+        if !self.close_sent {
+            // Echo the status code, as the close handshake asks.
+            self.close_sent = true;
+            let echo = &payload[..payload.len().min(2)];
+            self.write_frame(Opcode::Close, true, echo).await?;
+        }
+        self.closed = true;
+        Ok(Some(Message::Close(frame)))
+    }
+
+    /// Closes the connection after a protocol violation.
+    async fn fail<T>(&mut self, reason: &str) -> Result<T, Error> {
+        log::debug!("WebSocket protocol error: {}", reason);
+        self.abort(CloseFrame::PROTOCOL_ERROR).await;
+        Err(Error::WebSocketProtocolError)
+    }
+
+    async fn fail_too_large<T>(&mut self) -> Result<T, Error> {
+        self.abort(CloseFrame::MESSAGE_TOO_BIG).await;
+        Err(Error::MessageTooLarge)
+    }
+
+    /// Tells the server why the connection is being closed, if
+    /// possible, without waiting for its answer.
+    async fn abort(&mut self, code: u16) {
+        self.closed = true;
+        if !self.close_sent {
+            self.close_sent = true;
+            let _ = self
+                .write_frame(Opcode::Close, true, &code.to_be_bytes())
+                .await;
+        }
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        match self.stream.read_exact(buf).await {
+            Ok(()) => Ok(()),
+            Err(ReadExactError::UnexpectedEof) => Err(Error::WebSocketClosed),
+            Err(ReadExactError::Other(err)) => Err(err.into()),
+        }
+    }
+}
+
+/// Computes the `Sec-WebSocket-Accept` the server must answer `key`
+/// with.
+fn accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(ACCEPT_GUID.as_bytes());
+    base64::encode(sha1.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{accept_key, CloseFrame, Message, WebSocket, WebSocketOptions};
+    use crate::buf_reader::BufReader;
+    use crate::http::test_support::{block_on, Stream};
+    use crate::http::Error;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    fn socket(input: &[u8], options: &WebSocketOptions) -> WebSocket<Stream> {
+        let stream = Stream::new(input, usize::MAX);
+        WebSocket::from_stream(BufReader::with_capacity(64, stream), None, options)
+    }
+
+    /// Unmasks the frames written by the client.
+    fn written_frames(socket: &WebSocket<Stream>) -> Vec<(u8, Vec<u8>)> {
+        let mut output = &socket.stream.get_ref().output[..];
+        let mut frames = Vec::new();
+        while !output.is_empty() {
+            assert_eq!(output[1] & 0x80, 0x80);
+            let length = (output[1] & 0x7f) as usize;
+            let mask = &output[2..6];
+            let payload = output[6..6 + length]
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ mask[i % 4])
+                .collect();
+            frames.push((output[0], payload));
+            output = &output[6 + length..];
+        }
+        frames
+    }
+
+    #[test]
+    fn computes_accept_key() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn receives_fragments_pings_and_close() {
+        let input = b"\x01\x03Hel\x89\x02hi\x80\x02lo\x82\x01\x00\x88\x05\x03\xe8bye";
+        let mut socket = socket(input, &WebSocketOptions::new());
+        let messages = block_on(async {
+            let mut messages = Vec::new();
+            while let Some(message) = socket.receive().await.unwrap() {
+                messages.push(message);
+            }
+            messages
+        });
+        assert_eq!(
+            messages,
+            [
+                Message::Ping(b"hi".to_vec()),
+                Message::Text(String::from("Hello")),
+                Message::Binary(b"\x00".to_vec()),
+                Message::Close(Some(CloseFrame::new(CloseFrame::NORMAL, "bye"))),
+            ]
+        );
+        assert_eq!(
+            written_frames(&socket),
+            [(0x8a, b"hi".to_vec()), (0x88, b"\x03\xe8".to_vec())]
+        );
+        assert!(matches!(
+            block_on(socket.send_text("late")),
+            Err(Error::WebSocketClosed)
+        ));
+    }
+
+    #[test]
+    fn rejects_protocol_violations() {
+        let options = WebSocketOptions::new().with_max_frame_size(4);
+        for (input, expected_code) in [
+            (&b"\x81\x02\xc3\x28"[..], CloseFrame::INVALID_PAYLOAD),
+            (b"\x82\x05hello", CloseFrame::MESSAGE_TOO_BIG),
+            (b"\x80\x01a", CloseFrame::PROTOCOL_ERROR),
+            (b"\x82\x81\x00\x00\x00\x00a", CloseFrame::PROTOCOL_ERROR),
+        ] {
+            let mut socket = socket(input, &options);
+            assert!(block_on(socket.receive()).is_err());
+            assert_eq!(
+                written_frames(&socket),
+                [(0x88, expected_code.to_be_bytes().to_vec())]
+            );
+            assert!(matches!(block_on(socket.receive()), Ok(None)));
+        }
+    }
+
+    #[test]
+    fn sends_fragmented_messages() {
+        let mut socket = socket(b"", &WebSocketOptions::new().with_max_frame_size(4));
+        block_on(socket.send_text("Hello")).unwrap();
+        block_on(socket.send(Message::Ping(Vec::new()))).unwrap();
+        assert_eq!(
+            written_frames(&socket),
+            [
+                (0x01, b"Hell".to_vec()),
+                (0x80, b"o".to_vec()),
+                (0x89, Vec::new()),
+            ]
+        );
+    }
+}
@@ -1,10 +1,17 @@
 #![no_std]
 #![feature(async_fn_in_trait)]
 extern crate alloc;
+// serde_json reads lazy bodies from `std::io::Read`ers.
+#[cfg(feature = "json")]
+extern crate std;
 
+mod base64;
 pub mod buf_reader;
 pub mod bytes_iter;
+mod digest;
 mod http;
 pub mod tcp;
+pub mod timer;
+mod waker;
 
 pub use http::*;
@@ -1,20 +1,49 @@
 use alloc::ffi::CString;
+use alloc::string::String;
+use core::ffi::CStr;
+use core::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use core::task::Poll;
 use embedded_io_async::{ErrorType, Read, Write};
 use esp_idf_hal::io::EspIOError;
 use esp_idf_sys::EspError;
 
+/// Metadata about an established connection, as reported by its
+/// [`HttpConnect`] implementation.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ConnectionInfo {
+    /// The address of the remote peer, if it is known.
+    pub remote_addr: Option<SocketAddr>,
+    /// Whether the connection is encrypted with TLS.
+    pub tls: bool,
+    /// The application protocol negotiated through ALPN, if any.
+    pub alpn: Option<String>,
+}
+
 pub trait HttpConnect: Read + Write + Sized {
     async fn connect_http(url: &str, is_plain_tcp: bool) -> Result<Self, EspError>;
+
+    /// Returns the metadata of this connection, or `None` if it is
+    /// not known. In that case, the connection is assumed to use TLS
+    /// if the URL is `https`. The default implementation returns
+    /// `None`.
+    fn connection_info(&self) -> Option<ConnectionInfo> {
+        None
+    }
 }
 
-pub struct HttpStream(*mut esp_idf_sys::esp_tls);
+pub struct HttpStream {
+    tls: *mut esp_idf_sys::esp_tls,
+    is_plain_tcp: bool,
+}
 
 impl HttpConnect for HttpStream {
     async fn connect_http(url: &str, is_plain_tcp: bool) -> Result<Self, EspError> {
-        let conn = Self(unsafe { esp_idf_sys::esp_tls_init() });
+        let conn = Self {
+            tls: unsafe { esp_idf_sys::esp_tls_init() },
+            is_plain_tcp,
+        };
         let result = {
-            let tls = conn.0;
+            let tls = conn.tls;
             core::future::poll_fn(|_ctx| {
                 let c_url = CString::new(url).unwrap();
                 let result = unsafe {
@@ -42,14 +71,25 @@ impl HttpConnect for HttpStream {
             other => Err(EspError::from(other).unwrap()),
         }
     }
+
+    fn connection_info(&self) -> Option<ConnectionInfo> {
+        Some(ConnectionInfo {
+            remote_addr: self.peer_addr(),
+            tls: !self.is_plain_tcp,
+            alpn: self.alpn_protocol(),
+        })
+    }
 }
 
 impl HttpStream {
     // It can be used as TcpStream.
     pub async fn connect(host_name: &str, port: u16, is_plain_tcp: bool) -> Result<Self, EspError> {
-        let conn = Self(unsafe { esp_idf_sys::esp_tls_init() });
+        let conn = Self {
+            tls: unsafe { esp_idf_sys::esp_tls_init() },
+            is_plain_tcp,
+        };
         let result = {
-            let tls = conn.0;
+            let tls = conn.tls;
             core::future::poll_fn(|_ctx| {
                 let c_host_name = CString::new(host_name).unwrap();
                 let result = unsafe {
@@ -79,6 +119,52 @@ impl HttpStream {
             other => Err(EspError::from(other).unwrap()),
         }
     }
+
+    /// Returns the address of the remote peer of the underlying socket.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        let mut fd = -1;
+        if unsafe { esp_idf_sys::esp_tls_get_conn_sockfd(self.tls, &mut fd) } != esp_idf_sys::ESP_OK
+        {
+            return None;
+        }
+        let mut addr: esp_idf_sys::sockaddr_storage = unsafe { core::mem::zeroed() };
+        let mut len = core::mem::size_of::<esp_idf_sys::sockaddr_storage>() as _;
+        let addr_ptr = &mut addr as *mut esp_idf_sys::sockaddr_storage;
+        if unsafe { esp_idf_sys::lwip_getpeername(fd, addr_ptr as _, &mut len) } != 0 {
+            return None;
+        }
+        match addr.ss_family as u32 {
+            esp_idf_sys::AF_INET => {
+                let addr = unsafe { &*(addr_ptr as *const esp_idf_sys::sockaddr_in) };
+                let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+                Some(SocketAddr::new(ip.into(), u16::from_be(addr.sin_port)))
+            }
+            esp_idf_sys::AF_INET6 => {
+                let addr = unsafe { &*(addr_ptr as *const esp_idf_sys::sockaddr_in6) };
+                let ip = Ipv6Addr::from(unsafe { addr.sin6_addr.un.u8_addr });
+                Some(SocketAddr::new(ip.into(), u16::from_be(addr.sin6_port)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the application protocol negotiated through ALPN during
+    /// the TLS handshake, if any.
+    pub fn alpn_protocol(&self) -> Option<String> {
+        if self.is_plain_tcp {
+            return None;
+        }
+        let ssl = unsafe { esp_idf_sys::esp_tls_get_ssl_context(self.tls) };
+        if ssl.is_null() {
+            return None;
+        }
+        let protocol = unsafe { esp_idf_sys::mbedtls_ssl_get_alpn_protocol(ssl as _) };
+        if protocol.is_null() {
+            return None;
+        }
+        let protocol = unsafe { CStr::from_ptr(protocol) };
+        Some(String::from(protocol.to_string_lossy()))
+    }
 }
 
 impl ErrorType for HttpStream {
@@ -89,7 +175,7 @@ impl embedded_io_async::Read for HttpStream {
     async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
         let result = core::future::poll_fn(|_ctx| {
             match unsafe {
-                esp_idf_sys::esp_tls_conn_read(self.0, buf.as_mut_ptr() as _, buf.len())
+                esp_idf_sys::esp_tls_conn_read(self.tls, buf.as_mut_ptr() as _, buf.len())
             } as i32
             {
                 esp_idf_sys::ESP_TLS_ERR_SSL_WANT_READ => Poll::Pending,
@@ -107,7 +193,7 @@ impl embedded_io_async::Read for HttpStream {
 impl embedded_io_async::Write for HttpStream {
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
         let result = core::future::poll_fn(|_ctx| {
-            match unsafe { esp_idf_sys::esp_tls_conn_write(self.0, buf.as_ptr() as _, buf.len()) }
+            match unsafe { esp_idf_sys::esp_tls_conn_write(self.tls, buf.as_ptr() as _, buf.len()) }
                 as i32
             {
                 esp_idf_sys::ESP_TLS_ERR_SSL_WANT_WRITE => Poll::Pending,
@@ -125,7 +211,7 @@ impl embedded_io_async::Write for HttpStream {
 impl Drop for HttpStream {
     fn drop(&mut self) {
         unsafe {
-            esp_idf_sys::esp_tls_conn_destroy(self.0);
+            esp_idf_sys::esp_tls_conn_destroy(self.tls);
         }
     }
 }
@@ -0,0 +1,164 @@
+use crate::waker::AtomicWaker;
+use alloc::sync::Arc;
+use core::ffi::c_void;
+use core::future::Future;
+use core::pin::{pin, Pin};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll};
+
+// Anything before 2020-01-01 means the system clock was never set.
+const MIN_VALID_UNIX_TIME: u64 = 1_577_836_800;
+
+/// Returns the number of milliseconds since boot.
+pub fn uptime_ms() -> u64 {
+    (unsafe { esp_idf_sys::esp_timer_get_time() } / 1000) as u64
+}
+
+/// Returns the current wall-clock time as seconds since the Unix
+/// epoch, or `None` if the system clock has not been set yet.
+pub fn unix_time() -> Option<u64> {
+    let now = unsafe { esp_idf_sys::time(core::ptr::null_mut()) } as i64;
+    if now >= MIN_VALID_UNIX_TIME as i64 {
+        Some(now as u64)
+    } else {
+        None
+    }
+}
+
+/// Sets the system clock to `ms` milliseconds since the Unix epoch,
+/// eg. from a [`ClockSync`](crate::ClockSync). Returns whether the
+/// clock was set.
+pub fn set_unix_time_ms(ms: u64) -> bool {
+    let time = esp_idf_sys::timeval {
+        tv_sec: (ms / 1000) as esp_idf_sys::time_t,
+        tv_usec: ((ms % 1000) * 1000) as esp_idf_sys::suseconds_t,
+    };
+    unsafe { esp_idf_sys::settimeofday(&time, core::ptr::null()) == 0 }
+}
+
+/// Waits for at least `ms` milliseconds without blocking the executor.
+///
+/// The task is woken by a one-shot `esp_timer`, so it is not polled
+/// again before the time is up. If no timer can be created, this
+/// falls back to polling the clock.
+pub async fn delay_ms(ms: u32) {
+    match Delay::new(ms) {
+        Some(delay) => delay.await,
+        None => {
+            let deadline = uptime_ms() + ms as u64;
+            core::future::poll_fn(|ctx| {
+                if uptime_ms() >= deadline {
+                    Poll::Ready(())
+                } else {
+                    ctx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            })
+            .await
+        }
+    }
+}
+
+/// Runs `future` for at most `ms` milliseconds. Returns `None` if it
+/// did not complete in time, in which case it is dropped.
+pub async fn timeout<F: Future>(ms: u32, future: F) -> Option<F::Output> {
+    let mut future = pin!(future);
+    let mut delay = pin!(delay_ms(ms));
+    core::future::poll_fn(|ctx| match future.as_mut().poll(ctx) {
+        Poll::Ready(output) => Poll::Ready(Some(output)),
+        Poll::Pending => delay.as_mut().poll(ctx).map(|()| None),
+    })
+    .await
+}
+
+/// The state shared by a [`Delay`] and the callback of its timer,
+/// which runs in the `esp_timer` task. That task can preempt the
+/// polling one, so neither side may wait for the other.
+struct Shared {
+    fired: AtomicBool,
+    waker: AtomicWaker,
+}
+
+/// A future which completes when a one-shot `esp_timer` fires.
+struct Delay {
+    shared: Arc<Shared>,
+    handle: esp_idf_sys::esp_timer_handle_t,
+    started: bool,
+}
+
+impl Delay {
+    /// Starts a timer firing after `ms` milliseconds, or returns
+    /// `None` if it can't be created.
+    fn new(ms: u32) -> Option<Delay> {
+        let shared = Arc::new(Shared {
+            fired: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        });
+        // The timer owns a reference, released by the callback, or by
+        // `drop` if the timer is stopped before firing.
+        let arg = Arc::into_raw(shared.clone()) as *mut c_void;
+        let args = esp_idf_sys::esp_timer_create_args_t {
+            callback: Some(on_timer),
+            arg,
+            name: c"esp-minreq delay".as_ptr(),
+            ..Default::default()
+        };
+        let mut handle = core::ptr::null_mut();
+        if unsafe { esp_idf_sys::esp_timer_create(&args, &mut handle) } != esp_idf_sys::ESP_OK {
+            unsafe { drop(Arc::from_raw(arg as *const Shared)) };
+            return None;
+        }
+        let mut delay = Delay {
+            shared,
+            handle,
+            started: false,
+        };
+        let timeout_us = ms as u64 * 1000;
+        delay.started =
+            unsafe { esp_idf_sys::esp_timer_start_once(handle, timeout_us) } == esp_idf_sys::ESP_OK;
+        // If not started, dropping `delay` releases the timer's reference.
+        delay.started.then_some(delay)
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<()> {
+        let shared = &self.shared;
+        if shared.fired.load(Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+        shared.waker.register(ctx.waker());
+        // The timer may have fired before the waker was stored.
+        if shared.fired.load(Ordering::Acquire) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Delay {
+    fn drop(&mut self) {
+        // Stopping only succeeds if the timer had not fired, in which
+        // case the callback will never release its reference.
+        if !self.started
+            || unsafe { esp_idf_sys::esp_timer_stop(self.handle) } == esp_idf_sys::ESP_OK
+        {
+            unsafe { drop(Arc::from_raw(Arc::as_ptr(&self.shared))) };
+        }
+        unsafe { esp_idf_sys::esp_timer_delete(self.handle) };
+    }
+}
+
+unsafe extern "C" fn on_timer(arg: *mut c_void) {
+    let shared = Arc::from_raw(arg as *const Shared);
+    shared.fired.store(true, Ordering::Release);
+    shared.waker.wake();
+}
+
+/// Returns a random number from the hardware random number generator.
+pub(crate) fn random_u32() -> u32 {
+    unsafe { esp_idf_sys::esp_random() }
+}
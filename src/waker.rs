@@ -0,0 +1,130 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::Waker;
+
+/// No one is storing or taking the waker.
+const WAITING: usize = 0;
+/// `register` is storing a waker.
+const REGISTERING: usize = 0b01;
+/// `wake` is taking the waker.
+const WAKING: usize = 0b10;
+
+/// A waker slot which one task registers in and another wakes from,
+/// eg. an `esp_timer` callback or an interrupt handler.
+///
+/// Neither side ever waits for the other, so it is safe to use when
+/// the waking side can preempt the registering one, as the
+/// `esp_timer` task can on a single core: if they race, `wake` leaves
+/// the wake-up to `register`, which wakes the new waker itself.
+pub(crate) struct AtomicWaker {
+    state: AtomicUsize,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+// `waker` is only accessed by the side which moved `state` away from
+// `WAITING`.
+unsafe impl Send for AtomicWaker {}
+unsafe impl Sync for AtomicWaker {}
+
+impl AtomicWaker {
+    pub(crate) const fn new() -> AtomicWaker {
+        AtomicWaker {
+            state: AtomicUsize::new(WAITING),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    /// Stores `waker` to be woken by the next call to
+    /// [`wake`](#method.wake), replacing the one stored before.
+    pub(crate) fn register(&self, waker: &Waker) {
+        match self
+            .state
+            .compare_exchange(WAITING, REGISTERING, Ordering::Acquire, Ordering::Acquire)
+            .unwrap_or_else(|state| state)
+        {
+            WAITING => {
+                let slot = unsafe { &mut *self.waker.get() };
+                let old = match slot {
+                    Some(stored) if stored.will_wake(waker) => None,
+                    _ => slot.replace(waker.clone()),
+                };
+                let woken = self.state.compare_exchange(
+                    REGISTERING,
+                    WAITING,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                );
+                if woken.is_err() {
+                    // `wake` was called meanwhile and left it to us.
+                    let waker = slot.take();
+                    self.state.store(WAITING, Ordering::Release);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+                drop(old);
+            }
+            // `wake` is running, so wake the task right away.
+            WAKING => waker.wake_by_ref(),
+            // Another `register` is running, which can only happen
+            // when two tasks share this, and one of them wins.
+            _ => {}
+        }
+    }
+
+    /// Wakes the stored waker, if any, and removes it.
+    pub(crate) fn wake(&self) {
+        if self.state.fetch_or(WAKING, Ordering::AcqRel) == WAITING {
+            let waker = unsafe { (*self.waker.get()).take() };
+            self.state.fetch_and(!WAKING, Ordering::Release);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Default for AtomicWaker {
+    fn default() -> AtomicWaker {
+        AtomicWaker::new()
+    }
+}
+
+impl fmt::Debug for AtomicWaker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AtomicWaker")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::AtomicWaker;
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::task::Waker;
+    use std::task::Wake;
+
+    struct Counter(AtomicUsize);
+
+    impl Wake for Counter {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn wakes_registered_waker_once() {
+        let counter = Arc::new(Counter(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let slot = AtomicWaker::new();
+        slot.wake();
+        slot.register(&waker);
+        slot.register(&waker);
+        slot.wake();
+        slot.wake();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    }
+}